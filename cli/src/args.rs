@@ -0,0 +1,269 @@
+//! Parsing of the command line.
+
+use std::{fmt::Display, path::PathBuf};
+
+use miette::Diagnostic;
+
+pub const USAGE: &str = "\
+usage: xlang build <file>... [-o <output>] [options]
+       xlang run <file>... [options]
+       xlang --emit=<stage> <file>... [--json]
+
+The files are compiled into one program, where a function defined in one
+file can be called from another that declares its prototype.
+
+options:
+  -o <output>          where `build` writes the executable, by default the
+                       name of the first file without its extension
+  -O0, -O1, -O2        optimisation level, -O0 by default. -O1 goes through
+                       SSA and allocates registers, -O2 is the same as -O1
+                       for now
+  --target-asm <asm>   assembler syntax to generate, only `fasm` for now
+  --fasm <path>        the fasm binary, by default the one under `fasm/`
+  --emit <stage>       print a stage and stop there, one of `tokens`, `ast`,
+                       `symbols`, `cfg`, `ssa`, `ir-opt`, `asm` or `dot`
+  --json               print the stage as JSON
+  -h, --help           print this message";
+
+#[derive(Debug, Diagnostic)]
+pub enum ArgsError {
+    #[diagnostic(code(xlang::cli::no_command), help("see `xlang --help`"))]
+    NoCommand,
+    #[diagnostic(code(xlang::cli::unknown_command), help("see `xlang --help`"))]
+    UnknownCommand(String),
+    #[diagnostic(code(xlang::cli::unknown_option), help("see `xlang --help`"))]
+    UnknownOption(String),
+    #[diagnostic(code(xlang::cli::missing_value))]
+    MissingValue(String),
+    #[diagnostic(code(xlang::cli::no_input), help("see `xlang --help`"))]
+    NoInput,
+    #[diagnostic(
+        code(xlang::cli::unknown_target_asm),
+        help("the only supported assembler is `fasm`")
+    )]
+    UnknownTargetAsm(String),
+    #[diagnostic(code(xlang::cli::output_with_run))]
+    OutputWithRun,
+    #[diagnostic(
+        code(xlang::cli::unknown_stage),
+        help("the stages are `tokens`, `ast`, `symbols`, `cfg`, `ssa`, `ir-opt`, `asm` and `dot`")
+    )]
+    UnknownStage(String),
+    #[diagnostic(
+        code(xlang::cli::unknown_opt_level),
+        help("the optimisation levels are -O0, -O1 and -O2")
+    )]
+    UnknownOptLevel(String),
+}
+
+impl Display for ArgsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgsError::NoCommand => write!(f, "no command given"),
+            ArgsError::UnknownCommand(command) => write!(f, "unknown command `{command}`"),
+            ArgsError::UnknownOption(option) => write!(f, "unknown option `{option}`"),
+            ArgsError::MissingValue(option) => write!(f, "`{option}` needs a value"),
+            ArgsError::NoInput => write!(f, "no input files"),
+            ArgsError::UnknownTargetAsm(asm) => write!(f, "unknown assembler `{asm}`"),
+            ArgsError::OutputWithRun => write!(f, "`-o` only applies to `build`"),
+            ArgsError::UnknownStage(stage) => write!(f, "unknown stage `{stage}`"),
+            ArgsError::UnknownOptLevel(level) => {
+                write!(f, "unknown optimisation level `{level}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ArgsError {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Writes an executable.
+    Build,
+    /// Builds into a temporary directory and runs the result.
+    Run,
+    Help,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TargetAsm {
+    Fasm,
+}
+
+/// A phase of compilation `--emit` can stop after, in pipeline order.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Tokens,
+    Ast,
+    Symbols,
+    Cfg,
+    /// Graphviz drawings of the lowered functions.
+    Dot,
+    Ssa,
+    /// The IR handed to the backend, after the optimisation level's passes.
+    IrOpt,
+    Asm,
+}
+
+impl Stage {
+    fn from_name(name: &str) -> Option<Stage> {
+        match name {
+            "tokens" => Some(Stage::Tokens),
+            "ast" => Some(Stage::Ast),
+            "symbols" => Some(Stage::Symbols),
+            "cfg" => Some(Stage::Cfg),
+            "dot" => Some(Stage::Dot),
+            "ssa" => Some(Stage::Ssa),
+            "ir-opt" => Some(Stage::IrOpt),
+            "asm" => Some(Stage::Asm),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Options {
+    pub command: Command,
+    pub inputs: Vec<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub opt_level: u8,
+    pub target_asm: TargetAsm,
+    pub fasm: Option<PathBuf>,
+    pub emit: Option<Stage>,
+    pub json: bool,
+}
+
+impl Options {
+    /// Parses the arguments following the program name. The command may
+    /// be left out when only a stage is emitted.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, ArgsError> {
+        let mut args = args.into_iter().peekable();
+        let first = args.peek().cloned();
+        let command = match first.as_deref() {
+            Some("build") => Some(Command::Build),
+            Some("run") => Some(Command::Run),
+            Some("help") => Some(Command::Help),
+            _ => None,
+        };
+        if command.is_some() {
+            args.next();
+        }
+        let mut options = Options {
+            command: command.unwrap_or(Command::Build),
+            inputs: Vec::new(),
+            output: None,
+            opt_level: 0,
+            target_asm: TargetAsm::Fasm,
+            fasm: None,
+            emit: None,
+            json: false,
+        };
+        let mut help = command == Some(Command::Help);
+
+        while let Some(arg) = args.next() {
+            let mut value = |option: &str| {
+                args.next()
+                    .ok_or_else(|| ArgsError::MissingValue(option.to_string()))
+            };
+            let stage = |name: String| Stage::from_name(&name).ok_or(ArgsError::UnknownStage(name));
+            match arg.as_str() {
+                "-h" | "--help" => help = true,
+                "-o" => options.output = Some(value("-o")?.into()),
+                "-O0" => options.opt_level = 0,
+                "-O1" => options.opt_level = 1,
+                "-O2" => options.opt_level = 2,
+                level if level.starts_with("-O") => {
+                    return Err(ArgsError::UnknownOptLevel(level.to_string()))
+                }
+                "--target-asm" => match value("--target-asm")?.as_str() {
+                    "fasm" => options.target_asm = TargetAsm::Fasm,
+                    asm => return Err(ArgsError::UnknownTargetAsm(asm.to_string())),
+                },
+                "--fasm" => options.fasm = Some(value("--fasm")?.into()),
+                "--emit" => options.emit = Some(stage(value("--emit")?)?),
+                "--json" => options.json = true,
+                option if option.starts_with("--emit=") => {
+                    options.emit = Some(stage(option["--emit=".len()..].to_string())?)
+                }
+                option if option.starts_with('-') && option != "-" => {
+                    return Err(ArgsError::UnknownOption(option.to_string()))
+                }
+                input => options.inputs.push(input.into()),
+            }
+        }
+
+        if help {
+            options.command = Command::Help;
+            return Ok(options);
+        }
+        match (command, first) {
+            (Some(_), _) => (),
+            _ if options.emit.is_some() => (),
+            (None, Some(first)) => return Err(ArgsError::UnknownCommand(first)),
+            (None, None) => return Err(ArgsError::NoCommand),
+        }
+        if options.inputs.is_empty() {
+            return Err(ArgsError::NoInput);
+        }
+        if options.command == Command::Run && options.output.is_some() {
+            return Err(ArgsError::OutputWithRun);
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{ArgsError, Command, Options, Stage};
+
+    fn parse(args: &str) -> Result<Options, ArgsError> {
+        Options::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn commands_and_options() {
+        let options = parse("build a.c b.c -o prog -O1 --target-asm fasm").unwrap();
+        assert_eq!(options.command, Command::Build);
+        assert_eq!(options.inputs, [PathBuf::from("a.c"), PathBuf::from("b.c")]);
+        assert_eq!(options.output, Some(PathBuf::from("prog")));
+        assert_eq!(options.opt_level, 1);
+
+        let options = parse("run main.c --fasm /opt/fasm").unwrap();
+        assert_eq!(options.command, Command::Run);
+        assert_eq!(options.opt_level, 0);
+        assert_eq!(options.fasm, Some(PathBuf::from("/opt/fasm")));
+        assert_eq!(parse("run main.c -O2").unwrap().opt_level, 2);
+
+        assert_eq!(parse("--help").unwrap().command, Command::Help);
+        assert_eq!(parse("build -h").unwrap().command, Command::Help);
+
+        let options = parse("--emit=ir-opt a.c -O1 --json").unwrap();
+        assert_eq!(options.command, Command::Build);
+        assert_eq!(options.emit, Some(Stage::IrOpt));
+        assert!(options.json);
+        assert_eq!(options.opt_level, 1);
+        assert_eq!(
+            parse("run a.c --emit tokens").unwrap().emit,
+            Some(Stage::Tokens)
+        );
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        for (args, message) in [
+            ("", "no command given"),
+            ("compile a.c", "unknown command `compile`"),
+            ("build a.c -O3", "unknown optimisation level `-O3`"),
+            ("build a.c -o", "`-o` needs a value"),
+            ("build -O1", "no input files"),
+            ("build a.c --target-asm nasm", "unknown assembler `nasm`"),
+            ("run a.c -o a", "`-o` only applies to `build`"),
+            ("a.c --emit=llvm", "unknown stage `llvm`"),
+            ("a.c", "unknown command `a.c`"),
+        ] {
+            assert_eq!(parse(args).unwrap_err().to_string(), message);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! What `--emit` prints for each stage, as text or as JSON.
+
+use language::{
+    frontend::{ast::AST, cfg::Cfg, lexer::Token, symboltable::SymbolMap},
+    ir::text::{print_instruction, print_terminator, print_value},
+};
+
+use crate::json::Json;
+
+/// One token per line.
+pub fn tokens(tokens: &[Token], json: bool) -> String {
+    if json {
+        let tokens = tokens.iter().map(|token| {
+            Json::Object(vec![
+                ("kind", token.kind.to_string().into()),
+                ("span", token.span.into()),
+            ])
+        });
+        return tokens.collect::<Json>().to_string();
+    }
+    tokens.iter().map(|token| format!("{token}\n")).collect()
+}
+
+/// The top-level items as written back by their `Display`.
+pub fn ast(items: &[AST], json: bool) -> String {
+    if json {
+        let items = items.iter().map(|item| {
+            Json::Object(vec![
+                ("span", item.span.into()),
+                ("text", item.kind.to_string().into()),
+            ])
+        });
+        return items.collect::<Json>().to_string();
+    }
+    items
+        .iter()
+        .map(|item| format!("{}\n", item.kind))
+        .collect()
+}
+
+/// Every scope with its symbols, in the order the tables were created.
+pub fn symbols(map: &SymbolMap, json: bool) -> String {
+    if !json {
+        return map.to_string();
+    }
+    let tables = map.tables().iter().enumerate().map(|(id, table)| {
+        let symbols = table.symbols().iter().map(|symbol| {
+            Json::Object(vec![
+                ("name", symbol.name().into()),
+                // Function types are written with a trailing line break.
+                ("type", symbol.get_type().to_string().trim_end().into()),
+                ("kind", symbol.kind().to_string().into()),
+                ("span", symbol.span().into()),
+            ])
+        });
+        Json::Object(vec![
+            ("id", id.into()),
+            ("parent", table.parent().into()),
+            ("children", table.children().iter().copied().collect()),
+            ("symbols", symbols.collect()),
+        ])
+    });
+    tables.collect::<Json>().to_string()
+}
+
+/// The functions in the textual IR, or block by block in JSON.
+pub fn ir(cfgs: &[Cfg], json: bool) -> String {
+    if !json {
+        return cfgs
+            .iter()
+            .map(|cfg| cfg.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+    let functions = cfgs.iter().map(|cfg| {
+        let blocks = cfg.blocks().iter().map(|block| {
+            let instrs = block.instrs().iter().map(|i| print_instruction(cfg, i));
+            Json::Object(vec![
+                ("id", block.id().to_string().into()),
+                ("instrs", instrs.collect()),
+                ("term", print_terminator(cfg, block.term()).into()),
+            ])
+        });
+        let params = cfg.params().iter().map(|&param| print_value(cfg, param));
+        Json::Object(vec![
+            ("name", cfg.name().into()),
+            ("params", params.collect()),
+            ("ret", cfg.ret_type().to_string().into()),
+            ("blocks", blocks.collect()),
+        ])
+    });
+    functions.collect::<Json>().to_string()
+}
+
+/// One Graphviz digraph per function.
+pub fn dot(cfgs: &[Cfg], json: bool) -> String {
+    if json {
+        let graphs = cfgs.iter().map(|cfg| {
+            Json::Object(vec![
+                ("name", cfg.name().into()),
+                ("dot", cfg.to_dot().into()),
+            ])
+        });
+        return graphs.collect::<Json>().to_string();
+    }
+    cfgs.iter().map(Cfg::to_dot).collect()
+}
+
+pub fn asm(asm: &str, json: bool) -> String {
+    if json {
+        return Json::Object(vec![("asm", asm.into())]).to_string();
+    }
+    asm.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use language::{
+        frontend::{lexer::Lexer, parser::parse, symboltable::SymbolMap},
+        ir::text,
+    };
+
+    use super::{ir, symbols, tokens};
+
+    #[test]
+    fn stages_as_json() {
+        let src = "int main() { int a = 1; return a; }";
+        let lexed = Lexer::new(src, 0).tokenize().unwrap();
+        assert_eq!(
+            tokens(&lexed[..1], true),
+            r#"[{"kind":"`int`","span":{"file":0,"start":0,"end":3}}]"#
+        );
+
+        let source = parse(src, 0).unwrap();
+        let mut map = SymbolMap::new();
+        map.fill_from_source(&source.0).unwrap();
+        assert!(symbols(&map, true).starts_with(
+            r#"[{"id":0,"parent":null,"children":[1],"symbols":[{"name":"main","type":"int main","#
+        ));
+
+        let cfgs = text::parse("fn f(int %x) -> int {\nbb0:\n    ret %x\n}").unwrap();
+        assert_eq!(
+            ir(&cfgs, true),
+            r#"[{"name":"f","params":["%x"],"ret":"int","blocks":[{"id":"bb0","instrs":[],"term":"ret %x"}]}]"#
+        );
+        assert_eq!(ir(&cfgs, false), text::print(&cfgs));
+    }
+}
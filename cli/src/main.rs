@@ -1,3 +1,361 @@
-fn main() {
-    println!("Hello, world!");
+mod args;
+mod emit;
+mod json;
+
+use std::{
+    fmt::Display,
+    io::{self, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use args::{Command, Options, Stage, TargetAsm, USAGE};
+use language::{
+    backend::{
+        fasm::Fasm,
+        x86_64::{self, Allocator},
+    },
+    frontend::{
+        ast::Source, cfg::Cfg, lexer::Lexer, parser::parse, span::SourceMap,
+        symboltable::SymbolMap, typeck,
+    },
+    ir::{
+        ssa::{self, SsaKind},
+        verify::verify,
+    },
+    types::layout::TargetLayout,
+};
+use miette::{Diagnostic, Report};
+
+#[derive(Debug, Diagnostic)]
+enum CliError {
+    #[diagnostic(code(xlang::cli::read))]
+    Read { path: PathBuf, error: io::Error },
+    #[diagnostic(code(xlang::cli::write))]
+    Write { path: PathBuf, error: io::Error },
+    #[diagnostic(code(xlang::cli::run))]
+    Run { path: PathBuf, error: io::Error },
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Read { path, error } => {
+                write!(f, "could not read `{}`: {error}", path.display())
+            }
+            CliError::Write { path, error } => {
+                write!(f, "could not write `{}`: {error}", path.display())
+            }
+            CliError::Run { path, error } => {
+                write!(f, "could not run `{}`: {error}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// Exit status of invalid command lines, compile errors exit with 1.
+const USAGE_ERROR: u8 = 2;
+
+fn main() -> ExitCode {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{:?}", Report::new(e));
+            return ExitCode::from(USAGE_ERROR);
+        }
+    };
+    if options.command == Command::Help {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+
+    let asm = match compile(&options) {
+        Outcome::Failed => return ExitCode::FAILURE,
+        Outcome::Emitted => return ExitCode::SUCCESS,
+        Outcome::Asm(asm) => asm,
+    };
+    let mut fasm = Fasm::new();
+    if let Some(path) = &options.fasm {
+        fasm.set_path(path);
+    }
+    let status = link(&options, &fasm, &asm);
+    let _ = std::fs::remove_dir_all(fasm.dir());
+    status
+}
+
+/// Assembles `asm`, then writes or runs the executable.
+fn link(options: &Options, fasm: &Fasm, asm: &str) -> ExitCode {
+    let output = match &options.output {
+        Some(output) => output.clone(),
+        None => PathBuf::from(options.inputs[0].file_stem().unwrap_or("a.out".as_ref())),
+    };
+    let name = output.file_name().unwrap_or("a.out".as_ref());
+    let exe = match fasm.assemble(asm, &name.to_string_lossy()) {
+        Ok(exe) => exe,
+        Err(e) => return fail(e),
+    };
+
+    match options.command {
+        Command::Build => match std::fs::copy(&exe, &output) {
+            Ok(_) => ExitCode::SUCCESS,
+            Err(error) => fail(CliError::Write {
+                path: output,
+                error,
+            }),
+        },
+        Command::Run => match std::process::Command::new(&exe).status() {
+            // Killed by a signal otherwise.
+            Ok(status) => status
+                .code()
+                .map_or(ExitCode::FAILURE, |code| ExitCode::from(code as u8)),
+            Err(error) => fail(CliError::Run { path: exe, error }),
+        },
+        Command::Help => unreachable!("handled before compiling"),
+    }
+}
+
+fn fail(e: impl Diagnostic + Send + Sync + 'static) -> ExitCode {
+    eprintln!("{:?}", Report::new(e));
+    ExitCode::FAILURE
+}
+
+/// Reads every input, the `FileId` of each is its index.
+fn read(paths: &[PathBuf]) -> Option<SourceMap> {
+    let mut files = SourceMap::new();
+    for path in paths.iter() {
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                files.add(path.display().to_string(), text);
+            }
+            Err(error) => {
+                fail(CliError::Read {
+                    path: path.clone(),
+                    error,
+                });
+                return None;
+            }
+        }
+    }
+    Some(files)
+}
+
+/// Renders `e` against the inputs its labels point into.
+fn report(files: &SourceMap, e: impl Diagnostic + Send + Sync + 'static) {
+    eprintln!("{:?}", Report::new(e).with_source_code(files.clone()));
+}
+
+/// Writes an emitted stage, which may be piped into a pager that quits
+/// before reading all of it.
+fn print(text: String) {
+    let _ = writeln!(io::stdout().lock(), "{text}");
+}
+
+/// How far `compile` got.
+enum Outcome {
+    /// The errors were reported.
+    Failed,
+    /// The stage asked for with `--emit` was printed.
+    Emitted,
+    Asm(String),
+}
+
+/// Prints `stage` and stops compilation there if `--emit` asks for it.
+macro_rules! emit {
+    ($options:expr, $stage:expr, $print:expr) => {
+        if $options.emit == Some($stage) {
+            print($print);
+            return Outcome::Emitted;
+        }
+    };
+}
+
+/// Runs every pass up to assembly generation, reporting all errors of the
+/// first stage that has any.
+fn compile(options: &Options) -> Outcome {
+    let Some(files) = read(&options.inputs) else {
+        return Outcome::Failed;
+    };
+    let layout = TargetLayout::LP64;
+    let json = options.json;
+
+    if options.emit == Some(Stage::Tokens) {
+        let mut tokens = Vec::new();
+        for file in 0..options.inputs.len() {
+            match Lexer::new(files.text(file), file).tokenize() {
+                Ok(file_tokens) => tokens.extend(file_tokens),
+                Err(e) => {
+                    report(&files, e);
+                    return Outcome::Failed;
+                }
+            }
+        }
+        print(emit::tokens(&tokens, json));
+        return Outcome::Emitted;
+    }
+
+    let mut items = Vec::new();
+    let mut failed = false;
+    for file in 0..options.inputs.len() {
+        match parse(files.text(file), file) {
+            Ok(source) => items.extend(source.0),
+            Err(e) => {
+                report(&files, e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        return Outcome::Failed;
+    }
+    let source = Source(items);
+    emit!(options, Stage::Ast, emit::ast(&source.0, json));
+
+    let mut map = SymbolMap::new();
+    if let Err(errors) = map.fill_from_source(&source.0) {
+        for e in errors {
+            report(&files, e);
+        }
+        return Outcome::Failed;
+    }
+    emit!(options, Stage::Symbols, emit::symbols(&map, json));
+    if let Err(errors) = typeck::check(&source, &map, &layout) {
+        for e in errors {
+            report(&files, e);
+        }
+        return Outcome::Failed;
+    }
+
+    let mut cfgs = Cfg::fill_from_source(&source, &map, &layout);
+    if !verified(&cfgs) {
+        return Outcome::Failed;
+    }
+    emit!(options, Stage::Cfg, emit::ir(&cfgs, json));
+    emit!(options, Stage::Dot, emit::dot(&cfgs, json));
+    if options.emit == Some(Stage::Ssa) {
+        for cfg in cfgs.iter_mut() {
+            ssa::construct(cfg, SsaKind::Pruned);
+        }
+        print(emit::ir(&cfgs, json));
+        return Outcome::Emitted;
+    }
+
+    optimise(&mut cfgs, options.opt_level);
+    if !verified(&cfgs) {
+        return Outcome::Failed;
+    }
+    emit!(options, Stage::IrOpt, emit::ir(&cfgs, json));
+    let asm = match options.target_asm {
+        TargetAsm::Fasm => x86_64::emit(&cfgs, allocator(options.opt_level), &layout),
+    };
+    match asm {
+        Ok(asm) => {
+            emit!(options, Stage::Asm, emit::asm(&asm, json));
+            Outcome::Asm(asm)
+        }
+        Err(e) => {
+            fail(e);
+            Outcome::Failed
+        }
+    }
+}
+
+/// Checks `cfgs` with the IR verifier. Everything the frontend accepts is
+/// meant to lower to valid IR, so this only fails on a compiler bug, which
+/// is then reported like any other error instead of crashing a later pass.
+fn verified(cfgs: &[Cfg]) -> bool {
+    let mut valid = true;
+    for cfg in cfgs.iter() {
+        if let Err(errors) = verify(cfg) {
+            for e in errors {
+                fail(e);
+            }
+            valid = false;
+        }
+    }
+    valid
+}
+
+/// Values stay on the stack at -O0, where the assembly is easiest to
+/// follow.
+fn allocator(opt_level: u8) -> Allocator {
+    match opt_level {
+        0 => Allocator::Stack,
+        _ => Allocator::LinearScan,
+    }
+}
+
+/// The IR passes of each optimisation level. At -O1 the functions go
+/// through SSA, where later passes will run, and back. -O2 has no passes of
+/// its own yet and runs those of -O1.
+fn optimise(cfgs: &mut [Cfg], opt_level: u8) {
+    if opt_level == 0 {
+        return;
+    }
+    for cfg in cfgs.iter_mut() {
+        ssa::construct(cfg, SsaKind::Pruned);
+        ssa::destruct(cfg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compile, Options, Outcome};
+
+    /// Compiles `srcs`, each written to a temporary file named after `name`,
+    /// with the options in `args`.
+    fn compile_srcs(name: &str, srcs: &[&str], args: &str) -> Outcome {
+        let paths: Vec<_> = (0..srcs.len())
+            .map(|i| {
+                let file = format!("xlang-{}-{name}-{i}.c", std::process::id());
+                std::env::temp_dir().join(file)
+            })
+            .collect();
+        let mut command = String::from("build");
+        for (path, src) in paths.iter().zip(srcs) {
+            std::fs::write(path, src).unwrap();
+            command.push_str(&format!(" {}", path.display()));
+        }
+        let args = format!("{command} {args}");
+        let outcome = compile(&Options::parse(args.split_whitespace().map(String::from)).unwrap());
+        for path in paths {
+            std::fs::remove_file(path).unwrap();
+        }
+        outcome
+    }
+
+    fn compile_src(name: &str, src: &str, args: &str) -> Outcome {
+        compile_srcs(name, &[src], args)
+    }
+
+    #[test]
+    fn files_call_each_other_through_prototypes() {
+        let main = "int twice(int n); int main() { return twice(21); }";
+        let twice = "int twice(int n) { return n * 2; }";
+        let Outcome::Asm(asm) = compile_srcs("files", &[main, twice], "-O1") else {
+            panic!("expected the program to compile")
+        };
+        assert!(asm.contains("call twice"));
+        assert!(matches!(
+            compile_srcs("missing", &[main], "-O1"),
+            Outcome::Failed
+        ));
+    }
+
+    #[test]
+    fn file_scope_variables_are_rejected_at_every_level() {
+        for level in ["-O0", "-O1", "-O2"] {
+            let src = "int g = 5; int main() { return g; }";
+            assert!(matches!(
+                compile_src(&format!("global{level}"), src, level),
+                Outcome::Failed
+            ));
+            let src = "int main() { int g = 5; return g; }";
+            assert!(matches!(
+                compile_src(&format!("local{level}"), src, level),
+                Outcome::Asm(_)
+            ));
+        }
+    }
 }
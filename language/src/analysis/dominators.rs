@@ -0,0 +1,372 @@
+//! Dominator and post-dominator trees over a `Cfg`, computed with the
+//! iterative algorithm of Cooper, Harvey and Kennedy.
+//!
+//! Both trees hang off a virtual root: the entry for dominators, every
+//! block without successors for post-dominators. Blocks the virtual root
+//! cannot reach (unreachable code, or loops that never exit when looking
+//! backwards) are not part of the tree.
+
+use std::collections::BTreeSet;
+
+use crate::{frontend::cfg::Cfg, ir::BlockId};
+
+pub struct DomTree {
+    post: bool,
+    roots: Vec<BlockId>,
+    /// Immediate dominator per node; index `len` is the virtual root.
+    idom: Vec<Option<usize>>,
+    /// Reachable blocks in reverse postorder of the (possibly reversed) graph.
+    rpo: Vec<BlockId>,
+    children: Vec<Vec<BlockId>>,
+    /// Preorder entry and exit numbers of the tree, for `dominates`.
+    enter: Vec<usize>,
+    exit: Vec<usize>,
+}
+
+impl DomTree {
+    pub fn dominators(cfg: &Cfg) -> Self {
+        let roots = if cfg.blocks().is_empty() {
+            Vec::new()
+        } else {
+            vec![cfg.entry()]
+        };
+        Self::compute(
+            cfg.blocks().len(),
+            roots,
+            |b| cfg.succs(b),
+            |b| cfg.preds(b),
+            false,
+        )
+    }
+
+    pub fn post_dominators(cfg: &Cfg) -> Self {
+        let roots = cfg
+            .blocks()
+            .iter()
+            .map(|block| block.id())
+            .filter(|b| cfg.succs(*b).is_empty())
+            .collect();
+        Self::compute(
+            cfg.blocks().len(),
+            roots,
+            |b| cfg.preds(b),
+            |b| cfg.succs(b),
+            true,
+        )
+    }
+
+    fn compute(
+        len: usize,
+        roots: Vec<BlockId>,
+        succs: impl Fn(BlockId) -> Vec<BlockId>,
+        preds: impl Fn(BlockId) -> Vec<BlockId>,
+        post: bool,
+    ) -> Self {
+        let virt = len;
+        let succs_of = |n: usize| -> Vec<usize> {
+            if n == virt {
+                roots.iter().map(|b| b.0).collect()
+            } else {
+                succs(BlockId(n)).into_iter().map(|b| b.0).collect()
+            }
+        };
+        let preds_of = |n: usize| -> Vec<usize> {
+            let mut preds: Vec<usize> = preds(BlockId(n)).into_iter().map(|b| b.0).collect();
+            if roots.contains(&BlockId(n)) {
+                preds.push(virt);
+            }
+            preds
+        };
+
+        let mut order = Vec::new();
+        let mut seen = vec![false; len + 1];
+        let mut stack = vec![(virt, 0)];
+        seen[virt] = true;
+        while let Some((node, next)) = stack.pop() {
+            if let Some(succ) = succs_of(node).get(next) {
+                stack.push((node, next + 1));
+                if !seen[*succ] {
+                    seen[*succ] = true;
+                    stack.push((*succ, 0));
+                }
+            } else {
+                order.push(node);
+            }
+        }
+        order.reverse();
+
+        let mut rank = vec![usize::MAX; len + 1];
+        for (i, node) in order.iter().enumerate() {
+            rank[*node] = i;
+        }
+        let mut idom: Vec<Option<usize>> = vec![None; len + 1];
+        idom[virt] = Some(virt);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for node in order.iter().skip(1) {
+                let mut new: Option<usize> = None;
+                for pred in preds_of(*node) {
+                    if idom[pred].is_none() {
+                        continue;
+                    }
+                    new = Some(match new {
+                        None => pred,
+                        Some(mut a) => {
+                            let mut b = pred;
+                            while a != b {
+                                while rank[a] > rank[b] {
+                                    a = idom[a].unwrap();
+                                }
+                                while rank[b] > rank[a] {
+                                    b = idom[b].unwrap();
+                                }
+                            }
+                            a
+                        }
+                    });
+                }
+                if new.is_some() && idom[*node] != new {
+                    idom[*node] = new;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut children = vec![Vec::new(); len + 1];
+        for node in order.iter().skip(1) {
+            children[idom[*node].unwrap()].push(BlockId(*node));
+        }
+        // Layout order makes walks follow the source.
+        for kids in children.iter_mut() {
+            kids.sort();
+        }
+
+        let mut enter = vec![usize::MAX; len + 1];
+        let mut exit = vec![usize::MAX; len + 1];
+        let mut clock = 0;
+        let mut stack = vec![(virt, 0)];
+        enter[virt] = clock;
+        while let Some((node, next)) = stack.pop() {
+            if let Some(child) = children[node].get(next) {
+                stack.push((node, next + 1));
+                clock += 1;
+                enter[child.0] = clock;
+                stack.push((child.0, 0));
+            } else {
+                exit[node] = clock;
+            }
+        }
+
+        Self {
+            post,
+            roots,
+            idom,
+            rpo: order.into_iter().skip(1).map(BlockId).collect(),
+            children,
+            enter,
+            exit,
+        }
+    }
+
+    fn virt(&self) -> usize {
+        self.idom.len() - 1
+    }
+
+    /// Whether this is the post-dominator tree.
+    pub fn is_post(&self) -> bool {
+        self.post
+    }
+
+    /// Blocks of the tree in reverse postorder. For post-dominators the
+    /// order is that of the reversed graph.
+    pub fn rpo(&self) -> &[BlockId] {
+        &self.rpo
+    }
+
+    pub fn contains(&self, block: BlockId) -> bool {
+        self.idom[block.0].is_some()
+    }
+
+    /// The immediate (post-)dominator of `block`, `None` for the roots and
+    /// for blocks outside the tree.
+    pub fn idom(&self, block: BlockId) -> Option<BlockId> {
+        self.idom[block.0]
+            .filter(|idom| *idom != self.virt())
+            .map(BlockId)
+    }
+
+    /// The entry, or every block without successors.
+    pub fn roots(&self) -> &[BlockId] {
+        &self.roots
+    }
+
+    pub fn children(&self, block: BlockId) -> &[BlockId] {
+        &self.children[block.0]
+    }
+
+    /// Whether every path from the root to `b` goes through `a`. A block
+    /// dominates itself; blocks outside the tree dominate nothing.
+    pub fn dominates(&self, a: BlockId, b: BlockId) -> bool {
+        self.contains(a)
+            && self.contains(b)
+            && self.enter[a.0] <= self.enter[b.0]
+            && self.exit[b.0] <= self.exit[a.0]
+    }
+
+    pub fn strictly_dominates(&self, a: BlockId, b: BlockId) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    /// Dominance frontier of every block: where its dominance ends. For
+    /// post-dominators this is the set of blocks it is control dependent on.
+    pub fn frontiers(&self, cfg: &Cfg) -> Vec<BTreeSet<BlockId>> {
+        let mut frontier = vec![BTreeSet::new(); cfg.blocks().len()];
+        for block in self.rpo.iter() {
+            let preds = if self.post {
+                cfg.succs(*block)
+            } else {
+                cfg.preds(*block)
+            };
+            let preds: Vec<BlockId> = preds.into_iter().filter(|p| self.contains(*p)).collect();
+            if preds.len() < 2 {
+                continue;
+            }
+            for pred in preds {
+                let mut runner = Some(pred);
+                while let Some(r) = runner.filter(|r| Some(*r) != self.idom(*block)) {
+                    frontier[r.0].insert(*block);
+                    runner = self.idom(r);
+                }
+            }
+        }
+        frontier
+    }
+
+    /// Preorder walk of the tree, children in layout order. Blocks whose
+    /// only dominator is the virtual root start a new subtree.
+    pub fn walk(&self) -> Walk<'_> {
+        Walk {
+            tree: self,
+            stack: self.children[self.virt()].iter().rev().copied().collect(),
+        }
+    }
+}
+
+pub struct Walk<'a> {
+    tree: &'a DomTree,
+    stack: Vec<BlockId>,
+}
+
+impl Iterator for Walk<'_> {
+    type Item = BlockId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let block = self.stack.pop()?;
+        self.stack
+            .extend(self.tree.children(block).iter().rev().copied());
+        Some(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        frontend::cfg::Cfg,
+        ir::{BlockId, Operand, Terminator, ValueId},
+        types::designators::TypeInstance,
+    };
+
+    use super::DomTree;
+
+    /// entry -> (then | else) -> join -> ret, plus a block nothing reaches.
+    fn diamond() -> (Cfg, [BlockId; 5]) {
+        let mut cfg = Cfg::new("f", TypeInstance::Int);
+        let c = Operand::Value(ValueId(0));
+        cfg.add_param("c", TypeInstance::Int);
+        let blocks = [(); 5].map(|_| cfg.new_block());
+        let [entry, then, _else, join, dead] = blocks;
+        cfg.set_term(entry, Terminator::Branch(c, then, _else));
+        cfg.set_term(then, Terminator::Jump(join));
+        cfg.set_term(_else, Terminator::Jump(join));
+        cfg.set_term(join, Terminator::Ret(Some(c)));
+        cfg.set_term(dead, Terminator::Jump(join));
+        (cfg, blocks)
+    }
+
+    #[test]
+    fn diamond_dominators() {
+        let (cfg, [entry, then, _else, join, dead]) = diamond();
+        let doms = DomTree::dominators(&cfg);
+
+        assert_eq!(doms.roots(), [entry]);
+        assert!(doms.idom(entry).is_none());
+        assert_eq!(doms.idom(then), Some(entry));
+        assert_eq!(doms.idom(_else), Some(entry));
+        assert_eq!(doms.idom(join), Some(entry));
+        assert!(!doms.contains(dead));
+        assert!(doms.idom(dead).is_none());
+
+        assert!(doms.dominates(entry, join));
+        assert!(doms.dominates(join, join));
+        assert!(!doms.strictly_dominates(join, join));
+        assert!(!doms.dominates(then, join));
+        assert!(!doms.dominates(dead, join));
+
+        let frontiers = doms.frontiers(&cfg);
+        assert!(frontiers[then.0].iter().eq([join].iter()));
+        assert!(frontiers[entry.0].is_empty());
+        assert!(frontiers[join.0].is_empty());
+        assert_eq!(doms.walk().collect::<Vec<_>>(), [entry, then, _else, join]);
+    }
+
+    #[test]
+    fn diamond_post_dominators() {
+        let (cfg, [entry, then, _else, join, dead]) = diamond();
+        let pdoms = DomTree::post_dominators(&cfg);
+
+        assert!(pdoms.is_post());
+        assert_eq!(pdoms.roots(), [join]);
+        assert_eq!(pdoms.idom(entry), Some(join));
+        assert_eq!(pdoms.idom(then), Some(join));
+        assert_eq!(pdoms.idom(dead), Some(join));
+        assert!(pdoms.dominates(join, entry));
+        assert!(!pdoms.dominates(then, entry));
+
+        // Both arms are control dependent on the branch in `entry`.
+        let frontiers = pdoms.frontiers(&cfg);
+        assert!(frontiers[then.0].iter().eq([entry].iter()));
+        assert!(frontiers[_else.0].iter().eq([entry].iter()));
+    }
+
+    #[test]
+    fn loops_and_multiple_exits() {
+        // entry -> head; head -> (body | exit); body -> (head | early)
+        let mut cfg = Cfg::new("f", TypeInstance::Int);
+        let c = Operand::Value(cfg.add_param("c", TypeInstance::Int));
+        let [entry, head, body, exit, early] = [(); 5].map(|_| cfg.new_block());
+        cfg.set_term(entry, Terminator::Jump(head));
+        cfg.set_term(head, Terminator::Branch(c, body, exit));
+        cfg.set_term(body, Terminator::Branch(c, head, early));
+        cfg.set_term(exit, Terminator::Ret(Some(c)));
+        cfg.set_term(early, Terminator::Ret(None));
+
+        let doms = DomTree::dominators(&cfg);
+        assert_eq!(doms.idom(body), Some(head));
+        assert_eq!(doms.idom(head), Some(entry));
+        assert_eq!(doms.idom(early), Some(body));
+        assert_eq!(doms.idom(exit), Some(head));
+        assert!(doms.dominates(head, early));
+        assert!(!doms.dominates(body, exit));
+        assert!(doms.frontiers(&cfg)[body.0].iter().eq([head].iter()));
+
+        let pdoms = DomTree::post_dominators(&cfg);
+        assert_eq!(pdoms.roots(), [exit, early]);
+        assert!(pdoms.idom(head).is_none());
+        assert_eq!(pdoms.idom(entry), Some(head));
+        assert_eq!(
+            pdoms.walk().collect::<Vec<_>>(),
+            [head, entry, body, exit, early]
+        );
+    }
+}
@@ -0,0 +1,337 @@
+//! Runs the flat assembler vendored under `fasm/` on generated assembly,
+//! turning it into an executable.
+//!
+//! Errors fasm reports point at a line of the assembly, which is handed
+//! back as a diagnostic labelling that line of the generated source.
+
+use std::{
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use miette::{Diagnostic, NamedSource, SourceSpan};
+
+#[derive(Debug, Diagnostic)]
+pub enum FasmError {
+    #[diagnostic(
+        code(xlang::fasm::not_found),
+        help("point the driver at a fasm binary with `Fasm::set_path`")
+    )]
+    Spawn { path: PathBuf, error: io::Error },
+    #[diagnostic(code(xlang::fasm::io))]
+    Io { path: PathBuf, error: io::Error },
+    #[diagnostic(code(xlang::fasm::failed))]
+    Failed {
+        message: String,
+        #[source_code]
+        asm: NamedSource<String>,
+        #[label("{message}")]
+        span: Option<SourceSpan>,
+    },
+}
+
+impl Display for FasmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FasmError::Spawn { path, error } => {
+                write!(f, "could not run fasm at `{}`: {error}", path.display())
+            }
+            FasmError::Io { path, error } => write!(f, "`{}`: {error}", path.display()),
+            FasmError::Failed { message, .. } => write!(f, "fasm failed: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for FasmError {}
+
+/// Tells apart the directories of drivers created by the same process.
+static DRIVERS: AtomicUsize = AtomicUsize::new(0);
+
+pub struct Fasm {
+    path: PathBuf,
+    dir: PathBuf,
+}
+
+impl Default for Fasm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fasm {
+    /// The vendored 64-bit fasm, writing into a fresh directory under the
+    /// system's temporary directory.
+    pub fn new() -> Self {
+        let id = DRIVERS.fetch_add(1, Ordering::Relaxed);
+        Self {
+            path: PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../fasm/fasm.x64")),
+            dir: std::env::temp_dir().join(format!("xlang-{}-{id}", std::process::id())),
+        }
+    }
+
+    /// The fasm binary to run.
+    pub fn set_path(&mut self, path: impl Into<PathBuf>) {
+        self.path = path.into();
+    }
+
+    /// Where the assembly and the executable are written.
+    pub fn set_dir(&mut self, dir: impl Into<PathBuf>) {
+        self.dir = dir.into();
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Assembles `asm` into an executable called `name` and returns its
+    /// path.
+    pub fn assemble(&self, asm: &str, name: &str) -> Result<PathBuf, FasmError> {
+        let io_error = |path: &Path| {
+            let path = path.to_path_buf();
+            move |error| FasmError::Io { path, error }
+        };
+        std::fs::create_dir_all(&self.dir).map_err(io_error(&self.dir))?;
+        let source = self.dir.join(format!("{name}.asm"));
+        let output = self.dir.join(name);
+        std::fs::write(&source, asm).map_err(io_error(&source))?;
+
+        let result = Command::new(&self.path)
+            .arg(&source)
+            .arg(&output)
+            .output()
+            .map_err(|error| FasmError::Spawn {
+                path: self.path.clone(),
+                error,
+            })?;
+        if result.status.success() {
+            return Ok(output);
+        }
+
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        let (message, line) = parse_error(&stderr);
+        let message = message.unwrap_or_else(|| format!("fasm exited with {}", result.status));
+        Err(FasmError::Failed {
+            message,
+            span: line.and_then(|line| line_span(asm, line)),
+            asm: NamedSource::new(source.display().to_string(), asm.to_string()),
+        })
+    }
+}
+
+/// The message and line number of an error report of fasm:
+///
+/// ```text
+/// main.asm [5]:
+///     mov rax, bogus
+/// processed: mov rax,bogus
+/// error: undefined symbol 'bogus'.
+/// ```
+fn parse_error(stderr: &str) -> (Option<String>, Option<usize>) {
+    let line = stderr.lines().find_map(|l| {
+        let (_, rest) = l.strip_suffix("]:")?.rsplit_once(" [")?;
+        rest.parse().ok()
+    });
+    let message = stderr.lines().find_map(|l| {
+        let message = l.strip_prefix("error: ")?;
+        Some(message.trim_end_matches('.').to_string())
+    });
+    (message, line)
+}
+
+/// The span of the 1-based `line` of `text`, without its line break.
+fn line_span(text: &str, line: usize) -> Option<SourceSpan> {
+    let start: usize = text
+        .split_inclusive('\n')
+        .take(line.checked_sub(1)?)
+        .map(str::len)
+        .sum();
+    let len = text[start..].lines().next()?.len();
+    Some(SourceSpan::new(start.into(), len))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use miette::{GraphicalReportHandler, GraphicalTheme, Report, SourceSpan};
+
+    use crate::{
+        backend::x86_64::{emit, Allocator},
+        frontend::{cfg::Cfg, parser, symboltable::SymbolMap},
+        ir::{interp::run, text::parse},
+        types::layout::TargetLayout,
+    };
+
+    use super::{line_span, parse_error, Fasm, FasmError};
+
+    #[test]
+    fn fasm_errors() {
+        let stderr = "main.asm [5]:\n\tmov rax, bogus\nprocessed: mov rax,bogus\nerror: undefined symbol 'bogus'.\n";
+        let (message, line) = parse_error(stderr);
+        assert_eq!(message.as_deref(), Some("undefined symbol 'bogus'"));
+        assert_eq!(line, Some(5));
+        assert_eq!(parse_error("out of memory\n"), (None, None));
+
+        let text = "a\nbc\r\ndef";
+        assert_eq!(line_span(text, 2), Some(SourceSpan::new(2.into(), 2)));
+        assert_eq!(line_span(text, 3), Some(SourceSpan::new(6.into(), 3)));
+        assert!(line_span(text, 4).is_none());
+        assert!(line_span(text, 0).is_none());
+
+        let mut fasm = Fasm::new();
+        fasm.set_path("/nonexistent/fasm");
+        let err = fasm.assemble("", "empty").unwrap_err();
+        assert!(matches!(err, FasmError::Spawn { .. }));
+        let _ = std::fs::remove_dir_all(fasm.dir());
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    fn reports_the_failing_line() {
+        let asm = "format ELF64 executable 3\nsegment readable executable\nentry start\nstart:\n\tmov rax, bogus\n";
+        let fasm = Fasm::new();
+        let err = fasm.assemble(asm, "bogus").unwrap_err();
+        let _ = std::fs::remove_dir_all(fasm.dir());
+        let FasmError::Failed {
+            ref message, span, ..
+        } = err
+        else {
+            panic!("expected fasm to fail")
+        };
+        assert_eq!(message, "undefined symbol 'bogus'");
+        assert_eq!(span, Some(SourceSpan::new(73.into(), 15)));
+
+        let mut out = String::new();
+        GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor())
+            .render_report(&mut out, Report::new(err).as_ref())
+            .unwrap();
+        assert!(out.contains("mov rax, bogus"));
+        assert!(out.contains("undefined symbol 'bogus'"));
+    }
+
+    /// More values live across a call, and then across a division, than
+    /// there are registers to keep them in.
+    fn pressure() -> String {
+        let mut text = String::from("fn id(int %x) -> int {\nbb0:\n    ret %x\n}\n\n");
+        text.push_str("fn main() -> int {\n    int %c\n    int %s\n    int %q\n");
+        for i in 0..16 {
+            text.push_str(&format!("    int %v{i}\n    int %w{i}\n"));
+        }
+        text.push_str("bb0:\n");
+        for i in 0..16 {
+            text.push_str(&format!("    %v{i} <- int {}\n", 3 * i + 1));
+        }
+        text.push_str("    %c = call id(%v0)\n    %s <- %c\n");
+        for i in 0..16 {
+            text.push_str(&format!("    %s = %s + %v{i}\n"));
+        }
+        for i in 0..16 {
+            text.push_str(&format!("    %w{i} <- int {}\n", 5 * i + 2));
+        }
+        text.push_str("    %q = %w15 / %w3\n    %s = %s + %q\n");
+        for i in 0..16 {
+            text.push_str(&format!("    %s = %s - %w{i}\n"));
+        }
+        text.push_str("    ret %s\n}\n");
+        text
+    }
+
+    /// Builds and runs every program natively, checking the exit status
+    /// against the interpreter.
+    #[test]
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    fn executables_agree_with_the_interpreter() {
+        let mut programs = Vec::new();
+        for src in [
+            include_str!("../../examples/main.c"),
+            "int main() { int a = 5 + 10 * 2; return a - 3; }",
+            "int main() { int a = 1; { long a = 2; { int b = 3; return a * b; } } }",
+            "char main() { unsigned char c = 250; return c + 10; }",
+            "int add(int a, long b) { a = a + b; return a; } void nop() { return; } \
+             int main() { int x = 1; nop(); x = add(x, 2) * 3; char c = x = x + 1; \
+             return add(x, c); }",
+        ] {
+            let source = parser::parse(src, 0).unwrap();
+            let mut map = SymbolMap::new();
+            map.fill_from_source(&source.0).unwrap();
+            programs.push(Cfg::fill_from_source(&source, &map, &TargetLayout::LP64));
+        }
+        for text in [
+            "\
+fn fact(int %n) -> int {
+    int %t
+    int %r
+    int %m
+bb0:
+    branch %n, bb1, bb2
+bb1:
+    %t = %n - int 1
+    %r = call fact(%t)
+    %m = %r * %n
+    ret %m
+bb2:
+    ret int 1
+}
+
+fn main() -> int {
+    int %i
+    int %i1
+    int %acc
+    int %acc1
+    int %f
+bb0:
+    jump bb1
+bb1:
+    %i = phi [bb0: int 5, bb2: %i1]
+    %acc = phi [bb0: int 0, bb2: %acc1]
+    branch %i, bb2, bb3
+bb2:
+    %f = call fact(%i)
+    %acc1 = %acc + %f
+    %i1 = %i - int 1
+    jump bb1
+bb3:
+    ret %acc
+}
+",
+            "\
+fn f(int %a, int %b, int %c, int %d, int %e, int %g, int %h, char %i) -> int {
+    int %x
+bb0:
+    %x = %a - %h
+    %x = %x + %i
+    ret %x
+}
+
+fn main() -> int {
+    int %r
+bb0:
+    %r = call f(int 1, int 2, int 3, int 4, int 5, int 6, int 100, int 300)
+    ret %r
+}
+",
+            "fn main() -> long {\n int %r\nbb0:\n %r = uint 4294967295 / int 2\n ret %r\n}",
+            "fn main() -> long {\n ulong %r\nbb0:\n %r = long -8 / long 2\n ret %r\n}",
+        ] {
+            programs.push(parse(text).unwrap());
+        }
+        programs.push(parse(&pressure()).unwrap());
+
+        let fasm = Fasm::new();
+        for (i, cfgs) in programs.iter().enumerate() {
+            let expected = run(cfgs, &TargetLayout::LP64).unwrap();
+            for allocator in [Allocator::Stack, Allocator::LinearScan] {
+                let asm = emit(cfgs, allocator, &TargetLayout::LP64).unwrap();
+                let exe = fasm
+                    .assemble(&asm, &format!("program{i}-{allocator:?}"))
+                    .unwrap();
+                let status = Command::new(&exe).status().unwrap();
+                assert_eq!(status.code(), Some((expected & 0xff) as i32));
+            }
+        }
+        let _ = std::fs::remove_dir_all(fasm.dir());
+    }
+}
@@ -0,0 +1,3 @@
+pub mod fasm;
+pub mod regalloc;
+pub mod x86_64;
@@ -0,0 +1,681 @@
+//! Linear-scan register allocation, after Poletto and Sarkar, over a
+//! function laid out in block order, which is the order the backend emits
+//! it in.
+//!
+//! Each value gets a single live interval: the hull of every position where
+//! it is written or live, found by the usual backward dataflow. Intervals
+//! are visited by increasing start. A value live across a call only gets a
+//! callee-saved register. When no suitable register is free, whichever
+//! interval ends last is spilled to a stack slot for its whole lifetime.
+//!
+//! No register is kept back for spill code. Once every value has its
+//! location, each instruction is given the registers it may overwrite while
+//! it runs, as many as the backend `Needs` for it: those it names, then
+//! free ones. A spilled operand is reloaded into one of them and a spilled
+//! result stored from one. Where too few are free, registers of values live
+//! across the instruction are borrowed, and the backend saves them before
+//! it and restores them after. Edges into a block with phis likewise get a
+//! register to copy spilled values through.
+
+use std::collections::HashSet;
+
+use crate::{
+    frontend::cfg::Cfg,
+    ir::{BlockId, Instruction, Operand, ValueId},
+};
+
+/// The positions over which a value must keep its location, both ends
+/// included. Parameters are defined at 0, before the first block.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Interval {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Interval {
+    /// Whether the value is still needed after a call at `pos` that it
+    /// is not an argument or the result of.
+    pub fn crosses(&self, pos: usize) -> bool {
+        self.start < pos && pos < self.end
+    }
+
+    fn cover(interval: &mut Option<Interval>, pos: usize) {
+        *interval = match *interval {
+            Some(Interval { start, end }) => Some(Interval {
+                start: start.min(pos),
+                end: end.max(pos),
+            }),
+            None => Some(Interval {
+                start: pos,
+                end: pos,
+            }),
+        };
+    }
+}
+
+/// Where a value lives for the whole function.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Location<R> {
+    Reg(R),
+    /// Index of a spill slot, sized by the backend for the value's type.
+    Stack(usize),
+}
+
+/// The registers an instruction overwrites besides its result.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Needs<R> {
+    /// Registers the instruction uses by name.
+    pub fixed: Vec<R>,
+    /// How many other registers it uses.
+    pub any: usize,
+}
+
+impl<R> Default for Needs<R> {
+    fn default() -> Self {
+        Self {
+            fixed: Vec::new(),
+            any: 0,
+        }
+    }
+}
+
+/// The registers an instruction or edge may overwrite.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scratch<R> {
+    /// The fixed registers asked for, in order, then the others.
+    pub regs: Vec<R>,
+    /// Those of `regs` holding a value live across, to save and restore.
+    pub saved: Vec<R>,
+}
+
+impl<R> Default for Scratch<R> {
+    fn default() -> Self {
+        Self {
+            regs: Vec::new(),
+            saved: Vec::new(),
+        }
+    }
+}
+
+pub struct Allocation<R> {
+    /// `None` for values the function never reads or writes.
+    locations: Vec<Option<Location<R>>>,
+    /// The value of each spill slot.
+    spilled: Vec<ValueId>,
+    callee_saved: Vec<R>,
+    /// Of each instruction of each block, empty for phis.
+    scratch: Vec<Vec<Scratch<R>>>,
+    /// Of the edges into each block.
+    edges: Vec<Scratch<R>>,
+}
+
+impl<R> Default for Allocation<R> {
+    fn default() -> Self {
+        Self {
+            locations: Vec::new(),
+            spilled: Vec::new(),
+            callee_saved: Vec::new(),
+            scratch: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+}
+
+impl<R: Copy + Eq> Allocation<R> {
+    /// Every value in the stack slot of its own index, leaving every
+    /// register to the instructions.
+    pub fn stack(
+        cfg: &Cfg,
+        caller_saved: &[R],
+        callee_saved: &[R],
+        needs: impl Fn(&Instruction) -> Needs<R>,
+    ) -> Self {
+        let mut alloc = Self {
+            locations: (0..cfg.values().len())
+                .map(|i| Some(Location::Stack(i)))
+                .collect(),
+            spilled: (0..cfg.values().len()).map(ValueId).collect(),
+            ..Self::default()
+        };
+        alloc.plan(cfg, caller_saved, callee_saved, needs);
+        alloc
+    }
+
+    pub fn location(&self, value: ValueId) -> Location<R> {
+        self.locations[value.0].unwrap_or_else(|| panic!("{value} is never used"))
+    }
+
+    /// The registers the `index`th instruction of `block` may overwrite.
+    pub fn scratch(&self, block: BlockId, index: usize) -> &Scratch<R> {
+        &self.scratch[block.0][index]
+    }
+
+    /// The register that edges into `block` copy spilled phi operands and
+    /// results through.
+    pub fn edge(&self, block: BlockId) -> &Scratch<R> {
+        &self.edges[block.0]
+    }
+
+    /// Number of spill slots.
+    pub fn slots(&self) -> usize {
+        self.spilled.len()
+    }
+
+    /// The value held in each spill slot, by slot index.
+    pub fn spilled(&self) -> &[ValueId] {
+        &self.spilled
+    }
+
+    /// Callee-saved registers handed out, which the function must restore
+    /// before returning.
+    pub fn callee_saved(&self) -> &[R] {
+        &self.callee_saved
+    }
+
+    /// Picks the scratch registers of every instruction and edge, from the
+    /// caller-saved registers last to first, then the callee-saved ones.
+    /// Callee-saved registers overwritten without being saved join
+    /// `callee_saved`.
+    fn plan(
+        &mut self,
+        cfg: &Cfg,
+        caller_saved: &[R],
+        callee_saved: &[R],
+        needs: impl Fn(&Instruction) -> Needs<R>,
+    ) {
+        let order: Vec<R> = caller_saved
+            .iter()
+            .rev()
+            .chain(callee_saved)
+            .copied()
+            .collect();
+        let intervals = intervals(cfg);
+        let starts = block_starts(cfg);
+        let reg = |value: &ValueId| match self.locations[value.0] {
+            Some(Location::Reg(reg)) => Some(reg),
+            _ => None,
+        };
+        // Registers of the values whose interval satisfies `live`.
+        let live_regs = |live: &dyn Fn(&Interval) -> bool| -> Vec<R> {
+            intervals
+                .iter()
+                .enumerate()
+                .filter(|(_, interval)| interval.as_ref().is_some_and(live))
+                .filter_map(|(i, _)| reg(&ValueId(i)))
+                .collect()
+        };
+
+        let mut scratch = Vec::new();
+        let mut edges = Vec::new();
+        for block in cfg.blocks() {
+            let start = starts[block.id().0];
+            let mut block_scratch = Vec::new();
+            for (i, instr) in block.instrs().iter().enumerate() {
+                if matches!(instr, Instruction::Phi(_)) {
+                    block_scratch.push(Scratch::default());
+                    continue;
+                }
+                let pos = instr_pos(start, i, instr);
+                let across = live_regs(&|interval| interval.crosses(pos));
+                let operands: Vec<R> = instr
+                    .operands()
+                    .into_iter()
+                    .filter_map(|op| match op {
+                        Operand::Value(v) => reg(v),
+                        Operand::Const(_) => None,
+                    })
+                    .collect();
+                let Needs { fixed, any } = needs(instr);
+                let mut saved: Vec<R> = fixed
+                    .iter()
+                    .filter(|r| across.contains(r))
+                    .copied()
+                    .collect();
+                let mut regs = fixed;
+                // Free registers first, then ones borrowed from live values.
+                let candidates: Vec<R> = order
+                    .iter()
+                    .filter(|r| !regs.contains(r) && !operands.contains(r))
+                    .copied()
+                    .collect();
+                let (borrowable, free): (Vec<R>, Vec<R>) =
+                    candidates.into_iter().partition(|r| across.contains(r));
+                let wanted = regs.len() + any;
+                for r in free.into_iter().chain(borrowable).take(any) {
+                    if across.contains(&r) {
+                        saved.push(r);
+                    }
+                    regs.push(r);
+                }
+                assert_eq!(regs.len(), wanted, "too few registers for `{instr}`");
+                block_scratch.push(Scratch { regs, saved });
+            }
+            scratch.push(block_scratch);
+
+            // Phis are written on the edge, so only the values live into
+            // the block besides them must survive it.
+            let live_in = live_regs(&|interval| interval.start < start && start <= interval.end);
+            edges.push(match order.iter().find(|r| !live_in.contains(r)) {
+                Some(r) => Scratch {
+                    regs: vec![*r],
+                    saved: Vec::new(),
+                },
+                None => Scratch {
+                    regs: order.first().into_iter().copied().collect(),
+                    saved: order.first().into_iter().copied().collect(),
+                },
+            });
+        }
+
+        for plan in scratch.iter().flatten().chain(edges.iter()) {
+            for r in plan.regs.iter() {
+                if callee_saved.contains(r)
+                    && !plan.saved.contains(r)
+                    && !self.callee_saved.contains(r)
+                {
+                    self.callee_saved.push(*r);
+                }
+            }
+        }
+        self.scratch = scratch;
+        self.edges = edges;
+    }
+}
+
+/// Allocates registers to the values of `cfg`, preferring `caller_saved`
+/// ones for values that are not live across a call, then plans the scratch
+/// registers of every instruction from what it `needs`.
+pub fn linear_scan<R: Copy + Eq>(
+    cfg: &Cfg,
+    caller_saved: &[R],
+    callee_saved: &[R],
+    needs: impl Fn(&Instruction) -> Needs<R>,
+) -> Allocation<R> {
+    let calls = calls(cfg);
+    let starts = block_starts(cfg);
+    let mut order: Vec<(ValueId, Interval)> = intervals(cfg)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, interval)| Some((ValueId(i), interval?)))
+        .collect();
+    order.sort_by_key(|(value, interval)| (interval.start, *value));
+
+    let mut locations = vec![None; cfg.values().len()];
+    let mut free_caller = caller_saved.to_vec();
+    let mut free_callee = callee_saved.to_vec();
+    let mut active: Vec<(ValueId, Interval, R)> = Vec::new();
+    let mut spilled = Vec::new();
+    for (value, interval) in order {
+        // An instruction reads its operands before writing its result, so
+        // a register is free for the result once its last read is there.
+        // Phis at the start of a block and parameters are written at once.
+        let at_once = interval.start == 0 || starts.contains(&interval.start);
+        active.retain(|(_, other, reg)| {
+            if other.end > interval.start || (other.end == interval.start && at_once) {
+                return true;
+            }
+            if caller_saved.contains(reg) {
+                free_caller.push(*reg);
+            } else {
+                free_callee.push(*reg);
+            }
+            false
+        });
+
+        let crosses = calls.iter().any(|call| interval.crosses(*call));
+        let free = if crosses || free_caller.is_empty() {
+            &mut free_callee
+        } else {
+            &mut free_caller
+        };
+        if !free.is_empty() {
+            let reg = free.remove(0);
+            locations[value.0] = Some(Location::Reg(reg));
+            active.push((value, interval, reg));
+            continue;
+        }
+
+        // Spills whichever of `value` and the active values whose register
+        // it could take is needed the longest.
+        let victim = active
+            .iter_mut()
+            .filter(|(_, _, reg)| !crosses || callee_saved.contains(reg))
+            .max_by_key(|(_, other, _)| other.end);
+        match victim {
+            Some(victim) if victim.1.end > interval.end => {
+                spilled.push(victim.0);
+                locations[value.0] = Some(Location::Reg(victim.2));
+                *victim = (value, interval, victim.2);
+            }
+            _ => spilled.push(value),
+        }
+    }
+
+    for (slot, value) in spilled.iter().enumerate() {
+        locations[value.0] = Some(Location::Stack(slot));
+    }
+    let used: Vec<R> = locations
+        .iter()
+        .filter_map(|location| match location {
+            Some(Location::Reg(reg)) => Some(*reg),
+            _ => None,
+        })
+        .collect();
+    let mut alloc = Allocation {
+        locations,
+        spilled,
+        callee_saved: callee_saved
+            .iter()
+            .copied()
+            .filter(|reg| used.contains(reg))
+            .collect(),
+        ..Allocation::default()
+    };
+    alloc.plan(cfg, caller_saved, callee_saved, needs);
+    alloc
+}
+
+/// Where each block starts. Its instructions follow at every other
+/// position and its terminator comes last; phis are placed at the start.
+fn block_starts(cfg: &Cfg) -> Vec<usize> {
+    let mut starts = Vec::new();
+    let mut pos = 2;
+    for block in cfg.blocks() {
+        starts.push(pos);
+        pos += 2 * (block.instrs().len() + 2);
+    }
+    starts
+}
+
+fn instr_pos(start: usize, index: usize, instr: &Instruction) -> usize {
+    match instr {
+        Instruction::Phi(_) => start,
+        _ => start + 2 * (index + 1),
+    }
+}
+
+/// Positions of the calls of `cfg`.
+pub fn calls(cfg: &Cfg) -> Vec<usize> {
+    let starts = block_starts(cfg);
+    let mut calls = Vec::new();
+    for block in cfg.blocks() {
+        for (i, instr) in block.instrs().iter().enumerate() {
+            if matches!(instr, Instruction::Call(_)) {
+                calls.push(instr_pos(starts[block.id().0], i, instr));
+            }
+        }
+    }
+    calls
+}
+
+/// The live interval of every value, `None` for values never used.
+pub fn intervals(cfg: &Cfg) -> Vec<Option<Interval>> {
+    let starts = block_starts(cfg);
+    let live_out = live_out(cfg);
+    let mut intervals = vec![None; cfg.values().len()];
+    for param in cfg.params() {
+        Interval::cover(&mut intervals[param.0], 0);
+    }
+    for block in cfg.blocks() {
+        let start = starts[block.id().0];
+        let end = start + 2 * (block.instrs().len() + 1);
+        let mut live = live_out[block.id().0].clone();
+        for op in block.term().operands() {
+            if let Operand::Value(v) = op {
+                live.insert(*v);
+            }
+        }
+        for v in live.iter() {
+            Interval::cover(&mut intervals[v.0], end);
+        }
+        for (i, instr) in block.instrs().iter().enumerate().rev() {
+            let pos = instr_pos(start, i, instr);
+            if let Some(dst) = instr.result() {
+                Interval::cover(&mut intervals[dst.0], pos);
+                live.remove(&dst);
+            }
+            if matches!(instr, Instruction::Phi(_)) {
+                continue;
+            }
+            for op in instr.operands() {
+                if let Operand::Value(v) = op {
+                    Interval::cover(&mut intervals[v.0], pos);
+                    live.insert(*v);
+                }
+            }
+        }
+        for v in live {
+            Interval::cover(&mut intervals[v.0], start);
+        }
+    }
+    intervals
+}
+
+/// Values live on exit from each block. A phi reads its operand at the end
+/// of the predecessor it comes from and defines its result on entry.
+fn live_out(cfg: &Cfg) -> Vec<HashSet<ValueId>> {
+    let len = cfg.blocks().len();
+    let mut uses = vec![HashSet::new(); len];
+    let mut defs = vec![HashSet::new(); len];
+    let mut phi_uses = vec![HashSet::new(); len];
+    for block in cfg.blocks() {
+        let id = block.id().0;
+        for instr in block.instrs() {
+            if let Instruction::Phi(phi) = instr {
+                for (pred, op) in phi.incoming.iter() {
+                    if let Operand::Value(v) = op {
+                        phi_uses[pred.0].insert(*v);
+                    }
+                }
+            } else {
+                for op in instr.operands() {
+                    if let Operand::Value(v) = op {
+                        if !defs[id].contains(v) {
+                            uses[id].insert(*v);
+                        }
+                    }
+                }
+            }
+            if let Some(dst) = instr.result() {
+                defs[id].insert(dst);
+            }
+        }
+        for op in block.term().operands() {
+            if let Operand::Value(v) = op {
+                if !defs[id].contains(v) {
+                    uses[id].insert(*v);
+                }
+            }
+        }
+    }
+
+    let mut live_in = uses;
+    let mut live_out = phi_uses;
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in cfg.blocks().iter().rev() {
+            let id = block.id().0;
+            for succ in cfg.succs(block.id()) {
+                let new: Vec<ValueId> =
+                    live_in[succ.0].difference(&live_out[id]).copied().collect();
+                live_out[id].extend(new);
+            }
+            for v in live_out[id].iter() {
+                if !defs[id].contains(v) && live_in[id].insert(*v) {
+                    changed = true;
+                }
+            }
+        }
+    }
+    live_out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        frontend::ast::BinOp,
+        ir::{text::parse, BlockId, Instruction, ValueId},
+    };
+
+    use super::{calls, intervals, linear_scan, Allocation, Interval, Location, Needs, Scratch};
+
+    const LOOP: &str = "\
+fn main(int %n) -> int {
+    int %i
+    int %i1
+    int %acc
+    int %acc1
+    int %unused
+bb0:
+    jump bb1
+bb1:
+    %i = phi [bb0: %n, bb2: %i1]
+    %acc = phi [bb0: int 0, bb2: %acc1]
+    branch %i, bb2, bb3
+bb2:
+    %acc1 = %acc + %i
+    %i1 = %i - int 1
+    jump bb1
+bb3:
+    ret %acc
+}
+";
+
+    #[test]
+    fn intervals_span_loops() {
+        let cfg = parse(LOOP).unwrap().remove(0);
+        let intervals = intervals(&cfg);
+        let at = |value: usize| intervals[value].unwrap();
+        // bb0 is 2..4, bb1 is 6..12, bb2 is 14..20 and bb3 is 22..24.
+        assert_eq!(at(0), Interval { start: 0, end: 4 });
+        assert_eq!(at(1), Interval { start: 6, end: 18 });
+        assert_eq!(at(2), Interval { start: 18, end: 20 });
+        assert_eq!(at(3), Interval { start: 6, end: 24 });
+        assert_eq!(at(4), Interval { start: 16, end: 20 });
+        assert!(intervals[5].is_none());
+    }
+
+    #[test]
+    fn overlapping_intervals_get_distinct_locations() {
+        let cfg = parse(LOOP).unwrap().remove(0);
+        let intervals = intervals(&cfg);
+        for regs in [&["a", "b", "c"][..], &["a", "b"], &["a"], &[]] {
+            let alloc = linear_scan(&cfg, regs, &[], |_| Needs::default());
+            assert!(alloc.callee_saved().is_empty());
+            for (i, a) in intervals.iter().enumerate() {
+                for (j, b) in intervals.iter().enumerate().skip(i + 1) {
+                    let (Some(a), Some(b)) = (a, b) else {
+                        continue;
+                    };
+                    // Only a result may take over the register of an
+                    // operand last read by the same instruction.
+                    let handed_over = |x: &Interval, y: &Interval| {
+                        x.end == y.start && ![0, 2, 6, 14, 22].contains(&y.start)
+                    };
+                    if a.start <= b.end
+                        && b.start <= a.end
+                        && !handed_over(a, b)
+                        && !handed_over(b, a)
+                    {
+                        assert_ne!(alloc.location(ValueId(i)), alloc.location(ValueId(j)));
+                    }
+                }
+            }
+        }
+
+        // `%acc` lives the longest and is the one spilled.
+        let alloc = linear_scan(&cfg, &["a", "b"], &[], |_| Needs::default());
+        assert_eq!(alloc.location(ValueId(3)), Location::Stack(0));
+        assert_eq!(alloc.slots(), 1);
+        assert_eq!(alloc.location(ValueId(1)), Location::Reg("b"));
+    }
+
+    #[test]
+    fn values_live_across_calls_are_callee_saved() {
+        let cfg = parse(
+            "\
+fn main() -> int {
+    int %a
+    int %b
+    int %c
+    int %d
+bb0:
+    %a <- int 1
+    %b <- int 2
+    %c = call f(%a)
+    %d = %b + %c
+    ret %d
+}
+",
+        )
+        .unwrap()
+        .remove(0);
+        assert_eq!(calls(&cfg), [8]);
+        let alloc = linear_scan(&cfg, &["rdi"], &["rbx"], |_| Needs::default());
+        assert_eq!(alloc.location(ValueId(0)), Location::Reg("rdi"));
+        assert_eq!(alloc.location(ValueId(1)), Location::Reg("rbx"));
+        assert_eq!(alloc.location(ValueId(2)), Location::Reg("rdi"));
+        assert_eq!(alloc.callee_saved(), ["rbx"]);
+
+        // Without a callee-saved register, `%b` cannot stay in a register.
+        let alloc = linear_scan(&cfg, &["rdi", "rsi"], &[], |_| Needs::default());
+        assert_eq!(alloc.location(ValueId(1)), Location::Stack(0));
+
+        let stack = Allocation::stack(&cfg, &["rdi"], &[], |_| Needs::default());
+        assert_eq!(stack.location(ValueId(3)), Location::Stack(3));
+        assert_eq!(stack.slots(), 4);
+    }
+    #[test]
+    fn scratch_registers_avoid_operands_and_save_live_ones() {
+        let cfg = parse(
+            "\
+fn main(int %x, int %y, int %z) -> int {
+    int %s
+    int %t
+    int %u
+bb0:
+    %s = %x / %y
+    %t = %s + %z
+    %u = %t + %x
+    ret %u
+}
+",
+        )
+        .unwrap()
+        .remove(0);
+        let needs = |instr: &Instruction| match instr {
+            Instruction::BAssign(bin) if bin.op == BinOp::Div => Needs {
+                fixed: vec!["a"],
+                any: 1,
+            },
+            _ => Needs {
+                fixed: Vec::new(),
+                any: 2,
+            },
+        };
+        let alloc = linear_scan(&cfg, &["a", "b", "c"], &["r"], needs);
+        assert_eq!(alloc.location(ValueId(0)), Location::Reg("a"));
+        assert_eq!(alloc.location(ValueId(1)), Location::Reg("b"));
+        assert_eq!(alloc.location(ValueId(2)), Location::Reg("c"));
+
+        // `%x` is still needed in `a`, and `r` is the only free register.
+        let scratch = |index| alloc.scratch(BlockId(0), index).clone();
+        assert_eq!(
+            scratch(0),
+            Scratch {
+                regs: vec!["a", "r"],
+                saved: vec!["a"],
+            }
+        );
+        // Operands are never scratch, so `a` is borrowed from `%x`.
+        assert_eq!(
+            scratch(1),
+            Scratch {
+                regs: vec!["r", "a"],
+                saved: vec!["a"],
+            }
+        );
+        assert_eq!(alloc.callee_saved(), ["r"]);
+    }
+}
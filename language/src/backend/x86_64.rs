@@ -0,0 +1,813 @@
+//! Lowers `Cfg`s to x86-64 assembly for the flat assembler (fasm), as a
+//! static Linux executable that exits with `main`'s return value.
+//!
+//! Every value lives in a register or a stack slot below `rbp`, as picked by
+//! the `Allocator`. Slots are sized and aligned for their value's type by the
+//! `TargetLayout`, while registers hold values sign or zero extended to 64
+//! bits according to their type. Each instruction reloads its spilled operands
+//! into the scratch registers the allocation plans for it, and stores a
+//! spilled result from one. Arithmetic agrees with the interpreter: operands are converted to the destination's type, made
+//! unsigned if either operand is, and results wrap to the destination's
+//! width. Calls follow the System V ABI.
+
+use std::fmt::{Arguments, Display};
+
+use miette::Diagnostic;
+
+use crate::{
+    backend::regalloc::{self, Allocation, Location, Needs, Scratch},
+    frontend::{ast::BinOp, cfg::Cfg},
+    ir::{BlockId, Instruction, Operand, Terminator, ValueId},
+    types::{designators::TypeInstance, layout::TargetLayout},
+};
+
+#[derive(Debug, Diagnostic)]
+pub enum CodegenError {
+    #[diagnostic(code(xlang::codegen::no_main), help("define `int main()`"))]
+    NoMain,
+    #[diagnostic(code(xlang::codegen::unknown_function))]
+    UnknownFunction { caller: String, name: String },
+    #[diagnostic(code(xlang::codegen::arguments))]
+    Arguments {
+        caller: String,
+        func: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Display for CodegenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodegenError::NoMain => write!(f, "no `main` function to start from"),
+            CodegenError::UnknownFunction { caller, name } => {
+                write!(f, "in `{caller}`, call to unknown function `{name}`")
+            }
+            CodegenError::Arguments {
+                caller,
+                func,
+                expected,
+                found,
+            } => write!(
+                f,
+                "in `{caller}`, `{func}` takes {expected} arguments but got {found}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CodegenError {}
+
+/// Registers of the first integer arguments, in order.
+const ARGS: [Reg; 6] = [Reg::Rdi, Reg::Rsi, Reg::Rdx, Reg::Rcx, Reg::R8, Reg::R9];
+
+/// Allocatable registers a call may overwrite. With `CALLEE_SAVED` these are
+/// all 14 general-purpose registers besides `rsp` and `rbp`. Values are given
+/// them from the front and scratch registers from the back, so `rax`, `rcx`
+/// and `rdx` are the first to hold reloaded operands.
+const CALLER_SAVED: [Reg; 9] = [
+    Reg::Rsi,
+    Reg::Rdi,
+    Reg::R8,
+    Reg::R9,
+    Reg::R10,
+    Reg::R11,
+    Reg::Rdx,
+    Reg::Rcx,
+    Reg::Rax,
+];
+
+/// Allocatable registers a call preserves, saved by the functions using them.
+const CALLEE_SAVED: [Reg; 5] = [Reg::Rbx, Reg::R12, Reg::R13, Reg::R14, Reg::R15];
+
+/// The scratch registers `instr` is emitted with: `idiv` divides `rax` and
+/// `rdx` and calls return in `rax`, while everything else reloads each
+/// operand into a register of its own.
+fn needs(instr: &Instruction) -> Needs<Reg> {
+    match instr {
+        Instruction::BAssign(bin) if bin.op == BinOp::Div => Needs {
+            fixed: vec![Reg::Rax, Reg::Rdx],
+            any: 1,
+        },
+        Instruction::BAssign(_) => Needs {
+            fixed: Vec::new(),
+            any: 2,
+        },
+        Instruction::SAssign(_) | Instruction::Mov(_) => Needs {
+            fixed: Vec::new(),
+            any: 1,
+        },
+        // Nothing lives across a call in a caller-saved register.
+        Instruction::Call(_) => Needs {
+            fixed: vec![Reg::Rax],
+            any: 0,
+        },
+        Instruction::Phi(_) => Needs::default(),
+    }
+}
+
+/// The 14 general-purpose registers besides `rsp` and `rbp`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Reg {
+    Rax,
+    Rbx,
+    Rcx,
+    Rdx,
+    Rsi,
+    Rdi,
+    R8,
+    R9,
+    R10,
+    R11,
+    R12,
+    R13,
+    R14,
+    R15,
+}
+
+impl Reg {
+    /// Names of the 64, 32, 16 and 8-bit parts.
+    fn names(&self) -> [&'static str; 4] {
+        match self {
+            Reg::Rax => ["rax", "eax", "ax", "al"],
+            Reg::Rbx => ["rbx", "ebx", "bx", "bl"],
+            Reg::Rcx => ["rcx", "ecx", "cx", "cl"],
+            Reg::Rdx => ["rdx", "edx", "dx", "dl"],
+            Reg::Rsi => ["rsi", "esi", "si", "sil"],
+            Reg::Rdi => ["rdi", "edi", "di", "dil"],
+            Reg::R8 => ["r8", "r8d", "r8w", "r8b"],
+            Reg::R9 => ["r9", "r9d", "r9w", "r9b"],
+            Reg::R10 => ["r10", "r10d", "r10w", "r10b"],
+            Reg::R11 => ["r11", "r11d", "r11w", "r11b"],
+            Reg::R12 => ["r12", "r12d", "r12w", "r12b"],
+            Reg::R13 => ["r13", "r13d", "r13w", "r13b"],
+            Reg::R14 => ["r14", "r14d", "r14w", "r14b"],
+            Reg::R15 => ["r15", "r15d", "r15w", "r15b"],
+        }
+    }
+}
+
+impl Display for Reg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.names()[0])
+    }
+}
+
+/// Where values are kept.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Allocator {
+    /// Every value in its own stack slot.
+    Stack,
+    /// Registers from `regalloc::linear_scan`, the rest spilled.
+    LinearScan,
+}
+
+/// Emits the whole program: an entry point calling `main`, then every
+/// function, with the type sizes of `layout`, which is `LP64` on x86-64
+/// Linux.
+pub fn emit(
+    cfgs: &[Cfg],
+    allocator: Allocator,
+    layout: &TargetLayout,
+) -> Result<String, CodegenError> {
+    let Some(main) = cfgs.iter().find(|cfg| cfg.name() == "main") else {
+        return Err(CodegenError::NoMain);
+    };
+    let mut emitter = Emitter {
+        cfgs,
+        allocator,
+        layout,
+        alloc: Allocation::default(),
+        offsets: Vec::new(),
+        out: String::new(),
+    };
+    emitter.line(format_args!("format ELF64 executable 3"));
+    emitter.line(format_args!(""));
+    emitter.line(format_args!("segment readable executable"));
+    emitter.line(format_args!(""));
+    emitter.line(format_args!("entry start"));
+    emitter.line(format_args!(""));
+    emitter.line(format_args!("start:"));
+    emitter.instr(format_args!("call {}", symbol("main")));
+    if *main.ret_type() == TypeInstance::Void {
+        emitter.instr(format_args!("xor edi, edi"));
+    } else {
+        emitter.instr(format_args!("mov edi, eax"));
+    }
+    emitter.instr(format_args!("mov eax, 60 ; sys_exit"));
+    emitter.instr(format_args!("syscall"));
+    for cfg in cfgs.iter() {
+        emitter.line(format_args!(""));
+        emitter.func(cfg)?;
+    }
+    Ok(emitter.out)
+}
+
+/// The assembler label of a function, prefixed so that it cannot clash
+/// with a register or instruction name.
+fn symbol(func: &str) -> String {
+    format!("_{func}")
+}
+
+/// The label of a block, local to its function's label.
+fn block_label(block: BlockId) -> String {
+    format!(".{block}")
+}
+
+/// The label of the code running the phis of `to` when coming from `from`.
+fn edge_label(from: BlockId, to: BlockId) -> String {
+    format!(".{from}_{to}")
+}
+
+/// The `index`th 8 bytes below `rbp`, where the callee-saved registers are
+/// saved, above the spill slots.
+fn saved_slot(index: usize) -> String {
+    format!("qword [rbp-{}]", 8 * (index + 1))
+}
+
+/// The operand size of a memory access of `size` bytes.
+fn ptr_size(size: u64) -> &'static str {
+    match size {
+        1 => "byte",
+        2 => "word",
+        4 => "dword",
+        _ => "qword",
+    }
+}
+
+struct Emitter<'a> {
+    cfgs: &'a [Cfg],
+    allocator: Allocator,
+    layout: &'a TargetLayout,
+    /// Locations of the values of the function being emitted.
+    alloc: Allocation<Reg>,
+    /// Distance below `rbp` of each spill slot.
+    offsets: Vec<u64>,
+    out: String,
+}
+
+impl<'a> Emitter<'a> {
+    fn line(&mut self, line: Arguments) {
+        self.out.push_str(&line.to_string());
+        self.out.push('\n');
+    }
+
+    fn instr(&mut self, instr: Arguments) {
+        self.out.push('\t');
+        self.line(instr);
+    }
+
+    fn func(&mut self, cfg: &Cfg) -> Result<(), CodegenError> {
+        self.alloc = match self.allocator {
+            Allocator::Stack => Allocation::stack(cfg, &CALLER_SAVED, &CALLEE_SAVED, needs),
+            Allocator::LinearScan => {
+                regalloc::linear_scan(cfg, &CALLER_SAVED, &CALLEE_SAVED, needs)
+            }
+        };
+        self.line(format_args!("{}:", symbol(cfg.name())));
+        self.instr(format_args!("push rbp"));
+        self.instr(format_args!("mov rbp, rsp"));
+        let saved = self.alloc.callee_saved().to_vec();
+        let mut end = 8 * saved.len() as u64;
+        self.offsets.clear();
+        for value in self.alloc.spilled() {
+            let _type = &cfg.value(*value)._type;
+            let size = self.layout.size_of(_type).unwrap_or(self.layout.pointer);
+            let align = self.layout.align_of(_type).unwrap_or(self.layout.pointer);
+            end = (end + size).next_multiple_of(align);
+            self.offsets.push(end);
+        }
+        // Keeps `rsp` 16-byte aligned at calls.
+        let frame = end.next_multiple_of(16);
+        if frame > 0 {
+            self.instr(format_args!("sub rsp, {frame}"));
+        }
+        for (i, reg) in saved.iter().enumerate() {
+            self.instr(format_args!("mov {}, {reg}", saved_slot(i)));
+        }
+        // Parameters may be allocated to each other's argument registers,
+        // so all of them are pushed before any is stored, the spilled ones
+        // through `rax` before any register is written.
+        let in_regs = cfg.params().len().min(ARGS.len());
+        for reg in ARGS.iter().take(in_regs) {
+            self.instr(format_args!("push {reg}"));
+        }
+        let arg = |i: usize| {
+            if i < ARGS.len() {
+                format!("qword [rsp+{}]", 8 * (in_regs - 1 - i))
+            } else {
+                // Past the saved `rbp` and the return address.
+                format!("qword [rbp+{}]", 16 + 8 * (i - ARGS.len()))
+            }
+        };
+        for (i, param) in cfg.params().iter().enumerate() {
+            if let Location::Stack(_) = self.alloc.location(*param) {
+                self.instr(format_args!("mov rax, {}", arg(i)));
+                self.store(cfg, Reg::Rax, *param);
+            }
+        }
+        for (i, param) in cfg.params().iter().enumerate() {
+            if let Location::Reg(reg) = self.alloc.location(*param) {
+                self.instr(format_args!("mov {reg}, {}", arg(i)));
+                self.extend(reg, &cfg.value(*param)._type);
+            }
+        }
+        if in_regs > 0 {
+            self.instr(format_args!("add rsp, {}", 8 * in_regs));
+        }
+        if cfg.blocks().is_empty() {
+            self.instr(format_args!("ud2"));
+        }
+
+        for block in cfg.blocks() {
+            self.line(format_args!("{}:", block_label(block.id())));
+            for (i, instr) in block.instrs().iter().enumerate() {
+                if !matches!(instr, Instruction::Phi(_)) {
+                    self.instr(format_args!("; {instr}"));
+                }
+                let Scratch { regs, saved } = self.alloc.scratch(block.id(), i).clone();
+                for reg in saved.iter() {
+                    self.instr(format_args!("push {reg}"));
+                }
+                self.instruction(cfg, instr, &regs)?;
+                for reg in saved.iter().rev() {
+                    self.instr(format_args!("pop {reg}"));
+                }
+            }
+            self.instr(format_args!("; {}", block.term()));
+            self.terminator(cfg, block.id(), block.term());
+        }
+        Ok(())
+    }
+
+    /// Emits `instr` using the registers `needs` asked for, which the
+    /// allocation handed out in `regs`.
+    fn instruction(
+        &mut self,
+        cfg: &Cfg,
+        instr: &Instruction,
+        regs: &[Reg],
+    ) -> Result<(), CodegenError> {
+        match instr {
+            Instruction::BAssign(bin) => {
+                let _type = &cfg.value(bin.dst)._type;
+                let unsigned = cfg.operand_type(&bin.lop).is_unsigned()
+                    || cfg.operand_type(&bin.rop).is_unsigned();
+                let op_type = if unsigned {
+                    _type.to_unsigned()
+                } else {
+                    _type.clone()
+                };
+                if bin.op == BinOp::Div {
+                    // `rax` and `rdx` may hold either operand, so the
+                    // divisor is read first.
+                    let divisor = regs[2];
+                    self.load(cfg, divisor, &bin.rop);
+                    self.extend(divisor, &op_type);
+                    self.load(cfg, Reg::Rax, &bin.lop);
+                    self.extend(Reg::Rax, &op_type);
+                    if self.layout.is_signed(&op_type).unwrap_or(true) {
+                        self.instr(format_args!("cqo"));
+                        self.instr(format_args!("idiv {divisor}"));
+                    } else {
+                        self.instr(format_args!("xor edx, edx"));
+                        self.instr(format_args!("div {divisor}"));
+                    }
+                    self.store(cfg, Reg::Rax, bin.dst);
+                    return Ok(());
+                }
+                let (lhs, rhs) = (regs[0], regs[1]);
+                self.load(cfg, lhs, &bin.lop);
+                self.extend(lhs, &op_type);
+                self.load(cfg, rhs, &bin.rop);
+                self.extend(rhs, &op_type);
+                match bin.op {
+                    BinOp::Add => self.instr(format_args!("add {lhs}, {rhs}")),
+                    BinOp::Sub => self.instr(format_args!("sub {lhs}, {rhs}")),
+                    BinOp::Mul => self.instr(format_args!("imul {lhs}, {rhs}")),
+                    BinOp::Div => unreachable!("divisions are emitted above"),
+                }
+                self.store(cfg, lhs, bin.dst);
+            }
+            Instruction::SAssign(single) => {
+                self.load(cfg, regs[0], &single.src);
+                self.store(cfg, regs[0], single.dst);
+            }
+            Instruction::Mov(mov) => {
+                self.load(cfg, regs[0], &mov.src);
+                self.store(cfg, regs[0], mov.dst);
+            }
+            Instruction::Call(call) => {
+                let Some(callee) = self.cfgs.iter().find(|f| f.name() == call.func) else {
+                    return Err(CodegenError::UnknownFunction {
+                        caller: cfg.name().to_string(),
+                        name: call.func.clone(),
+                    });
+                };
+                if callee.params().len() != call.args.len() {
+                    return Err(CodegenError::Arguments {
+                        caller: cfg.name().to_string(),
+                        func: call.func.clone(),
+                        expected: callee.params().len(),
+                        found: call.args.len(),
+                    });
+                }
+                let stack = call.args.len().saturating_sub(ARGS.len());
+                let pad = stack % 2;
+                if pad == 1 {
+                    self.instr(format_args!("sub rsp, 8"));
+                }
+                // Every argument is copied below the stack arguments before
+                // any argument register is written. Those in registers go
+                // first, leaving `rax` free for the rest, since nothing
+                // live across a call is in a caller-saved register.
+                if !call.args.is_empty() {
+                    self.instr(format_args!("sub rsp, {}", 8 * call.args.len()));
+                }
+                for (i, arg) in call.args.iter().enumerate() {
+                    if let Some(src) = self.direct(arg) {
+                        self.instr(format_args!("mov qword [rsp+{}], {src}", 8 * i));
+                    }
+                }
+                for (i, arg) in call.args.iter().enumerate() {
+                    if self.direct(arg).is_none() {
+                        self.load(cfg, Reg::Rax, arg);
+                        self.instr(format_args!("mov qword [rsp+{}], rax", 8 * i));
+                    }
+                }
+                for reg in ARGS.iter().take(call.args.len()) {
+                    self.instr(format_args!("pop {reg}"));
+                }
+                self.instr(format_args!("call {}", symbol(&call.func)));
+                if stack + pad > 0 {
+                    self.instr(format_args!("add rsp, {}", 8 * (stack + pad)));
+                }
+                if let Some(dst) = call.dst {
+                    self.store(cfg, Reg::Rax, dst);
+                }
+            }
+            // Assigned on the edges into the block.
+            Instruction::Phi(_) => (),
+        }
+        Ok(())
+    }
+
+    fn terminator(&mut self, cfg: &Cfg, block: BlockId, term: &Terminator) {
+        match term {
+            // Nothing is live after returning, so `rax` is free.
+            Terminator::Ret(op) => {
+                if let Some(op) = op {
+                    self.load(cfg, Reg::Rax, op);
+                    self.extend(Reg::Rax, cfg.ret_type());
+                }
+                let saved = self.alloc.callee_saved().to_vec();
+                for (i, reg) in saved.iter().enumerate() {
+                    self.instr(format_args!("mov {reg}, {}", saved_slot(i)));
+                }
+                self.instr(format_args!("leave"));
+                self.instr(format_args!("ret"));
+            }
+            Terminator::Jump(target) => self.edge(cfg, block, *target),
+            Terminator::Branch(Operand::Const(cond), then, _else) => {
+                let target = if cond.to_i128() != 0 { then } else { _else };
+                self.edge(cfg, block, *target);
+            }
+            Terminator::Branch(Operand::Value(cond), then, _else) => {
+                match self.alloc.location(*cond) {
+                    Location::Reg(reg) => self.instr(format_args!("test {reg}, {reg}")),
+                    Location::Stack(slot) => {
+                        let (_, operand) = self.spill_slot(cfg, *cond, slot);
+                        self.instr(format_args!("cmp {operand}, 0"));
+                    }
+                }
+                if has_phis(cfg, *_else) {
+                    self.instr(format_args!("jz {}", edge_label(block, *_else)));
+                    self.edge(cfg, block, *then);
+                    self.line(format_args!("{}:", edge_label(block, *_else)));
+                    self.edge(cfg, block, *_else);
+                } else {
+                    self.instr(format_args!("jz {}", block_label(*_else)));
+                    self.edge(cfg, block, *then);
+                }
+            }
+            Terminator::Unreachable => self.instr(format_args!("ud2")),
+        }
+    }
+
+    /// Jumps from `from` to `to`, first assigning the phis of `to` all at
+    /// once: every operand is copied onto the stack, then every result
+    /// read back. Spilled ones go through the register of the edge.
+    fn edge(&mut self, cfg: &Cfg, from: BlockId, to: BlockId) {
+        let mut phis = Vec::new();
+        for instr in cfg.block(to).instrs() {
+            let Instruction::Phi(phi) = instr else {
+                break;
+            };
+            let op = phi
+                .incoming_from(from)
+                .unwrap_or_else(|| panic!("phi in {to} of `{}` misses {from}", cfg.name()));
+            self.instr(format_args!("; {phi}"));
+            phis.push((phi.dst, op));
+        }
+        if !phis.is_empty() {
+            self.phis(cfg, to, &phis);
+        }
+        self.instr(format_args!("jmp {}", block_label(to)));
+    }
+
+    fn phis(&mut self, cfg: &Cfg, to: BlockId, phis: &[(ValueId, &Operand)]) {
+        let spilled: Vec<bool> = phis
+            .iter()
+            .map(|(dst, _)| matches!(self.alloc.location(*dst), Location::Stack(_)))
+            .collect();
+        let through_scratch = phis
+            .iter()
+            .zip(spilled.iter())
+            .any(|((_, op), spilled)| *spilled || self.direct(op).is_none());
+        let Scratch { regs, saved } = self.alloc.edge(to).clone();
+        let (scratch, save) = (regs[0], through_scratch && !saved.is_empty());
+        let area = 8 * (phis.len() + save as usize);
+        self.instr(format_args!("sub rsp, {area}"));
+        for (i, (_, op)) in phis.iter().enumerate() {
+            if let Some(src) = self.direct(op) {
+                self.instr(format_args!("mov qword [rsp+{}], {src}", 8 * i));
+            }
+        }
+        if save {
+            self.instr(format_args!(
+                "mov qword [rsp+{}], {scratch}",
+                8 * phis.len()
+            ));
+        }
+        for (i, (_, op)) in phis.iter().enumerate() {
+            if self.direct(op).is_none() {
+                self.load(cfg, scratch, op);
+                self.instr(format_args!("mov qword [rsp+{}], {scratch}", 8 * i));
+            }
+        }
+        for (i, (dst, _)) in phis.iter().enumerate() {
+            if spilled[i] {
+                self.instr(format_args!("mov {scratch}, qword [rsp+{}]", 8 * i));
+                self.store(cfg, scratch, *dst);
+            }
+        }
+        if save {
+            self.instr(format_args!(
+                "mov {scratch}, qword [rsp+{}]",
+                8 * phis.len()
+            ));
+        }
+        for (i, (dst, _)) in phis.iter().enumerate() {
+            if let Location::Reg(reg) = self.alloc.location(*dst) {
+                self.instr(format_args!("mov {reg}, qword [rsp+{}]", 8 * i));
+                self.extend(reg, &cfg.value(*dst)._type);
+            }
+        }
+        self.instr(format_args!("add rsp, {area}"));
+    }
+
+    /// The register or immediate `op` can be copied to memory from without
+    /// a scratch register, `None` for spilled values and wide constants.
+    fn direct(&self, op: &Operand) -> Option<String> {
+        match op {
+            Operand::Const(val) => i32::try_from(val.to_i128())
+                .is_ok()
+                .then(|| val.to_string()),
+            Operand::Value(id) => match self.alloc.location(*id) {
+                Location::Reg(reg) => Some(reg.to_string()),
+                Location::Stack(_) => None,
+            },
+        }
+    }
+
+    /// The size of `value`'s spill slot and the memory operand of it.
+    fn spill_slot(&self, cfg: &Cfg, value: ValueId, slot: usize) -> (u64, String) {
+        let _type = &cfg.value(value)._type;
+        let size = self.layout.size_of(_type).unwrap_or(self.layout.pointer);
+        let operand = format!("{} [rbp-{}]", ptr_size(size), self.offsets[slot]);
+        (size, operand)
+    }
+
+    /// Loads `op` into `reg`, reloading spilled values from their slot
+    /// extended to 64 bits.
+    fn load(&mut self, cfg: &Cfg, reg: Reg, op: &Operand) {
+        let id = match op {
+            Operand::Const(val) => return self.instr(format_args!("mov {reg}, {val}")),
+            Operand::Value(id) => *id,
+        };
+        let slot = match self.alloc.location(id) {
+            Location::Reg(src) if src == reg => return,
+            Location::Reg(src) => return self.instr(format_args!("mov {reg}, {src}")),
+            Location::Stack(slot) => slot,
+        };
+        let (size, operand) = self.spill_slot(cfg, id, slot);
+        let [q, d, ..] = reg.names();
+        let signed = self.layout.is_signed(&cfg.value(id)._type).unwrap_or(true);
+        match (size, signed) {
+            (1 | 2, true) => self.instr(format_args!("movsx {q}, {operand}")),
+            (1 | 2, false) => self.instr(format_args!("movzx {d}, {operand}")),
+            (4, true) => self.instr(format_args!("movsxd {q}, {operand}")),
+            (4, false) => self.instr(format_args!("mov {d}, {operand}")),
+            _ => self.instr(format_args!("mov {q}, {operand}")),
+        }
+    }
+
+    /// Converts `src` to the type of `dst` and stores it there, spilling
+    /// it to its slot if `dst` has no register.
+    fn store(&mut self, cfg: &Cfg, src: Reg, dst: ValueId) {
+        self.extend(src, &cfg.value(dst)._type);
+        match self.alloc.location(dst) {
+            Location::Reg(reg) if reg == src => (),
+            Location::Reg(reg) => self.instr(format_args!("mov {reg}, {src}")),
+            Location::Stack(slot) => {
+                let (size, operand) = self.spill_slot(cfg, dst, slot);
+                let [q, d, w, b] = src.names();
+                let src = match size {
+                    1 => b,
+                    2 => w,
+                    4 => d,
+                    _ => q,
+                };
+                self.instr(format_args!("mov {operand}, {src}"));
+            }
+        }
+    }
+
+    /// Truncates `reg` to the width of `_type`, then sign or zero extends it
+    /// back to 64 bits.
+    fn extend(&mut self, reg: Reg, _type: &TypeInstance) {
+        let [q, d, w, b] = reg.names();
+        let signed = self.layout.is_signed(_type).unwrap_or(true);
+        match (self.layout.size_of(_type), signed) {
+            (Some(1), true) => self.instr(format_args!("movsx {q}, {b}")),
+            (Some(1), false) => self.instr(format_args!("movzx {d}, {b}")),
+            (Some(2), true) => self.instr(format_args!("movsx {q}, {w}")),
+            (Some(2), false) => self.instr(format_args!("movzx {d}, {w}")),
+            (Some(4), true) => self.instr(format_args!("movsxd {q}, {d}")),
+            (Some(4), false) => self.instr(format_args!("mov {d}, {d}")),
+            _ => (),
+        }
+    }
+}
+
+fn has_phis(cfg: &Cfg, block: BlockId) -> bool {
+    matches!(cfg.block(block).instrs().first(), Some(Instruction::Phi(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        frontend::{cfg::Cfg, parser, symboltable::SymbolMap},
+        ir::text::parse,
+        types::layout::TargetLayout,
+    };
+
+    use super::{emit, Allocator, CodegenError};
+
+    #[test]
+    fn emits_an_executable() {
+        let source = parser::parse("int main() { int a = 5; return a * 2; }", 0).unwrap();
+        let mut map = SymbolMap::new();
+        map.fill_from_source(&source.0).unwrap();
+        let cfgs = Cfg::fill_from_source(&source, &map, &TargetLayout::LP64);
+        let asm = emit(&cfgs, Allocator::Stack, &TargetLayout::LP64).unwrap();
+
+        assert!(asm.starts_with("format ELF64 executable 3\n"));
+        assert!(asm.contains("\nentry start\n"));
+        assert!(asm.contains("\tcall _main\n\tmov edi, eax\n"));
+        assert!(asm.contains("\n_main:\n\tpush rbp\n\tmov rbp, rsp\n\tsub rsp, 16\n.bb0:\n"));
+        assert!(asm.contains("\tmov rax, 5\n\tmovsxd rax, eax\n\tmov dword [rbp-4], eax\n"));
+        assert!(asm.contains("\timul rax, rcx\n"));
+        assert!(asm.ends_with("\tleave\n\tret\n"));
+    }
+
+    #[test]
+    fn phis_are_assigned_on_edges() {
+        let cfgs = parse(
+            "\
+fn main() -> int {
+    int %c
+    int %i
+bb0:
+    %c <- int 1
+    branch %c, bb1, bb2
+bb1:
+    jump bb2
+bb2:
+    %i = phi [bb0: int 3, bb1: int 4]
+    ret %i
+}
+",
+        )
+        .unwrap();
+        let asm = emit(&cfgs, Allocator::Stack, &TargetLayout::LP64).unwrap();
+        assert!(asm.contains("\tcmp dword [rbp-4], 0\n\tjz .bb0_bb2\n\tjmp .bb1\n.bb0_bb2:\n"));
+        // Constants are copied straight onto the stack, and the spilled
+        // `%i` read back through `rax`.
+        assert!(asm.contains("\tsub rsp, 8\n\tmov qword [rsp+0], 3\n\tmov rax, qword [rsp+0]\n"));
+        assert!(asm.contains("\tmov qword [rsp+0], 4\n"));
+        assert!(asm.contains("\tmov dword [rbp-8], eax\n\tadd rsp, 8\n\tjmp .bb2\n"));
+    }
+
+    #[test]
+    fn values_across_calls_use_callee_saved_registers() {
+        let cfgs = parse(
+            "\
+fn f(int %x) -> int {
+bb0:
+    ret %x
+}
+
+fn main() -> int {
+    int %a
+    int %b
+bb0:
+    %a <- int 2
+    %b = call f(%a)
+    %b = %b + %a
+    ret %b
+}
+",
+        )
+        .unwrap();
+        let asm = emit(&cfgs, Allocator::LinearScan, &TargetLayout::LP64).unwrap();
+        assert!(asm
+            .contains("\n_f:\n\tpush rbp\n\tmov rbp, rsp\n\tpush rdi\n\tmov rsi, qword [rsp+0]\n"));
+        assert!(asm.contains("\tmovsxd rsi, esi\n\tadd rsp, 8\n.bb0:\n"));
+        assert!(asm.contains("\tsub rsp, 16\n\tmov qword [rbp-8], rbx\n.bb0:\n"));
+        assert!(asm.contains("\tsub rsp, 8\n\tmov qword [rsp+0], rbx\n\tpop rdi\n\tcall _f\n"));
+        assert!(asm.ends_with("\tmov rbx, qword [rbp-8]\n\tleave\n\tret\n"));
+    }
+
+    #[test]
+    fn scratch_registers_are_allocated_too() {
+        let mut text = String::from("fn main() -> int {\n    int %q\n");
+        for i in 0..14 {
+            text.push_str(&format!("    int %v{i}\n"));
+        }
+        text.push_str("bb0:\n");
+        for i in 0..14 {
+            text.push_str(&format!("    %v{i} <- int {i}\n"));
+        }
+        text.push_str("    %q = %v13 / %v3\n");
+        for i in 0..14 {
+            text.push_str(&format!("    %q = %q + %v{i}\n"));
+        }
+        text.push_str("    ret %q\n}\n");
+        let asm = emit(
+            &parse(&text).unwrap(),
+            Allocator::LinearScan,
+            &TargetLayout::LP64,
+        )
+        .unwrap();
+        assert!(asm.contains("\t; %9 <- 8\n\tmov rax, 8\n\tmovsxd rax, eax\n\t; %10 <- 9\n"));
+        // `%0` is spilled, and the values in the registers the division
+        // needs are saved around it.
+        assert!(asm.contains("\tpush rax\n\tpush rdx\n\tpush rcx\n\tmov rcx, r9\n"));
+        assert!(asm.contains(
+            "\tidiv rcx\n\tmovsxd rax, eax\n\tmov dword [rbp-44], eax\n\tpop rcx\n\tpop rdx\n\tpop rax\n"
+        ));
+    }
+
+    #[test]
+    fn stack_slots_are_sized_by_the_layout() {
+        let cfgs = parse(
+            "\
+fn main() -> int {
+    char %c
+    long %l
+    ushort %s
+bb0:
+    %c <- char 1
+    %l <- long 2
+    %s <- ushort 3
+    %l = %l + %c
+    %l = %l + %s
+    ret %l
+}
+",
+        )
+        .unwrap();
+        let asm = emit(&cfgs, Allocator::Stack, &TargetLayout::LP64).unwrap();
+        assert!(asm.contains("\tsub rsp, 32\n"));
+        assert!(asm.contains("\tmov byte [rbp-1], al\n"));
+        assert!(asm.contains("\tmov qword [rbp-16], rax\n"));
+        assert!(asm.contains("\tmov word [rbp-18], ax\n"));
+        assert!(asm.contains("\tmov rax, qword [rbp-16]\n"));
+        assert!(asm.contains("\tmovsx rcx, byte [rbp-1]\n"));
+        assert!(asm.contains("\tmovzx ecx, word [rbp-18]\n"));
+    }
+
+    #[test]
+    fn missing_functions() {
+        let cfgs = parse("fn f() -> int {\nbb0:\n ret int 0\n}").unwrap();
+        assert!(matches!(
+            emit(&cfgs, Allocator::Stack, &TargetLayout::LP64),
+            Err(CodegenError::NoMain)
+        ));
+
+        let cfgs = parse("fn main() -> int {\n int %x\nbb0:\n %x = call g()\n ret %x\n}").unwrap();
+        let err = emit(&cfgs, Allocator::Stack, &TargetLayout::LP64).unwrap_err();
+        assert_eq!(err.to_string(), "in `main`, call to unknown function `g`");
+    }
+}
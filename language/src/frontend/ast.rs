@@ -1,19 +1,26 @@
 use std::fmt::Display;
 
-use crate::types::designators::TypeInstance;
+use crate::types::{designators::TypeInstance, layout::TargetLayout};
 
+use super::span::Span;
+
+#[derive(Debug)]
 pub struct Source<'a>(pub Vec<AST<'a>>);
 
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug)]
 pub struct AST<'a> {
     pub kind: ASTKind<'a>,
+    pub span: Span,
 }
 
 impl<'a> AST<'a> {
-    pub fn new(kind: ASTKind<'a>) -> Self {
-        Self { kind }
+    pub fn new(kind: ASTKind<'a>, span: Span) -> Self {
+        Self { kind, span }
     }
 }
 
+#[derive(Debug)]
 pub enum ASTKind<'a> {
     Val(Value),
     VarDec(Variable<'a>),
@@ -22,30 +29,54 @@ pub enum ASTKind<'a> {
     FuncDef(FuncDef<'a>),
     Func(Func<'a>),
     Return(Return<'a>),
+    Block(Block<'a>),
 }
 
+/// Nodes print as the C they were parsed from. Expressions print without
+/// the `;` that ends them as statements, which the enclosing body adds.
 impl<'a> Display for ASTKind<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ASTKind::Expr(expr) => write!(f, "{}", expr),
-            ASTKind::Val(val) => write!(f, "{}", val),
+            ASTKind::Val(val) => write!(f, "{}", val.literal()),
             ASTKind::VarDec(var) => write!(f, "{}", var),
-            ASTKind::FuncDef(fdef) => write!(f, "{}", fdef),
+            ASTKind::FuncDef(fdef) => write!(f, "{};", fdef),
             ASTKind::Return(ret) => write!(f, "{}", ret),
             ASTKind::Param(param) => write!(f, "{}", param),
             ASTKind::Func(func) => write!(f, "{}", func),
+            ASTKind::Block(block) => write!(f, "{}", block),
         }
     }
 }
 
 /// Values that have no associated identifiers with them by default
 /// Like numbers and string literals.
-#[derive(Copy, Clone, Eq, PartialEq, PartialOrd, Ord)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
 pub enum Value {
     Integer(SignKind),
 }
 
 impl Value {
+    /// A zero of the given type, `None` for `void`.
+    pub fn zero(_type: &TypeInstance) -> Option<Value> {
+        let zero = match _type {
+            TypeInstance::Void => return None,
+            TypeInstance::Char | TypeInstance::SChar => SignKind::Signed(Signed::Char(0)),
+            TypeInstance::Short => SignKind::Signed(Signed::Short(0)),
+            TypeInstance::Long => SignKind::Signed(Signed::Long(0)),
+            TypeInstance::LongLong => SignKind::Signed(Signed::LongLong(0)),
+            TypeInstance::Int | TypeInstance::Func(..) | TypeInstance::Ptr(_) => {
+                SignKind::Signed(Signed::Int(0))
+            }
+            TypeInstance::UChar => SignKind::Unsigned(Unsigned::Char(0)),
+            TypeInstance::UShort => SignKind::Unsigned(Unsigned::Short(0)),
+            TypeInstance::UInt => SignKind::Unsigned(Unsigned::Int(0)),
+            TypeInstance::ULong => SignKind::Unsigned(Unsigned::Long(0)),
+            TypeInstance::ULongLong => SignKind::Unsigned(Unsigned::LongLong(0)),
+        };
+        Some(Value::Integer(zero))
+    }
+
     pub fn get_type(&self) -> TypeInstance {
         match self {
             Value::Integer(SignKind::Signed(Signed::Char(_))) => TypeInstance::Char,
@@ -53,16 +84,84 @@ impl Value {
             Value::Integer(SignKind::Signed(Signed::Short(_))) => TypeInstance::Short,
             Value::Integer(SignKind::Signed(Signed::Long(_))) => TypeInstance::Long,
             Value::Integer(SignKind::Signed(Signed::LongLong(_))) => TypeInstance::LongLong,
-            Value::Integer(SignKind::Unsigned(Unsigned::Char(_))) => TypeInstance::Char,
-            Value::Integer(SignKind::Unsigned(Unsigned::Int(_))) => TypeInstance::Int,
-            Value::Integer(SignKind::Unsigned(Unsigned::Short(_))) => TypeInstance::Short,
-            Value::Integer(SignKind::Unsigned(Unsigned::Long(_))) => TypeInstance::Long,
-            Value::Integer(SignKind::Unsigned(Unsigned::LongLong(_))) => TypeInstance::LongLong,
+            Value::Integer(SignKind::Unsigned(Unsigned::Char(_))) => TypeInstance::UChar,
+            Value::Integer(SignKind::Unsigned(Unsigned::Int(_))) => TypeInstance::UInt,
+            Value::Integer(SignKind::Unsigned(Unsigned::Short(_))) => TypeInstance::UShort,
+            Value::Integer(SignKind::Unsigned(Unsigned::Long(_))) => TypeInstance::ULong,
+            Value::Integer(SignKind::Unsigned(Unsigned::LongLong(_))) => TypeInstance::ULongLong,
+        }
+    }
+
+    /// The value as a C literal, with the suffix that gives it its type.
+    /// `char` and `short` have no literals and print as their value.
+    pub fn literal(&self) -> String {
+        let suffix = match self {
+            Value::Integer(SignKind::Signed(Signed::Long(_))) => "l",
+            Value::Integer(SignKind::Signed(Signed::LongLong(_))) => "ll",
+            Value::Integer(SignKind::Unsigned(Unsigned::Int(_))) => "u",
+            Value::Integer(SignKind::Unsigned(Unsigned::Long(_))) => "ul",
+            Value::Integer(SignKind::Unsigned(Unsigned::LongLong(_))) => "ull",
+            _ => "",
+        };
+        format!("{self}{suffix}")
+    }
+
+    pub fn to_i128(&self) -> i128 {
+        match *self {
+            Value::Integer(SignKind::Signed(Signed::Char(n))) => n.into(),
+            Value::Integer(SignKind::Signed(Signed::Short(n))) => n.into(),
+            Value::Integer(SignKind::Signed(Signed::Int(n))) => n.into(),
+            Value::Integer(SignKind::Signed(Signed::Long(n))) => n.into(),
+            Value::Integer(SignKind::Signed(Signed::LongLong(n))) => n.into(),
+            Value::Integer(SignKind::Unsigned(Unsigned::Char(n))) => n.into(),
+            Value::Integer(SignKind::Unsigned(Unsigned::Short(n))) => n.into(),
+            Value::Integer(SignKind::Unsigned(Unsigned::Int(n))) => n.into(),
+            Value::Integer(SignKind::Unsigned(Unsigned::Long(n))) => n.into(),
+            Value::Integer(SignKind::Unsigned(Unsigned::LongLong(n))) => n.into(),
         }
     }
+
+    /// The value converted to `_type` as C does, wrapping around to its
+    /// width on `layout`. `None` for `void`.
+    pub fn convert(&self, _type: &TypeInstance, layout: &TargetLayout) -> Option<Value> {
+        Value::wrap(self.to_i128(), _type, layout)
+    }
+
+    /// `n` truncated to the width of `_type` on `layout`, `None` for `void`.
+    /// Functions are as wide as pointers.
+    pub fn wrap(n: i128, _type: &TypeInstance, layout: &TargetLayout) -> Option<Value> {
+        let bits = match _type {
+            TypeInstance::Func(..) => layout.pointer,
+            _type => layout.size_of(_type)?,
+        } * 8;
+        let signed = layout.is_signed(_type).unwrap_or(true);
+        let n = n & ((1 << bits) - 1);
+        let n = if signed && n >> (bits - 1) == 1 {
+            n - (1 << bits)
+        } else {
+            n
+        };
+        let int = match _type {
+            TypeInstance::Void => return None,
+            TypeInstance::Char if !signed => SignKind::Unsigned(Unsigned::Char(n as u8)),
+            TypeInstance::Char | TypeInstance::SChar => SignKind::Signed(Signed::Char(n as i8)),
+            TypeInstance::Short => SignKind::Signed(Signed::Short(n as i16)),
+            TypeInstance::Int => SignKind::Signed(Signed::Int(n as i32)),
+            TypeInstance::Long | TypeInstance::Ptr(_) | TypeInstance::Func(..) => {
+                SignKind::Signed(Signed::Long(n as i64))
+            }
+            TypeInstance::LongLong => SignKind::Signed(Signed::LongLong(n as i64)),
+            TypeInstance::UChar => SignKind::Unsigned(Unsigned::Char(n as u8)),
+            TypeInstance::UShort => SignKind::Unsigned(Unsigned::Short(n as u16)),
+            TypeInstance::UInt => SignKind::Unsigned(Unsigned::Int(n as u32)),
+            TypeInstance::ULong => SignKind::Unsigned(Unsigned::Long(n as u64)),
+            TypeInstance::ULongLong => SignKind::Unsigned(Unsigned::LongLong(n as u64)),
+        };
+        Some(Value::Integer(int))
+    }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, PartialOrd, Ord)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
 pub enum Signed {
     Char(i8),
     Short(i16),
@@ -71,7 +170,7 @@ pub enum Signed {
     LongLong(i64),
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, PartialOrd, Ord)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
 pub enum Unsigned {
     Char(u8),
     Short(u16),
@@ -80,7 +179,7 @@ pub enum Unsigned {
     LongLong(u64),
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, PartialOrd, Ord)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
 pub enum SignKind {
     Signed(Signed),
     Unsigned(Unsigned),
@@ -123,7 +222,6 @@ impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::Integer(i) => write!(f, "{i}"),
-            _ => todo!(),
         }
     }
 }
@@ -131,18 +229,24 @@ impl Display for Value {
 /// Values or Expressions of values that are assosiated with an identifier
 /// in the source language
 /// ex. int a = 42 + 3 - b;
-pub struct Variable<'a>(pub TypeInstance, pub &'a str, pub Expr<'a>);
+/// The span covers the identifier.
+#[derive(Debug)]
+pub struct Variable<'a>(pub TypeInstance, pub &'a str, pub Expr<'a>, pub Span);
 impl<'a> Display for Variable<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "{} {} = {};", self.0, self.1, self.2)
+        write!(f, "{} {} = {};", self.0, self.1, self.2)
     }
 }
 
 ///Expressions are collections of values and/or variables that are not
 ///necessarily assosiated with an identifier but might be.
+#[derive(Debug)]
 pub enum Expr<'a> {
     Binary(BinExpr<'a>),
     Unary(UnaryExpr<'a>),
+    Call(CallExpr<'a>),
+    Assign(AssignExpr<'a>),
+    SizeOf(SizeOfExpr),
     Noop(Value),
 }
 
@@ -151,23 +255,96 @@ impl<'a> Display for Expr<'a> {
         match self {
             Expr::Binary(bin) => write!(f, "{bin}"),
             Expr::Unary(un) => write!(f, "{un}"),
-            Expr::Noop(no) => write!(f, "{no}"),
+            Expr::Call(call) => write!(f, "{call}"),
+            Expr::Assign(assign) => write!(f, "{assign}"),
+            Expr::SizeOf(size) => write!(f, "{size}"),
+            Expr::Noop(no) => write!(f, "{}", no.literal()),
         }
     }
 }
 
+#[derive(Debug)]
 pub struct BinExpr<'a> {
     pub lhs: Box<AST<'a>>,
     pub op: BinOp,
     pub rhs: Box<AST<'a>>,
 }
 
+/// Operands are parenthesised where the tree differs from how the printed
+/// expression would parse: lower precedence on either side, equal on the
+/// right since the operators associate to the left.
 impl<'a> Display for BinExpr<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} {} {}", self.lhs.kind, self.op, self.rhs.kind)
+        let grouped = |operand: &AST, right: bool| match &operand.kind {
+            ASTKind::Expr(Expr::Binary(bin)) => {
+                let (inner, outer) = (bin.op.precedence(), self.op.precedence());
+                inner < outer || (right && inner == outer)
+            }
+            ASTKind::Expr(Expr::Assign(_)) => true,
+            _ => false,
+        };
+        for (operand, right) in [(&self.lhs, false), (&self.rhs, true)] {
+            if right {
+                write!(f, " {} ", self.op)?;
+            }
+            if grouped(operand, right) {
+                write!(f, "({})", operand.kind)?;
+            } else {
+                write!(f, "{}", operand.kind)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `func(args)`, spanning the whole call.
+#[derive(Debug)]
+pub struct CallExpr<'a> {
+    pub func: Id<'a>,
+    pub args: Vec<AST<'a>>,
+    pub span: Span,
+}
+
+impl<'a> Display for CallExpr<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}(", self.func)?;
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", arg.kind)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// `target = value`, which evaluates to the new value of `target`.
+#[derive(Debug)]
+pub struct AssignExpr<'a> {
+    pub target: Id<'a>,
+    pub value: Box<AST<'a>>,
+}
+
+impl<'a> Display for AssignExpr<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} = {}", self.target, self.value.kind)
+    }
+}
+
+/// `sizeof(type)`, spanning from the keyword to the closing parenthesis.
+#[derive(Debug)]
+pub struct SizeOfExpr {
+    pub _type: TypeInstance,
+    pub span: Span,
+}
+
+impl Display for SizeOfExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "sizeof({})", self._type)
     }
 }
 
+#[derive(Debug)]
 pub enum UnaryExpr<'a> {
     Id(Id<'a>),
 }
@@ -176,12 +353,13 @@ impl<'a> Display for UnaryExpr<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             UnaryExpr::Id(id) => write!(f, "{}", id),
-            _ => todo!(),
         }
     }
 }
 
-pub struct Id<'a>(&'a str);
+/// A use of a name, spanning it.
+#[derive(Debug)]
+pub struct Id<'a>(pub &'a str, pub Span);
 
 impl<'a> Display for Id<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -189,6 +367,7 @@ impl<'a> Display for Id<'a> {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum BinOp {
     Add,
     Sub,
@@ -196,7 +375,17 @@ pub enum BinOp {
     Div,
 }
 
-impl<'a> Display for BinOp {
+impl BinOp {
+    /// How tightly the operator binds, higher first.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinOp::Add | BinOp::Sub => 1,
+            BinOp::Mul | BinOp::Div => 2,
+        }
+    }
+}
+
+impl Display for BinOp {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             BinOp::Add => write!(f, "+"),
@@ -207,49 +396,100 @@ impl<'a> Display for BinOp {
     }
 }
 
-pub struct Param<'a>(pub TypeInstance, pub &'a str);
+/// A function parameter, spanning its name.
+#[derive(Debug)]
+pub struct Param<'a>(pub TypeInstance, pub &'a str, pub Span);
 
 impl<'a> Display for Param<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{} {}", self.0, self.1)
     }
 }
-pub struct FuncDef<'a>(pub TypeInstance, pub &'a str, pub Vec<Param<'a>>);
+/// Return type, name, parameters and the span of the name.
+#[derive(Debug)]
+pub struct FuncDef<'a>(pub TypeInstance, pub &'a str, pub Vec<Param<'a>>, pub Span);
+
+impl<'a> FuncDef<'a> {
+    /// The type of the function this declares.
+    pub fn get_type(&self) -> TypeInstance {
+        let params = self.2.iter().map(|param| param.0.clone()).collect();
+        TypeInstance::Func(Box::new(self.0.clone()), self.1.to_string(), Some(params))
+    }
+}
 
+/// The signature, without the `;` of a prototype.
 impl<'a> Display for FuncDef<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} {}(", self.0, self.1);
-        for param in self.2.iter() {
-            write!(f, "{},", param);
+        write!(f, "{} {}(", self.0, self.1)?;
+        for (i, param) in self.2.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", param)?;
         }
-        writeln!(f, ");")
+        write!(f, ")")
     }
 }
 
+#[derive(Debug)]
 pub struct Func<'a>(pub FuncDef<'a>, pub Vec<AST<'a>>);
 
 impl<'a> Display for Func<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "{} {{", self.0);
-        for ast in self.1.iter() {
-            writeln!(f, "{}", ast.kind)?;
+        write!(f, "{} ", self.0)?;
+        write_body(f, &self.1, 0)
+    }
+}
+
+/// A `{}` compound statement, opening a new scope.
+#[derive(Debug)]
+pub struct Block<'a>(pub Vec<AST<'a>>);
+
+impl<'a> Display for Block<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_body(f, &self.0, 0)
+    }
+}
+
+/// Writes `stmts` between braces, one per line, indented one level deeper
+/// than the closing brace at `depth`.
+fn write_body(f: &mut std::fmt::Formatter<'_>, stmts: &[AST], depth: usize) -> std::fmt::Result {
+    writeln!(f, "{{")?;
+    for stmt in stmts.iter() {
+        write!(f, "{:1$}", "", 4 * (depth + 1))?;
+        match &stmt.kind {
+            ASTKind::Block(block) => write_body(f, &block.0, depth + 1)?,
+            ASTKind::Expr(_) | ASTKind::Val(_) => write!(f, "{};", stmt.kind)?,
+            kind => write!(f, "{kind}")?,
         }
-        writeln!(f, "}}")
+        writeln!(f)?;
     }
+    write!(f, "{:1$}}}", "", 4 * depth)
 }
-pub struct Return<'a>(pub Box<AST<'a>>);
+
+/// `return`, with the value unless the function returns `void`.
+#[derive(Debug)]
+pub struct Return<'a>(pub Option<Box<AST<'a>>>);
 
 impl<'a> Display for Return<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "return {};", self.0.kind)
+        match &self.0 {
+            Some(value) => write!(f, "return {};", value.kind),
+            None => write!(f, "return;"),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        frontend::ast::{
-            BinExpr, BinOp, Expr, Func, FuncDef, Return, SignKind, Unsigned, Value, Variable, AST,
+        frontend::{
+            ast::{
+                BinExpr, BinOp, Expr, Func, FuncDef, Return, SignKind, Source, Unsigned, Value,
+                Variable, AST,
+            },
+            parser::parse,
+            span::Span,
         },
         types::designators::TypeInstance,
     };
@@ -259,28 +499,109 @@ mod tests {
     #[test]
     fn print_simple_cprogram() {
         let expr = Expr::Binary(BinExpr {
-            lhs: Box::new(AST::new(ASTKind::Val(Value::Integer(SignKind::Unsigned(
-                Unsigned::Int(5),
-            ))))),
+            lhs: Box::new(AST::new(
+                ASTKind::Val(Value::Integer(SignKind::Unsigned(Unsigned::Int(5)))),
+                Span::default(),
+            )),
             op: BinOp::Add,
-            rhs: Box::new(AST::new(ASTKind::Val(Value::Integer(SignKind::Unsigned(
-                Unsigned::Int(10),
-            ))))),
+            rhs: Box::new(AST::new(
+                ASTKind::Val(Value::Integer(SignKind::Unsigned(Unsigned::Int(10)))),
+                Span::default(),
+            )),
         });
 
-        let var = AST::new(ASTKind::VarDec(Variable(TypeInstance::Int, "a", expr)));
-        let ret = Return(Box::new(AST {
+        let var = AST::new(
+            ASTKind::VarDec(Variable(TypeInstance::Int, "a", expr, Span::default())),
+            Span::default(),
+        );
+        let ret = Return(Some(Box::new(AST {
             kind: ASTKind::Val(Value::Integer(SignKind::Unsigned(Unsigned::Int(0)))),
-        }));
+            span: Span::default(),
+        })));
 
         let ret_ast = AST {
             kind: ASTKind::Return(ret),
+            span: Span::default(),
         };
 
         let ast = ASTKind::Func(Func(
-            FuncDef(TypeInstance::Void, "main", Vec::new()),
+            FuncDef(TypeInstance::Void, "main", Vec::new(), Span::default()),
             vec![var, ret_ast],
         ));
-        println!("{}", ast);
+        assert_eq!(
+            ast.to_string(),
+            "void main() {\n    int a = 5u + 10u;\n    return 0u;\n}"
+        );
+    }
+
+    /// The `Debug` form of a tree, without the spans that differ between
+    /// the original and the reprinted source.
+    fn shape(source: &Source) -> String {
+        let mut tree = format!("{source:?}");
+        while let Some(start) = tree.find("Span {") {
+            let end = start + tree[start..].find('}').unwrap();
+            tree.replace_range(start..=end, "_");
+        }
+        tree
+    }
+
+    #[test]
+    fn printed_source_parses_back_to_the_same_tree() {
+        let src = "\
+int f(int a, unsigned long b);
+long g(void);
+int f(int a, unsigned long b) {
+    long l = 3000000000;
+    l = l + 7u * b;
+    {
+        int a = 0x10ULL - 1l;
+        f(a, sizeof(char));
+        a;
+    }
+    return (a + 2) * l;
+}
+";
+        let source = parse(src, 0).unwrap();
+        let printed: String = source
+            .0
+            .iter()
+            .map(|item| format!("{}\n", item.kind))
+            .collect();
+        assert_eq!(
+            printed,
+            "\
+int f(int a, unsigned long b);
+long g();
+int f(int a, unsigned long b) {
+    long l = 3000000000l;
+    l = l + 7u * b;
+    {
+        int a = 16ull - 1l;
+        f(a, sizeof(char));
+        a;
+    }
+    return (a + 2) * l;
+}
+"
+        );
+        assert_eq!(shape(&parse(&printed, 0).unwrap()), shape(&source));
+    }
+
+    #[test]
+    fn nested_binary_expressions_keep_their_grouping() {
+        for (src, printed) in [
+            ("(x + y) / 2", "(x + y) / 2"),
+            ("x + y / 2", "x + y / 2"),
+            ("x - (y - 1) - (x * y)", "x - (y - 1) - x * y"),
+            ("x / (y * 2) * (x = 3)", "x / (y * 2) * (x = 3)"),
+            ("f((x - y) * 2, x - y)", "f((x - y) * 2, x - y)"),
+        ] {
+            let src = format!("int f(int x, int y) {{ return {src}; }}");
+            let source = parse(&src, 0).unwrap();
+            let ASTKind::Func(func) = &source.0[0].kind else {
+                panic!("expected a function")
+            };
+            assert_eq!(func.1[0].kind.to_string(), format!("return {printed};"));
+        }
     }
 }
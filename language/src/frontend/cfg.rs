@@ -1,142 +1,784 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use crate::{
+    ir::{
+        text::print_function, BinAssign, BlockId, Call, Instruction, Move, Operand, SingleAssign,
+        Terminator, ValueData, ValueId,
+    },
+    types::{designators::TypeInstance, layout::TargetLayout},
+};
+
 use super::{
-    ast::{ASTKind, BinExpr, BinOp, Expr, Source, UnaryExpr, Value},
-    symboltable::{Symbol, SymbolError, SymbolMap},
+    ast::{ASTKind, AssignExpr, BinExpr, CallExpr, Expr, Func, Source, UnaryExpr, Value},
+    symboltable::{SymbolId, SymbolMap},
+    typeck::{common_type, is_integer},
 };
 
-pub enum Instruction<'a> {
-    BAssign(BinAssign<'a>),
-    SAssign(SingleAssign<'a>),
-    Mov(Move<'a>),
-    Goto(Goto<'a>),
-    Call(Call<'a>),
+/// A straight-line run of instructions closed by a terminator. The `prev`
+/// and `next` edges are kept in sync with the terminators by `Cfg::set_term`.
+#[derive(Debug)]
+pub struct BasicBlock {
+    prev: Option<Vertices>,
+    instrs: Vec<Instruction>,
+    term: Terminator,
+    next: Option<Vertices>,
+    id: BlockId,
 }
 
-impl<'a> Instruction<'a> {}
+impl BasicBlock {
+    fn new(id: BlockId) -> Self {
+        Self {
+            prev: None,
+            instrs: Vec::new(),
+            term: Terminator::Unreachable,
+            next: None,
+            id,
+        }
+    }
+
+    pub fn add(&mut self, instr: Instruction) {
+        self.instrs.push(instr)
+    }
 
-pub struct BinAssign<'a> {
-    lhs: &'a str,
-    lop: &'a str,
-    op: BinOp,
-    rop: &'a str,
-}
+    pub fn id(&self) -> BlockId {
+        self.id
+    }
 
-pub struct SingleAssign<'a> {
-    lhs: &'a str,
-    rhs: &'a Symbol,
-}
+    pub fn instrs(&self) -> &[Instruction] {
+        &self.instrs
+    }
 
-pub struct Move<'a> {
-    lhs: &'a str,
-    rhs: &'a str,
-}
+    pub fn instrs_mut(&mut self) -> &mut Vec<Instruction> {
+        &mut self.instrs
+    }
 
-pub struct Goto<'a> {
-    id: BlockId<'a>,
+    pub fn term(&self) -> &Terminator {
+        &self.term
+    }
+
+    /// Operands of the terminator may be rewritten freely, its targets only
+    /// through `Cfg::set_term`.
+    pub fn term_operands_mut(&mut self) -> Vec<&mut Operand> {
+        self.term.operands_mut()
+    }
+
+    pub fn prev(&self) -> Option<&Vertices> {
+        self.prev.as_ref()
+    }
+
+    pub fn next(&self) -> Option<&Vertices> {
+        self.next.as_ref()
+    }
 }
 
-pub struct Call<'a> {
-    id: BlockId<'a>,
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Vertices {
+    Linear(BlockId),
+    Branch(Vec<BlockId>),
 }
 
-pub struct DebugPrint {
-    val: String,
+impl Vertices {
+    fn from_ids(mut ids: Vec<BlockId>) -> Option<Self> {
+        match ids.len() {
+            0 => None,
+            1 => ids.pop().map(Vertices::Linear),
+            _ => Some(Vertices::Branch(ids)),
+        }
+    }
+
+    pub fn ids(&self) -> Vec<BlockId> {
+        match self {
+            Vertices::Linear(id) => vec![*id],
+            Vertices::Branch(ids) => ids.clone(),
+        }
+    }
 }
 
-pub type BlockId<'a> = &'a str;
+/// The control flow graph of a single function. Blocks and values are
+/// owned by the graph and referred to by index.
+#[derive(Debug)]
+pub struct Cfg {
+    name: String,
+    ret: TypeInstance,
+    params: Vec<ValueId>,
+    blocks: Vec<BasicBlock>,
+    values: Vec<ValueData>,
+    /// Every value name in use, so temporaries can skip them.
+    names: HashSet<String>,
+    /// Counter naming the temporaries of this function.
+    temps: usize,
+}
 
-pub struct BasicBlock<'a> {
-    prev: Option<Vertices<'a>>,
-    instrs: Vec<Instruction<'a>>,
-    next: Option<Vertices<'a>>,
-    id: BlockId<'a>,
+/// The textual IR of `ir::text`.
+impl Display for Cfg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", print_function(self))
+    }
 }
 
-impl<'a> BasicBlock<'a> {
-    fn new(prev: Option<Vertices<'a>>, name: &'a str) -> Self {
+impl Cfg {
+    /// An empty function without blocks.
+    pub fn new(name: impl Into<String>, ret: TypeInstance) -> Self {
         Self {
-            prev,
-            instrs: Vec::new(),
-            next: None,
-            id: name,
+            name: name.into(),
+            ret,
+            params: Vec::new(),
+            blocks: Vec::new(),
+            values: Vec::new(),
+            names: HashSet::new(),
+            temps: 0,
         }
     }
 
-    fn entry() -> Self {
-        Self {
-            prev: None,
-            instrs: Vec::new(),
-            next: None,
-            id: "entry",
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn ret_type(&self) -> &TypeInstance {
+        &self.ret
+    }
+
+    pub fn params(&self) -> &[ValueId] {
+        &self.params
+    }
+
+    pub fn add_param(&mut self, name: impl Into<String>, _type: TypeInstance) -> ValueId {
+        let id = self.new_value(name, _type);
+        self.params.push(id);
+        id
+    }
+
+    /// The first block; execution starts here.
+    pub fn entry(&self) -> BlockId {
+        BlockId(0)
+    }
+
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    pub fn block(&self, id: BlockId) -> &BasicBlock {
+        &self.blocks[id.0]
+    }
+
+    pub fn block_mut(&mut self, id: BlockId) -> &mut BasicBlock {
+        &mut self.blocks[id.0]
+    }
+
+    pub fn values(&self) -> &[ValueData] {
+        &self.values
+    }
+
+    pub fn value(&self, id: ValueId) -> &ValueData {
+        &self.values[id.0]
+    }
+
+    pub fn new_value(&mut self, name: impl Into<String>, _type: TypeInstance) -> ValueId {
+        let name = name.into();
+        self.names.insert(name.clone());
+        self.values.push(ValueData { name, _type });
+        ValueId(self.values.len() - 1)
+    }
+
+    /// Returns a new temporary `tN`, counting up from the last one and
+    /// skipping names already in use.
+    pub fn new_temp(&mut self, _type: TypeInstance) -> ValueId {
+        loop {
+            let name = format!("t{}", self.temps);
+            self.temps += 1;
+            if !self.names.contains(&name) {
+                return self.new_value(name, _type);
+            }
         }
     }
 
-    fn add(&mut self, instr: Instruction<'a>) {
-        self.instrs.push(instr)
+    pub fn operand_type(&self, op: &Operand) -> TypeInstance {
+        match op {
+            Operand::Const(val) => val.get_type(),
+            Operand::Value(id) => self.value(*id)._type.clone(),
+        }
     }
-}
 
-pub enum Vertices<'a> {
-    Linear(BlockId<'a>),
-    Branch(Vec<BlockId<'a>>),
+    /// Appends an unconnected block ending in `Terminator::Unreachable`.
+    pub fn new_block(&mut self) -> BlockId {
+        let id = BlockId(self.blocks.len());
+        self.blocks.push(BasicBlock::new(id));
+        id
+    }
+
+    /// Replaces the terminator of `id` and rewires the edges it implies.
+    pub fn set_term(&mut self, id: BlockId, term: Terminator) {
+        for succ in self.block(id).term.successors() {
+            let block = self.block_mut(succ);
+            let mut preds = block.prev.as_ref().map(Vertices::ids).unwrap_or_default();
+            preds.retain(|p| *p != id);
+            block.prev = Vertices::from_ids(preds);
+        }
+        let succs = term.successors();
+        for succ in succs.iter() {
+            let block = self.block_mut(*succ);
+            let mut preds = block.prev.as_ref().map(Vertices::ids).unwrap_or_default();
+            preds.push(id);
+            block.prev = Vertices::from_ids(preds);
+        }
+        let block = self.block_mut(id);
+        block.next = Vertices::from_ids(succs);
+        block.term = term;
+    }
+
+    pub fn preds(&self, id: BlockId) -> Vec<BlockId> {
+        self.block(id)
+            .prev
+            .as_ref()
+            .map(Vertices::ids)
+            .unwrap_or_default()
+    }
+
+    pub fn succs(&self, id: BlockId) -> Vec<BlockId> {
+        self.block(id).term.successors()
+    }
+
+    /// Renders the graph in Graphviz DOT, one record node per block with
+    /// its instructions, for `dot -Tsvg`.
+    pub fn to_dot(&self) -> String {
+        let mut dot = format!("digraph \"{}\" {{\n", escape_dot(&self.name));
+        dot.push_str("    node [shape=record, fontname=monospace];\n");
+        for block in self.blocks.iter() {
+            let mut label = format!("{{{}|", block.id);
+            for instr in block.instrs.iter() {
+                label.push_str(&escape_dot(&instr.to_string()));
+                label.push_str("\\l");
+            }
+            if !block.instrs.is_empty() {
+                label.push('|');
+            }
+            label.push_str(&escape_dot(&block.term.to_string()));
+            label.push_str("\\l}");
+            dot.push_str(&format!("    {} [label=\"{}\"];\n", block.id, label));
+        }
+        for block in self.blocks.iter() {
+            match &block.term {
+                Terminator::Jump(target) => {
+                    dot.push_str(&format!("    {} -> {};\n", block.id, target));
+                }
+                Terminator::Branch(_, then, _else) => {
+                    dot.push_str(&format!("    {} -> {} [label=\"true\"];\n", block.id, then));
+                    dot.push_str(&format!(
+                        "    {} -> {} [label=\"false\"];\n",
+                        block.id, _else
+                    ));
+                }
+                Terminator::Ret(_) | Terminator::Unreachable => (),
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Builds one CFG per function definition in `source`. `map` must have
+    /// been filled from the same source.
+    pub fn fill_from_source<'a>(
+        source: &Source<'a>,
+        map: &'a SymbolMap,
+        layout: &TargetLayout,
+    ) -> Vec<Cfg> {
+        let mut cfgs = Vec::new();
+        for ast in source.0.iter() {
+            match &ast.kind {
+                ASTKind::Func(func) => {
+                    let mut builder = CfgBuilder::new(func, map, *layout);
+                    builder.fold_func(func);
+                    cfgs.push(builder.cfg);
+                }
+                ASTKind::Expr(_)
+                | ASTKind::Param(_)
+                | ASTKind::Return(_)
+                | ASTKind::Val(_)
+                | ASTKind::VarDec(_)
+                | ASTKind::FuncDef(_)
+                | ASTKind::Block(_) => (),
+            }
+        }
+        cfgs
+    }
 }
 
-pub struct Cfg<'a> {
-    blocks: Vec<BasicBlock<'a>>,
-    map: SymbolMap,
-    pos: usize,
+/// Lowering state for one function.
+struct CfgBuilder<'a> {
+    cfg: Cfg,
+    map: &'a SymbolMap,
+    /// The value of every symbol seen so far.
+    values: HashMap<SymbolId, ValueId>,
+    curr: BlockId,
+    layout: TargetLayout,
 }
 
-impl<'a> Cfg<'a> {
-    fn new(map: SymbolMap) -> Self {
+impl<'a> CfgBuilder<'a> {
+    fn new(func: &Func<'a>, map: &'a SymbolMap, layout: TargetLayout) -> Self {
+        let mut cfg = Cfg::new(func.0 .1, func.0 .0.clone());
+        let mut values = HashMap::new();
+        for param in func.0 .2.iter() {
+            let symbol = map.symbol_at(param.2).unwrap();
+            values.insert(symbol, cfg.add_param(param.1, param.0.clone()));
+        }
+        let curr = cfg.new_block();
         Self {
-            blocks: Vec::new(),
+            cfg,
             map,
-            pos: 0,
+            values,
+            curr,
+            layout,
         }
     }
 
-    fn new_block(&mut self, prev: Option<Vertices>) -> BlockId {
-        todo!()
+    fn add(&mut self, instr: Instruction) {
+        self.cfg.block_mut(self.curr).add(instr)
     }
 
-    fn fold_ast(&mut self, ast: ASTKind, block: &mut BasicBlock) {
+    /// `op` as a `_type`. Constants are converted in place, values through
+    /// a temporary, the only instruction that may change an integer type.
+    fn convert(&mut self, op: Operand, _type: &TypeInstance) -> Operand {
+        let from = self.cfg.operand_type(&op);
+        if from == *_type || !is_integer(&from) || !is_integer(_type) {
+            return op;
+        }
+        match op {
+            Operand::Const(val) => val.convert(_type, &self.layout).map_or(op, Operand::Const),
+            Operand::Value(_) => {
+                let dst = self.cfg.new_temp(_type.clone());
+                self.add(Instruction::SAssign(SingleAssign { dst, src: op }));
+                Operand::Value(dst)
+            }
+        }
+    }
+
+    /// The value behind a symbol, created on first sight.
+    fn value(&mut self, symbol: SymbolId) -> ValueId {
+        if let Some(id) = self.values.get(&symbol) {
+            return *id;
+        }
+        let data = self.map.symbol(symbol);
+        let id = self.cfg.new_value(data.name(), data.get_type());
+        self.values.insert(symbol, id);
+        id
+    }
+
+    fn fold_ast(&mut self, ast: &ASTKind<'a>) {
         match ast {
-            ASTKind::Val(val) => todo!(),
-            ASTKind::VarDec(var) => todo!(),
-            ASTKind::Expr(expr) => todo!(),
-            ASTKind::Return(ret) => todo!(),
-            ASTKind::FuncDef(fdef) => todo!(),
-            ASTKind::Param(param) => todo!(),
-            ASTKind::Func(func) => todo!(),
+            // A literal on its own computes nothing.
+            ASTKind::Val(_) => (),
+            ASTKind::VarDec(var) => {
+                let dst = self.value(self.map.symbol_at(var.3).unwrap());
+                let src = self.fold_expr(&var.2);
+                let src = self.convert(src, &var.0);
+                self.add(Instruction::Mov(Move { dst, src }));
+            }
+            ASTKind::Block(block) => {
+                for ast in block.0.iter() {
+                    self.fold_ast(&ast.kind);
+                }
+            }
+            // Calls to `void` functions are only allowed as statements.
+            ASTKind::Expr(Expr::Call(call)) => {
+                self.fold_call(call);
+            }
+            ASTKind::Expr(expr) => {
+                self.fold_expr(expr);
+            }
+            ASTKind::Return(ret) => {
+                let src = ret.0.as_ref().map(|value| {
+                    let src = self.fold_operand(&value.kind);
+                    self.convert(src, &self.cfg.ret.clone())
+                });
+                self.cfg.set_term(self.curr, Terminator::Ret(src));
+                // Anything following a return is unreachable.
+                self.curr = self.cfg.new_block();
+            }
+            // Declarations inside a body carry no code.
+            ASTKind::FuncDef(_) | ASTKind::Param(_) | ASTKind::Func(_) => (),
         }
     }
 
-    fn fold_expr(&'a mut self, expr: Expr, block: &'a mut BasicBlock<'a>) {
-        todo!()
+    /// Lowers an operand of an expression.
+    fn fold_operand(&mut self, ast: &ASTKind<'a>) -> Operand {
+        match ast {
+            ASTKind::Val(val) => Operand::Const(*val),
+            ASTKind::Expr(expr) => self.fold_expr(expr),
+            _ => unreachable!("operand is not an expression"),
+        }
     }
 
-    fn fold_val(&'a mut self, val: Value, block: &'a mut BasicBlock<'a>) {
-        let name = self.map.get_from_val(self.pos, val).unwrap();
-        let val = self.map.get(self.pos, name).unwrap();
-        let ins = SingleAssign {
-            lhs: name,
-            rhs: val,
-        };
-        block.add(Instruction::SAssign(ins));
-    }
-
-    fn fill_from_source(&mut self, source: Source) {
-        let mut entry = BasicBlock::entry();
-        for ast in source.0.into_iter() {
-            match ast.kind {
-                ASTKind::Expr(expr) => todo!(),
-                ASTKind::Param(param) => todo!(),
-                ASTKind::Return(ret) => todo!(),
-                ASTKind::Val(val) => todo!(),
-                ASTKind::VarDec(var) => todo!(),
-                ASTKind::FuncDef(fdef) => todo!(),
-                ASTKind::Func(func) => todo!(),
+    fn fold_expr(&mut self, expr: &Expr<'a>) -> Operand {
+        match expr {
+            Expr::Binary(BinExpr { lhs, op, rhs }) => {
+                let lop = self.fold_operand(&lhs.kind);
+                let rop = self.fold_operand(&rhs.kind);
+                let (ltype, rtype) = (self.cfg.operand_type(&lop), self.cfg.operand_type(&rop));
+                let _type = if is_integer(&ltype) && is_integer(&rtype) {
+                    common_type(&ltype, &rtype, &self.layout)
+                } else {
+                    ltype
+                };
+                let (lop, rop) = (self.convert(lop, &_type), self.convert(rop, &_type));
+                let dst = self.cfg.new_temp(_type);
+                self.add(Instruction::BAssign(BinAssign {
+                    dst,
+                    lop,
+                    op: *op,
+                    rop,
+                }));
+                Operand::Value(dst)
+            }
+            Expr::Unary(UnaryExpr::Id(id)) => {
+                let symbol = self
+                    .map
+                    .resolved(id)
+                    .expect("names are resolved before lowering");
+                Operand::Value(self.value(symbol))
+            }
+            Expr::Call(call) => self
+                .fold_call(call)
+                .expect("typeck rejects using the result of a `void` call"),
+            Expr::Assign(AssignExpr { target, value }) => {
+                let symbol = self
+                    .map
+                    .resolved(target)
+                    .expect("names are resolved before lowering");
+                let dst = self.value(symbol);
+                let src = self.fold_operand(&value.kind);
+                let src = self.convert(src, &self.cfg.value(dst)._type.clone());
+                self.add(Instruction::Mov(Move { dst, src }));
+                Operand::Value(dst)
             }
+            Expr::SizeOf(size) => {
+                let bytes = self
+                    .layout
+                    .size_of(&size._type)
+                    .expect("typeck rejects `sizeof` of types without a size");
+                let size_type = self.layout.size_type();
+                Operand::Const(
+                    Value::wrap(bytes.into(), &size_type, &self.layout)
+                        .expect("the size type is an integer"),
+                )
+            }
+            Expr::Noop(val) => Operand::Const(*val),
         }
     }
+
+    /// Lowers a call, converting the arguments to the parameter types.
+    /// Returns the result, `None` for `void` functions.
+    fn fold_call(&mut self, call: &CallExpr<'a>) -> Option<Operand> {
+        let symbol = self
+            .map
+            .resolved(&call.func)
+            .expect("names are resolved before lowering");
+        let TypeInstance::Func(ret, _, params) = self.map.symbol(symbol).get_type() else {
+            unreachable!("typeck only lets functions be called")
+        };
+        let params = params.unwrap_or_default();
+        let args = call
+            .args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let op = self.fold_operand(&arg.kind);
+                match params.get(i) {
+                    Some(param) => self.convert(op, param),
+                    None => op,
+                }
+            })
+            .collect();
+        let dst = (*ret != TypeInstance::Void).then(|| self.cfg.new_temp(*ret));
+        self.add(Instruction::Call(Call {
+            dst,
+            func: call.func.0.to_string(),
+            args,
+        }));
+        dst.map(Operand::Value)
+    }
+
+    fn fold_func(&mut self, func: &Func<'a>) {
+        for ast in func.1.iter() {
+            self.fold_ast(&ast.kind);
+        }
+
+        // Drop the empty block opened after a trailing `return`, otherwise
+        // falling off the end returns zero, as `main` is required to.
+        let last = self.cfg.block(self.curr);
+        if self.curr != self.cfg.entry() && last.prev.is_none() && last.instrs.is_empty() {
+            self.cfg.blocks.pop();
+        } else {
+            let ret = Value::zero(&self.cfg.ret).map(Operand::Const);
+            self.cfg.set_term(self.curr, Terminator::Ret(ret));
+        }
+    }
+}
+
+/// Escapes the characters that are special inside a quoted record label.
+fn escape_dot(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '"' | '\\' | '{' | '}' | '|' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        frontend::{
+            ast::{BinOp, SignKind, Signed, Value},
+            parser::parse,
+            symboltable::SymbolMap,
+        },
+        ir::{interp::run, text::print, verify, BlockId, Instruction, Move, Operand, Terminator},
+        types::{designators::TypeInstance, layout::TargetLayout},
+    };
+
+    use super::{Cfg, Vertices};
+
+    fn int(n: i32) -> Value {
+        Value::Integer(SignKind::Signed(Signed::Int(n)))
+    }
+
+    #[test]
+    fn lower_main() {
+        let source = parse("int main() { int a = 5 + 10 * 2; return a; }", 0).unwrap();
+        let mut map = SymbolMap::new();
+        map.fill_from_source(&source.0).unwrap();
+        let cfgs = Cfg::fill_from_source(&source, &map, &TargetLayout::default());
+        assert_eq!(cfgs.len(), 1);
+        let cfg = &cfgs[0];
+        assert_eq!(cfg.name(), "main");
+
+        // The unreachable block opened after `return` is dropped.
+        let blocks = cfg.blocks();
+        assert_eq!(blocks.len(), 1);
+        assert!(blocks[0].next().is_none());
+        assert!(blocks[0].prev().is_none());
+
+        // Literals are immediate operands, only the results need temporaries.
+        let instrs = blocks[0].instrs();
+        assert_eq!(instrs.len(), 3);
+        let Instruction::BAssign(mul) = &instrs[0] else {
+            panic!("expected the multiplication")
+        };
+        assert_eq!(mul.op, BinOp::Mul);
+        assert_eq!(mul.lop, Operand::Const(int(10)));
+        let Instruction::BAssign(add) = &instrs[1] else {
+            panic!("expected the addition")
+        };
+        assert_eq!(add.op, BinOp::Add);
+        assert_eq!(add.lop, Operand::Const(int(5)));
+        assert_eq!(add.rop, Operand::Value(mul.dst));
+        assert_eq!(cfg.value(mul.dst).name, "t0");
+        assert_eq!(cfg.value(add.dst).name, "t1");
+        let Instruction::Mov(a) = &instrs[2] else {
+            panic!("expected the declaration of `a`")
+        };
+        assert_eq!(cfg.value(a.dst).name, "a");
+        assert_eq!(a.src, Operand::Value(add.dst));
+        assert!(matches!(cfg.value(a.dst)._type, TypeInstance::Int));
+        assert_eq!(
+            *blocks[0].term(),
+            Terminator::Ret(Some(Operand::Value(a.dst)))
+        );
+    }
+
+    #[test]
+    fn conversions_are_explicit() {
+        let src = "int main() { unsigned char c = 250; long l = 3; return c + 10 + l; }";
+        let source = parse(src, 0).unwrap();
+        let mut map = SymbolMap::new();
+        map.fill_from_source(&source.0).unwrap();
+        let cfgs = Cfg::fill_from_source(&source, &map, &TargetLayout::default());
+        verify(&cfgs[0]).unwrap();
+        assert_eq!(
+            print(&cfgs),
+            "\
+fn main() -> int {
+    uchar %c
+    long %l
+    int %t0
+    int %t1
+    long %t2
+    long %t3
+    int %t4
+bb0:
+    %c <- uchar 250
+    %l <- long 3
+    %t0 = %c
+    %t1 = %t0 + int 10
+    %t2 = %t1
+    %t3 = %t2 + %l
+    %t4 = %t3
+    ret %t4
+}
+"
+        );
+        assert_eq!(run(&cfgs, &TargetLayout::default()).unwrap(), 263);
+    }
+
+    #[test]
+    fn calls_and_assignments() {
+        let src = "long sq(char c) { return c * c; } void nop() { return; } \
+                   int main() { int x = 2; nop(); x = sq(x + 1); return x; }";
+        let source = parse(src, 0).unwrap();
+        let mut map = SymbolMap::new();
+        map.fill_from_source(&source.0).unwrap();
+        let cfgs = Cfg::fill_from_source(&source, &map, &TargetLayout::default());
+        for cfg in cfgs.iter() {
+            verify(cfg).unwrap();
+        }
+        assert_eq!(
+            cfgs[1].to_string(),
+            "\
+fn nop() -> void {
+bb0:
+    ret
+}
+"
+        );
+        assert_eq!(
+            cfgs[2].to_string(),
+            "\
+fn main() -> int {
+    int %x
+    int %t0
+    char %t1
+    long %t2
+    int %t3
+bb0:
+    %x <- int 2
+    call nop()
+    %t0 = %x + int 1
+    %t1 = %t0
+    %t2 = call sq(%t1)
+    %t3 = %t2
+    %x <- %t3
+    ret %x
+}
+"
+        );
+        assert_eq!(run(&cfgs, &TargetLayout::default()).unwrap(), 9);
+    }
+
+    #[test]
+    fn sizeof_is_a_constant_of_the_layout() {
+        let src = "int main() { return sizeof(long) + sizeof(char) * 10; }";
+        let source = parse(src, 0).unwrap();
+        let mut map = SymbolMap::new();
+        map.fill_from_source(&source.0).unwrap();
+        for (layout, size) in [(TargetLayout::LP64, 18), (TargetLayout::ILP32, 14)] {
+            let cfgs = Cfg::fill_from_source(&source, &map, &layout);
+            let Instruction::BAssign(mul) = &cfgs[0].blocks()[0].instrs()[0] else {
+                panic!("expected the multiplication")
+            };
+            assert_eq!(cfgs[0].operand_type(&mul.lop), layout.size_type());
+            assert_eq!(run(&cfgs, &layout).unwrap(), size);
+        }
+    }
+
+    #[test]
+    fn code_after_return_gets_its_own_block() {
+        let source = parse("int f(int x) { return 1; x * 2; } void g() { }", 0).unwrap();
+        let mut map = SymbolMap::new();
+        map.fill_from_source(&source.0).unwrap();
+        let cfgs = Cfg::fill_from_source(&source, &map, &TargetLayout::default());
+        assert_eq!(cfgs.len(), 2);
+        assert_eq!(cfgs[0].params().len(), 1);
+
+        let blocks = cfgs[0].blocks();
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks[1].prev().is_none());
+        assert!(matches!(blocks[0].term(), Terminator::Ret(Some(_))));
+        assert!(matches!(blocks[1].term(), Terminator::Ret(Some(_))));
+        let Instruction::BAssign(mul) = &blocks[1].instrs()[0] else {
+            panic!("expected the multiplication")
+        };
+        assert_eq!(mul.lop, Operand::Value(cfgs[0].params()[0]));
+        assert_eq!(mul.rop, Operand::Const(int(2)));
+
+        let blocks = cfgs[1].blocks();
+        assert_eq!(blocks.len(), 1);
+        assert!(blocks[0].instrs().is_empty());
+        assert_eq!(*blocks[0].term(), Terminator::Ret(None));
+    }
+
+    #[test]
+    fn edges_follow_terminators() {
+        let mut cfg = Cfg::new("f", TypeInstance::Int);
+        let x = cfg.add_param("x", TypeInstance::Int);
+        let entry = cfg.new_block();
+        let then = cfg.new_block();
+        let _else = cfg.new_block();
+        let join = cfg.new_block();
+        let t = cfg.new_value("t", TypeInstance::Int);
+        cfg.block_mut(then).add(Instruction::Mov(Move {
+            dst: t,
+            src: Operand::Value(x),
+        }));
+        cfg.set_term(entry, Terminator::Branch(Operand::Value(x), then, _else));
+        cfg.set_term(then, Terminator::Jump(join));
+        cfg.set_term(_else, Terminator::Jump(join));
+        cfg.set_term(join, Terminator::Ret(Some(Operand::Value(x))));
+        assert_eq!(
+            cfg.block(entry).next(),
+            Some(&Vertices::Branch(vec![then, _else]))
+        );
+        assert_eq!(
+            cfg.block(join).prev(),
+            Some(&Vertices::Branch(vec![then, _else]))
+        );
+        assert_eq!(cfg.block(then).prev(), Some(&Vertices::Linear(entry)));
+
+        // Retargeting a terminator moves the edges with it.
+        cfg.set_term(entry, Terminator::Jump(then));
+        assert_eq!(cfg.block(entry).next(), Some(&Vertices::Linear(then)));
+        assert!(cfg.block(_else).prev().is_none());
+        assert_eq!(cfg.preds(join), vec![then, _else]);
+        assert_eq!(cfg.succs(entry), vec![then]);
+        assert_eq!(cfg.block(BlockId(0)).id(), entry);
+    }
+
+    #[test]
+    fn temporaries_skip_names_in_use() {
+        let mut cfg = Cfg::new("f", TypeInstance::Int);
+        cfg.add_param("t1", TypeInstance::Int);
+        let names: Vec<_> = (0..3)
+            .map(|_| {
+                let id = cfg.new_temp(TypeInstance::Int);
+                cfg.value(id).name.clone()
+            })
+            .collect();
+        assert_eq!(names, ["t0", "t2", "t3"]);
+    }
+
+    #[test]
+    fn dot_labels_branches() {
+        let mut cfg = Cfg::new("f", TypeInstance::Int);
+        let c = cfg.add_param("c", TypeInstance::Int);
+        let [entry, then, _else] = [cfg.new_block(), cfg.new_block(), cfg.new_block()];
+        cfg.block_mut(entry).add(Instruction::Mov(Move {
+            dst: c,
+            src: Operand::Value(c),
+        }));
+        cfg.set_term(entry, Terminator::Branch(Operand::Value(c), then, _else));
+        cfg.set_term(then, Terminator::Jump(_else));
+        cfg.set_term(_else, Terminator::Ret(Some(Operand::Value(c))));
+
+        let dot = cfg.to_dot();
+        assert!(dot.starts_with("digraph \"f\" {\n"));
+        assert!(dot.contains("    bb0 [label=\"{bb0|%0 \\<- %0\\l|branch %0, bb1, bb2\\l}\"];\n"));
+        assert!(dot.contains("    bb0 -> bb1 [label=\"true\"];\n"));
+        assert!(dot.contains("    bb0 -> bb2 [label=\"false\"];\n"));
+        assert!(dot.contains("    bb1 -> bb2;\n"));
+        assert!(dot.ends_with("}\n"));
+    }
 }
@@ -0,0 +1,442 @@
+use std::fmt::Display;
+
+use miette::Diagnostic;
+
+use super::{
+    ast::{SignKind, Signed, Unsigned},
+    span::{FileId, Span},
+};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Keyword {
+    Int,
+    Char,
+    Short,
+    Long,
+    Void,
+    Signed,
+    Unsigned,
+    Return,
+    Sizeof,
+}
+
+impl Keyword {
+    fn from_ident(ident: &str) -> Option<Self> {
+        match ident {
+            "int" => Some(Keyword::Int),
+            "char" => Some(Keyword::Char),
+            "short" => Some(Keyword::Short),
+            "long" => Some(Keyword::Long),
+            "void" => Some(Keyword::Void),
+            "signed" => Some(Keyword::Signed),
+            "unsigned" => Some(Keyword::Unsigned),
+            "return" => Some(Keyword::Return),
+            "sizeof" => Some(Keyword::Sizeof),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Keyword {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Keyword::Int => write!(f, "int"),
+            Keyword::Char => write!(f, "char"),
+            Keyword::Short => write!(f, "short"),
+            Keyword::Long => write!(f, "long"),
+            Keyword::Void => write!(f, "void"),
+            Keyword::Signed => write!(f, "signed"),
+            Keyword::Unsigned => write!(f, "unsigned"),
+            Keyword::Return => write!(f, "return"),
+            Keyword::Sizeof => write!(f, "sizeof"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TokenKind<'a> {
+    Keyword(Keyword),
+    Ident(&'a str),
+    /// Integer literal, already typed according to its suffix and magnitude.
+    Integer(SignKind),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Assign,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Semi,
+    Comma,
+    Eof,
+}
+
+impl<'a> Display for TokenKind<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenKind::Keyword(kw) => write!(f, "`{kw}`"),
+            TokenKind::Ident(name) => write!(f, "identifier `{name}`"),
+            TokenKind::Integer(i) => write!(f, "integer `{i}`"),
+            TokenKind::Plus => write!(f, "`+`"),
+            TokenKind::Minus => write!(f, "`-`"),
+            TokenKind::Star => write!(f, "`*`"),
+            TokenKind::Slash => write!(f, "`/`"),
+            TokenKind::Assign => write!(f, "`=`"),
+            TokenKind::LParen => write!(f, "`(`"),
+            TokenKind::RParen => write!(f, "`)`"),
+            TokenKind::LBrace => write!(f, "`{{`"),
+            TokenKind::RBrace => write!(f, "`}}`"),
+            TokenKind::Semi => write!(f, "`;`"),
+            TokenKind::Comma => write!(f, "`,`"),
+            TokenKind::Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Token<'a> {
+    pub kind: TokenKind<'a>,
+    pub span: Span,
+}
+
+impl<'a> Display for Token<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} @ {}", self.kind, self.span)
+    }
+}
+
+#[derive(Debug, Diagnostic)]
+pub enum LexError {
+    #[diagnostic(code(xlang::lex::unexpected_char))]
+    UnexpectedChar(char, #[label("not valid here")] Span),
+    #[diagnostic(code(xlang::lex::unterminated_comment))]
+    UnterminatedComment(#[label("comment starts here")] Span),
+    #[diagnostic(
+        code(xlang::lex::invalid_literal),
+        help("integer literals take `u`, `l`, `ll` or a combination such as `ull`")
+    )]
+    InvalidSuffix(#[label("in this literal")] Span),
+    #[diagnostic(code(xlang::lex::literal_too_large))]
+    IntegerTooLarge(#[label("does not fit in `unsigned long long`")] Span),
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LexError::UnexpectedChar(c, _) => write!(f, "unexpected character {c:?}"),
+            LexError::UnterminatedComment(_) => write!(f, "unterminated block comment"),
+            LexError::InvalidSuffix(_) => write!(f, "invalid integer literal"),
+            LexError::IntegerTooLarge(_) => write!(f, "integer literal is too large"),
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
+
+pub struct Lexer<'a> {
+    src: &'a str,
+    file: FileId,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(src: &'a str, file: FileId) -> Self {
+        Self { src, file, pos: 0 }
+    }
+
+    /// Lexes the whole input. The returned stream always ends with `TokenKind::Eof`.
+    pub fn tokenize(mut self) -> Result<Vec<Token<'a>>, LexError> {
+        let mut tokens = Vec::new();
+        loop {
+            let token = self.next_token()?;
+            tokens.push(token);
+            if token.kind == TokenKind::Eof {
+                return Ok(tokens);
+            }
+        }
+    }
+
+    pub fn next_token(&mut self) -> Result<Token<'a>, LexError> {
+        self.skip_trivia()?;
+        let start = self.pos;
+        let c = match self.peek() {
+            Some(c) => c,
+            None => {
+                return Ok(Token {
+                    kind: TokenKind::Eof,
+                    span: self.span(start),
+                })
+            }
+        };
+
+        if c.is_ascii_digit() {
+            return self.integer();
+        }
+        if c.is_ascii_alphabetic() || c == '_' {
+            return Ok(self.word());
+        }
+
+        self.bump();
+        let kind = match c {
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '=' => TokenKind::Assign,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '{' => TokenKind::LBrace,
+            '}' => TokenKind::RBrace,
+            ';' => TokenKind::Semi,
+            ',' => TokenKind::Comma,
+            c => return Err(LexError::UnexpectedChar(c, self.span(start))),
+        };
+        Ok(Token {
+            kind,
+            span: self.span(start),
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.src[self.pos..].chars().nth(1)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn span(&self, start: usize) -> Span {
+        Span::new(self.file, start, self.pos)
+    }
+
+    fn eat_while(&mut self, pred: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&pred) {
+            self.bump();
+        }
+        &self.src[start..self.pos]
+    }
+
+    /// Skips whitespace, `// line` and `/* block */` comments.
+    fn skip_trivia(&mut self) -> Result<(), LexError> {
+        loop {
+            match (self.peek(), self.peek_second()) {
+                (Some(c), _) if c.is_whitespace() => {
+                    self.bump();
+                }
+                (Some('/'), Some('/')) => {
+                    self.eat_while(|c| c != '\n');
+                }
+                (Some('/'), Some('*')) => {
+                    let start = self.pos;
+                    self.pos += 2;
+                    match self.src[self.pos..].find("*/") {
+                        Some(end) => self.pos += end + 2,
+                        None => {
+                            let span = Span::new(self.file, start, start + 2);
+                            self.pos = self.src.len();
+                            return Err(LexError::UnterminatedComment(span));
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn word(&mut self) -> Token<'a> {
+        let start = self.pos;
+        let word = self.eat_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        let kind = match Keyword::from_ident(word) {
+            Some(kw) => TokenKind::Keyword(kw),
+            None => TokenKind::Ident(word),
+        };
+        Token {
+            kind,
+            span: self.span(start),
+        }
+    }
+
+    fn integer(&mut self) -> Result<Token<'a>, LexError> {
+        let start = self.pos;
+        let (radix, digits) = match (self.peek(), self.peek_second()) {
+            (Some('0'), Some('x' | 'X')) => {
+                self.pos += 2;
+                (16, self.eat_while(|c| c.is_ascii_hexdigit()))
+            }
+            (Some('0'), _) => (8, self.eat_while(|c| c.is_ascii_digit())),
+            _ => (10, self.eat_while(|c| c.is_ascii_digit())),
+        };
+        let suffix = self.eat_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        let span = self.span(start);
+
+        if digits.is_empty() {
+            return Err(LexError::InvalidSuffix(span));
+        }
+        let suffix = Suffix::parse(suffix).ok_or(LexError::InvalidSuffix(span))?;
+        let value = match u64::from_str_radix(digits, radix) {
+            Ok(value) => value,
+            Err(_) if radix == 8 && digits.bytes().any(|b| b > b'7') => {
+                return Err(LexError::InvalidSuffix(span))
+            }
+            Err(_) => return Err(LexError::IntegerTooLarge(span)),
+        };
+        let int = suffix
+            .type_literal(value, radix == 10)
+            .ok_or(LexError::IntegerTooLarge(span))?;
+        Ok(Token {
+            kind: TokenKind::Integer(int),
+            span,
+        })
+    }
+}
+
+/// The `u`/`l`/`ll` suffix of an integer literal.
+struct Suffix {
+    unsigned: bool,
+    longs: u8,
+}
+
+impl Suffix {
+    fn parse(suffix: &str) -> Option<Self> {
+        let lower = suffix.to_ascii_lowercase();
+        let (unsigned, rest) = match lower.strip_prefix('u') {
+            Some(rest) => (true, rest),
+            None => match lower.strip_suffix('u') {
+                Some(rest) => (true, rest),
+                None => (false, lower.as_str()),
+            },
+        };
+        // `lL` and `Ll` are not valid spellings of `long long`.
+        let longs = match rest {
+            "" => 0,
+            "l" => 1,
+            "ll" if !suffix.contains("lL") && !suffix.contains("Ll") => 2,
+            _ => return None,
+        };
+        Some(Self { unsigned, longs })
+    }
+
+    /// Picks the first type in which `value` fits, following the C rules for
+    /// integer constants: decimal literals without `u` never become unsigned.
+    fn type_literal(&self, value: u64, decimal: bool) -> Option<SignKind> {
+        let signed_ok = !self.unsigned;
+        let unsigned_ok = self.unsigned || !decimal;
+
+        if self.longs == 0 {
+            if signed_ok && value <= i32::MAX as u64 {
+                return Some(SignKind::Signed(Signed::Int(value as i32)));
+            }
+            if unsigned_ok && value <= u32::MAX as u64 {
+                return Some(SignKind::Unsigned(Unsigned::Int(value as u32)));
+            }
+        }
+        if self.longs <= 1 {
+            if signed_ok && value <= i64::MAX as u64 {
+                return Some(SignKind::Signed(Signed::Long(value as i64)));
+            }
+            if unsigned_ok {
+                return Some(SignKind::Unsigned(Unsigned::Long(value)));
+            }
+        }
+        if signed_ok && value <= i64::MAX as u64 {
+            return Some(SignKind::Signed(Signed::LongLong(value as i64)));
+        }
+        if unsigned_ok {
+            return Some(SignKind::Unsigned(Unsigned::LongLong(value)));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::{
+        ast::{SignKind, Signed, Unsigned},
+        span::Span,
+    };
+
+    use super::{Keyword, LexError, Lexer, TokenKind};
+
+    fn kinds(src: &str) -> Vec<TokenKind<'_>> {
+        match Lexer::new(src, 0).tokenize() {
+            Ok(tokens) => tokens.into_iter().map(|t| t.kind).collect(),
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn lex_main() {
+        let src = include_str!("../../examples/main.c");
+        let tokens = Lexer::new(src, 0).tokenize().unwrap();
+        let kinds: Vec<_> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Keyword(Keyword::Int),
+                TokenKind::Ident("main"),
+                TokenKind::LParen,
+                TokenKind::RParen,
+                TokenKind::LBrace,
+                TokenKind::Keyword(Keyword::Return),
+                TokenKind::Integer(SignKind::Signed(Signed::Int(0))),
+                TokenKind::Semi,
+                TokenKind::RBrace,
+                TokenKind::Eof,
+            ]
+        );
+        assert_eq!(tokens[1].span, Span::new(0, 4, 8));
+        assert_eq!(&src[tokens[5].span.start..tokens[5].span.end], "return");
+    }
+
+    #[test]
+    fn lex_sizeof() {
+        assert_eq!(
+            kinds("sizeof sizeofx"),
+            vec![
+                TokenKind::Keyword(Keyword::Sizeof),
+                TokenKind::Ident("sizeofx"),
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_literals_and_comments() {
+        let kinds = kinds("a /* b */ = 5u + 10l * 3000000000 // tail\n- 0x10ULL;");
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Ident("a"),
+                TokenKind::Assign,
+                TokenKind::Integer(SignKind::Unsigned(Unsigned::Int(5))),
+                TokenKind::Plus,
+                TokenKind::Integer(SignKind::Signed(Signed::Long(10))),
+                TokenKind::Star,
+                TokenKind::Integer(SignKind::Signed(Signed::Long(3000000000))),
+                TokenKind::Minus,
+                TokenKind::Integer(SignKind::Unsigned(Unsigned::LongLong(16))),
+                TokenKind::Semi,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_errors() {
+        let err = Lexer::new("int a = 5lul;", 0).tokenize().unwrap_err();
+        assert!(matches!(err, LexError::InvalidSuffix(span) if span == Span::new(0, 8, 12)));
+        let err = Lexer::new("a @ b", 1).tokenize().unwrap_err();
+        assert!(matches!(err, LexError::UnexpectedChar('@', span) if span == Span::new(1, 2, 3)));
+        let err = Lexer::new("/* open", 0).tokenize().unwrap_err();
+        assert!(matches!(err, LexError::UnterminatedComment(_)));
+    }
+}
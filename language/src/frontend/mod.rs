@@ -1,3 +1,7 @@
 pub mod ast;
 pub mod cfg;
+pub mod lexer;
+pub mod parser;
+pub mod span;
 pub mod symboltable;
+pub mod typeck;
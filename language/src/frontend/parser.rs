@@ -0,0 +1,621 @@
+use std::fmt::Display;
+
+use miette::Diagnostic;
+
+use crate::types::designators::TypeInstance;
+
+use super::{
+    ast::{
+        ASTKind, AssignExpr, BinExpr, BinOp, Block, CallExpr, Expr, Func, FuncDef, Id, Param,
+        Return, SizeOfExpr, Source, UnaryExpr, Value, Variable, AST,
+    },
+    lexer::{Keyword, LexError, Lexer, Token, TokenKind},
+    span::{FileId, Span},
+};
+
+#[derive(Debug, Diagnostic)]
+pub enum ParseError {
+    #[diagnostic(transparent)]
+    Lex(LexError),
+    #[diagnostic(code(xlang::parse::unexpected_token))]
+    Unexpected {
+        expected: &'static str,
+        found: String,
+        #[label("expected {expected}")]
+        span: Span,
+    },
+    #[diagnostic(
+        code(xlang::parse::not_assignable),
+        help("only variables and parameters can be assigned to")
+    )]
+    NotAssignable {
+        #[label("cannot be assigned to")]
+        span: Span,
+    },
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Lex(e) => write!(f, "{e}"),
+            ParseError::Unexpected {
+                expected, found, ..
+            } => write!(f, "expected {expected}, found {found}"),
+            ParseError::NotAssignable { .. } => write!(f, "expression is not assignable"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<LexError> for ParseError {
+    fn from(e: LexError) -> Self {
+        ParseError::Lex(e)
+    }
+}
+
+/// Lexes and parses a whole translation unit.
+pub fn parse(src: &str, file: FileId) -> Result<Source<'_>, ParseError> {
+    let tokens = Lexer::new(src, file).tokenize()?;
+    Parser::new(tokens).parse_source()
+}
+
+pub struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// `tokens` must end with `TokenKind::Eof`, as produced by `Lexer::tokenize`.
+    pub fn new(tokens: Vec<Token<'a>>) -> Self {
+        Self { tokens, pos: 0 }
+    }
+
+    pub fn parse_source(&mut self) -> Result<Source<'a>, ParseError> {
+        let mut items = Vec::new();
+        while self.peek().kind != TokenKind::Eof {
+            items.push(self.item()?);
+        }
+        Ok(Source(items))
+    }
+
+    fn peek(&self) -> Token<'a> {
+        self.tokens[self.pos]
+    }
+
+    /// Span of the most recently consumed token.
+    fn last_span(&self) -> Span {
+        self.tokens[self.pos.saturating_sub(1)].span
+    }
+
+    fn bump(&mut self) -> Token<'a> {
+        let token = self.peek();
+        if token.kind != TokenKind::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, kind: TokenKind) -> bool {
+        if self.peek().kind == kind {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind, expected: &'static str) -> Result<Token<'a>, ParseError> {
+        if self.peek().kind == kind {
+            Ok(self.bump())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    fn unexpected(&self, expected: &'static str) -> ParseError {
+        let found = self.peek();
+        ParseError::Unexpected {
+            expected,
+            found: found.kind.to_string(),
+            span: found.span,
+        }
+    }
+
+    fn ident(&mut self) -> Result<(&'a str, Span), ParseError> {
+        let token = self.peek();
+        match token.kind {
+            TokenKind::Ident(name) => {
+                self.bump();
+                Ok((name, token.span))
+            }
+            _ => Err(self.unexpected("identifier")),
+        }
+    }
+
+    fn at_type(&self) -> bool {
+        matches!(
+            self.peek().kind,
+            TokenKind::Keyword(
+                Keyword::Int
+                    | Keyword::Char
+                    | Keyword::Short
+                    | Keyword::Long
+                    | Keyword::Void
+                    | Keyword::Signed
+                    | Keyword::Unsigned
+            )
+        )
+    }
+
+    /// type := 'void' | ['signed' | 'unsigned'] int | 'signed' | 'unsigned'
+    /// int  := 'char' | 'int' | 'short' ['int'] | 'long' ['long'] ['int']
+    fn type_spec(&mut self) -> Result<TypeInstance, ParseError> {
+        let unsigned = if self.eat(TokenKind::Keyword(Keyword::Signed)) {
+            Some(false)
+        } else if self.eat(TokenKind::Keyword(Keyword::Unsigned)) {
+            Some(true)
+        } else {
+            None
+        };
+        let _type = match self.peek().kind {
+            TokenKind::Keyword(Keyword::Void) if unsigned.is_none() => {
+                self.bump();
+                TypeInstance::Void
+            }
+            TokenKind::Keyword(Keyword::Char) => {
+                self.bump();
+                TypeInstance::Char
+            }
+            TokenKind::Keyword(Keyword::Int) => {
+                self.bump();
+                TypeInstance::Int
+            }
+            TokenKind::Keyword(Keyword::Short) => {
+                self.bump();
+                self.eat(TokenKind::Keyword(Keyword::Int));
+                TypeInstance::Short
+            }
+            TokenKind::Keyword(Keyword::Long) => {
+                self.bump();
+                let _type = if self.eat(TokenKind::Keyword(Keyword::Long)) {
+                    TypeInstance::LongLong
+                } else {
+                    TypeInstance::Long
+                };
+                self.eat(TokenKind::Keyword(Keyword::Int));
+                _type
+            }
+            // A lone `signed` or `unsigned` means `int`.
+            _ if unsigned.is_some() => TypeInstance::Int,
+            _ => return Err(self.unexpected("type")),
+        };
+        Ok(match unsigned {
+            Some(true) => _type.to_unsigned(),
+            Some(false) if _type == TypeInstance::Char => TypeInstance::SChar,
+            _ => _type,
+        })
+    }
+
+    /// item := type ident '(' params ')' (';' | block)
+    ///       | type ident '=' expr ';'
+    fn item(&mut self) -> Result<AST<'a>, ParseError> {
+        let start = self.peek().span;
+        let _type = self.type_spec()?;
+        let (name, name_span) = self.ident()?;
+        if self.peek().kind == TokenKind::Assign {
+            return self.var_rest(start, _type, name, name_span);
+        }
+
+        self.expect(TokenKind::LParen, "`(` or `=`")?;
+        let params = self.params()?;
+        let fdef = FuncDef(_type, name, params, name_span);
+        if self.eat(TokenKind::Semi) {
+            return Ok(AST::new(ASTKind::FuncDef(fdef), start.to(self.last_span())));
+        }
+
+        self.expect(TokenKind::LBrace, "`;` or `{`")?;
+        let mut body = Vec::new();
+        while !self.eat(TokenKind::RBrace) {
+            body.push(self.stmt()?);
+        }
+        Ok(AST::new(
+            ASTKind::Func(Func(fdef, body)),
+            start.to(self.last_span()),
+        ))
+    }
+
+    /// params := ')' | 'void' ')' | type ident (',' type ident)* ')'
+    fn params(&mut self) -> Result<Vec<Param<'a>>, ParseError> {
+        let mut params = Vec::new();
+        if self.eat(TokenKind::RParen) {
+            return Ok(params);
+        }
+        if self.peek().kind == TokenKind::Keyword(Keyword::Void)
+            && self.tokens[self.pos + 1].kind == TokenKind::RParen
+        {
+            self.pos += 2;
+            return Ok(params);
+        }
+        loop {
+            let _type = self.type_spec()?;
+            let (name, span) = self.ident()?;
+            params.push(Param(_type, name, span));
+            if self.eat(TokenKind::RParen) {
+                return Ok(params);
+            }
+            self.expect(TokenKind::Comma, "`,` or `)`")?;
+        }
+    }
+
+    /// stmt := 'return' [assign] ';' | type ident '=' assign ';'
+    ///       | '{' stmt* '}' | assign ';'
+    fn stmt(&mut self) -> Result<AST<'a>, ParseError> {
+        let start = self.peek().span;
+        if self.eat(TokenKind::LBrace) {
+            let mut body = Vec::new();
+            while !self.eat(TokenKind::RBrace) {
+                body.push(self.stmt()?);
+            }
+            return Ok(AST::new(
+                ASTKind::Block(Block(body)),
+                start.to(self.last_span()),
+            ));
+        }
+        if self.eat(TokenKind::Keyword(Keyword::Return)) {
+            let value = match self.peek().kind {
+                TokenKind::Semi => None,
+                _ => Some(Box::new(self.assign()?)),
+            };
+            let end = self.expect(TokenKind::Semi, "`;`")?.span;
+            return Ok(AST::new(ASTKind::Return(Return(value)), start.to(end)));
+        }
+        if self.at_type() {
+            let _type = self.type_spec()?;
+            let (name, name_span) = self.ident()?;
+            return self.var_rest(start, _type, name, name_span);
+        }
+        let expr = self.assign()?;
+        let end = self.expect(TokenKind::Semi, "`;`")?.span;
+        Ok(AST::new(ASTKind::Expr(into_expr(expr)), start.to(end)))
+    }
+
+    fn var_rest(
+        &mut self,
+        start: Span,
+        _type: TypeInstance,
+        name: &'a str,
+        name_span: Span,
+    ) -> Result<AST<'a>, ParseError> {
+        self.expect(TokenKind::Assign, "`=`")?;
+        let init = self.assign()?;
+        let end = self.expect(TokenKind::Semi, "`;`")?.span;
+        Ok(AST::new(
+            ASTKind::VarDec(Variable(_type, name, into_expr(init), name_span)),
+            start.to(end),
+        ))
+    }
+
+    /// assign := expr ['=' assign], where the left side is an identifier
+    fn assign(&mut self) -> Result<AST<'a>, ParseError> {
+        let lhs = self.expr()?;
+        if !self.eat(TokenKind::Assign) {
+            return Ok(lhs);
+        }
+        let ASTKind::Expr(Expr::Unary(UnaryExpr::Id(target))) = lhs.kind else {
+            return Err(ParseError::NotAssignable { span: lhs.span });
+        };
+        let value = self.assign()?;
+        let span = lhs.span.to(value.span);
+        Ok(AST::new(
+            ASTKind::Expr(Expr::Assign(AssignExpr {
+                target,
+                value: Box::new(value),
+            })),
+            span,
+        ))
+    }
+
+    /// expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<AST<'a>, ParseError> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Plus => BinOp::Add,
+                TokenKind::Minus => BinOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.bump();
+            let rhs = self.term()?;
+            lhs = binary(lhs, op, rhs);
+        }
+    }
+
+    /// term := factor (('*' | '/') factor)*
+    fn term(&mut self) -> Result<AST<'a>, ParseError> {
+        let mut lhs = self.factor()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Star => BinOp::Mul,
+                TokenKind::Slash => BinOp::Div,
+                _ => return Ok(lhs),
+            };
+            self.bump();
+            let rhs = self.factor()?;
+            lhs = binary(lhs, op, rhs);
+        }
+    }
+
+    /// factor := integer | ident | ident '(' args ')' | '(' assign ')'
+    ///         | 'sizeof' '(' type ')'
+    fn factor(&mut self) -> Result<AST<'a>, ParseError> {
+        let token = self.peek();
+        match token.kind {
+            TokenKind::Integer(i) => {
+                self.bump();
+                Ok(AST::new(ASTKind::Val(Value::Integer(i)), token.span))
+            }
+            TokenKind::Ident(name) => {
+                self.bump();
+                if self.eat(TokenKind::LParen) {
+                    let args = self.args()?;
+                    let span = token.span.to(self.last_span());
+                    let call = CallExpr {
+                        func: Id(name, token.span),
+                        args,
+                        span,
+                    };
+                    return Ok(AST::new(ASTKind::Expr(Expr::Call(call)), span));
+                }
+                Ok(AST::new(
+                    ASTKind::Expr(Expr::Unary(UnaryExpr::Id(Id(name, token.span)))),
+                    token.span,
+                ))
+            }
+            TokenKind::Keyword(Keyword::Sizeof) => {
+                self.bump();
+                self.expect(TokenKind::LParen, "`(`")?;
+                let _type = self.type_spec()?;
+                let span = token.span.to(self.expect(TokenKind::RParen, "`)`")?.span);
+                let size = SizeOfExpr { _type, span };
+                Ok(AST::new(ASTKind::Expr(Expr::SizeOf(size)), span))
+            }
+            TokenKind::LParen => {
+                self.bump();
+                let mut expr = self.assign()?;
+                let end = self.expect(TokenKind::RParen, "`)`")?.span;
+                expr.span = token.span.to(end);
+                Ok(expr)
+            }
+            _ => Err(self.unexpected("expression")),
+        }
+    }
+
+    /// args := ')' | assign (',' assign)* ')'
+    fn args(&mut self) -> Result<Vec<AST<'a>>, ParseError> {
+        let mut args = Vec::new();
+        if self.eat(TokenKind::RParen) {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.assign()?);
+            if self.eat(TokenKind::RParen) {
+                return Ok(args);
+            }
+            self.expect(TokenKind::Comma, "`,` or `)`")?;
+        }
+    }
+}
+
+fn binary<'a>(lhs: AST<'a>, op: BinOp, rhs: AST<'a>) -> AST<'a> {
+    let span = lhs.span.to(rhs.span);
+    AST::new(
+        ASTKind::Expr(Expr::Binary(BinExpr {
+            lhs: Box::new(lhs),
+            op,
+            rhs: Box::new(rhs),
+        })),
+        span,
+    )
+}
+
+/// Expression parsing only ever yields `Val` or `Expr` nodes.
+fn into_expr(ast: AST) -> Expr {
+    match ast.kind {
+        ASTKind::Val(val) => Expr::Noop(val),
+        ASTKind::Expr(expr) => expr,
+        _ => unreachable!("expression parser produced a non-expression node"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        frontend::{
+            ast::{ASTKind, Expr, AST},
+            span::Span,
+        },
+        types::designators::TypeInstance,
+    };
+
+    use super::{parse, ParseError};
+
+    /// Renders an expression with every binary node parenthesised.
+    fn grouped(ast: &AST) -> String {
+        match &ast.kind {
+            ASTKind::Expr(Expr::Binary(bin)) => {
+                format!("({} {} {})", grouped(&bin.lhs), bin.op, grouped(&bin.rhs))
+            }
+            kind => kind.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_main() {
+        let source = parse(include_str!("../../examples/main.c"), 0).unwrap();
+        assert_eq!(source.0.len(), 1);
+        match &source.0[0].kind {
+            ASTKind::Func(func) => {
+                assert_eq!(func.0 .1, "main");
+                assert_eq!(func.1.len(), 1);
+                assert_eq!(func.1[0].kind.to_string(), "return 0;");
+            }
+            _ => panic!("expected a function"),
+        }
+    }
+
+    #[test]
+    fn parse_precedence() {
+        let source = parse(
+            "int f(int a, long b) { return a - 2 - 3 * (4 + b) / 6; }",
+            0,
+        )
+        .unwrap();
+        let ASTKind::Func(func) = &source.0[0].kind else {
+            panic!("expected a function");
+        };
+        assert_eq!(func.0.to_string(), "int f(int a, long b)");
+        let ASTKind::Return(ret) = &func.1[0].kind else {
+            panic!("expected a return");
+        };
+        let value = ret.0.as_ref().unwrap();
+        assert_eq!(grouped(value), "((a - 2) - ((3 * (4 + b)) / 6))");
+        assert_eq!(value.span, Span::new(0, 30, 53));
+        assert_eq!(func.0 .2[1].2, Span::new(0, 18, 19));
+    }
+
+    #[test]
+    fn parse_declarations() {
+        let source = parse("long long g = 1; int f(void); short int h() { f; }", 0).unwrap();
+        assert_eq!(source.0.len(), 3);
+        assert!(matches!(source.0[0].kind, ASTKind::VarDec(_)));
+        assert!(matches!(source.0[1].kind, ASTKind::FuncDef(_)));
+        assert!(matches!(source.0[2].kind, ASTKind::Func(_)));
+
+        let source = parse(
+            "unsigned a = 1; signed char b = 1; char c = 1; unsigned long long d = 1; \
+             signed e = 1; unsigned short int f = 1;",
+            0,
+        )
+        .unwrap();
+        let types: Vec<TypeInstance> = source
+            .0
+            .iter()
+            .map(|ast| match &ast.kind {
+                ASTKind::VarDec(var) => var.0.clone(),
+                _ => panic!("expected a declaration"),
+            })
+            .collect();
+        assert_eq!(
+            types,
+            [
+                TypeInstance::UInt,
+                TypeInstance::SChar,
+                TypeInstance::Char,
+                TypeInstance::ULongLong,
+                TypeInstance::Int,
+                TypeInstance::UShort,
+            ]
+        );
+        assert!(parse("unsigned void f();", 0).is_err());
+    }
+
+    #[test]
+    fn parse_calls_and_assignments() {
+        let source = parse(
+            "void f(int a) { a = b = g(1, a + 2) * h(); (a = 1); return; }",
+            0,
+        )
+        .unwrap();
+        let ASTKind::Func(func) = &source.0[0].kind else {
+            panic!("expected a function");
+        };
+        let ASTKind::Expr(Expr::Assign(assign)) = &func.1[0].kind else {
+            panic!("expected an assignment");
+        };
+        assert_eq!(assign.target.0, "a");
+        assert_eq!(assign.value.kind.to_string(), "b = g(1, a + 2) * h()");
+        let ASTKind::Expr(Expr::Assign(inner)) = &assign.value.kind else {
+            panic!("expected a nested assignment");
+        };
+        let ASTKind::Expr(Expr::Binary(bin)) = &inner.value.kind else {
+            panic!("expected a product");
+        };
+        let ASTKind::Expr(Expr::Call(call)) = &bin.lhs.kind else {
+            panic!("expected a call");
+        };
+        assert_eq!(call.func.0, "g");
+        assert_eq!(call.args.len(), 2);
+        assert_eq!(call.span, Span::new(0, 24, 35));
+        assert_eq!(func.1[1].kind.to_string(), "a = 1");
+        assert_eq!(func.1[2].kind.to_string(), "return;");
+
+        assert!(matches!(
+            parse("int f() { f(1,); }", 0),
+            Err(ParseError::Unexpected {
+                expected: "expression",
+                ..
+            })
+        ));
+        let err = parse("int f(int a) { a + 1 = 2; }", 0).unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::NotAssignable { span } if span == Span::new(0, 15, 20)
+        ));
+    }
+
+    #[test]
+    fn parse_sizeof() {
+        let source = parse("int f() { return sizeof(unsigned short) * 2; }", 0).unwrap();
+        let ASTKind::Func(func) = &source.0[0].kind else {
+            panic!("expected a function");
+        };
+        let ASTKind::Return(ret) = &func.1[0].kind else {
+            panic!("expected a return");
+        };
+        let value = ret.0.as_ref().unwrap();
+        assert_eq!(value.kind.to_string(), "sizeof(unsigned short) * 2");
+        let ASTKind::Expr(Expr::Binary(bin)) = &value.kind else {
+            panic!("expected a product");
+        };
+        let ASTKind::Expr(Expr::SizeOf(size)) = &bin.lhs.kind else {
+            panic!("expected a sizeof");
+        };
+        assert_eq!(size._type, TypeInstance::UShort);
+        assert_eq!(size.span, Span::new(0, 17, 39));
+
+        // Only types are accepted, not expressions.
+        assert!(matches!(
+            parse("int f(int a) { return sizeof(a); }", 0),
+            Err(ParseError::Unexpected {
+                expected: "type",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = parse("int main() { return 1 + ; }", 0).unwrap_err();
+        match err {
+            ParseError::Unexpected {
+                expected,
+                found,
+                span,
+            } => {
+                assert_eq!(expected, "expression");
+                assert_eq!(found, "`;`");
+                assert_eq!(span, Span::new(0, 24, 25));
+            }
+            _ => panic!("expected an unexpected token"),
+        }
+        assert!(matches!(
+            parse("int main() { return 1 }", 0),
+            Err(ParseError::Unexpected {
+                expected: "`;`",
+                ..
+            })
+        ));
+        assert!(matches!(parse("int $", 0), Err(ParseError::Lex(_))));
+    }
+}
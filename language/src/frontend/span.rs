@@ -0,0 +1,135 @@
+use std::fmt::Display;
+
+use miette::{MietteError, MietteSpanContents, SourceCode, SourceSpan, SpanContents};
+
+/// Index of a source file, handed out by whoever loads the sources.
+pub type FileId = usize;
+
+/// A byte range `[start, end)` inside the file `file`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(file: FileId, start: usize, end: usize) -> Self {
+        Self { file, start, end }
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Self {
+        Self {
+            file: self.file,
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}..{}", self.file, self.start, self.end)
+    }
+}
+
+/// Offsets in a `SourceSpan` carry the file id above this bit, so that a
+/// `SourceMap` can tell which file a label points into. Spans of file `0`
+/// keep their offset and render against the bare text of that file.
+const FILE_SHIFT: u32 = usize::BITS * 5 / 8;
+
+impl From<Span> for SourceSpan {
+    fn from(span: Span) -> Self {
+        SourceSpan::new(((span.file << FILE_SHIFT) | span.start).into(), span.len())
+    }
+}
+
+/// The named texts of every file of a compilation, indexed by `FileId`.
+/// Diagnostics whose labels point into several files render against it.
+#[derive(Clone, Default)]
+pub struct SourceMap {
+    files: Vec<(String, String)>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file and returns the id its spans must carry.
+    pub fn add(&mut self, name: impl Into<String>, text: impl Into<String>) -> FileId {
+        self.files.push((name.into(), text.into()));
+        self.files.len() - 1
+    }
+
+    pub fn name(&self, file: FileId) -> &str {
+        &self.files[file].0
+    }
+
+    pub fn text(&self, file: FileId) -> &str {
+        &self.files[file].1
+    }
+}
+
+impl SourceCode for SourceMap {
+    fn read_span<'a>(
+        &'a self,
+        span: &SourceSpan,
+        context_lines_before: usize,
+        context_lines_after: usize,
+    ) -> Result<Box<dyn SpanContents<'a> + 'a>, MietteError> {
+        let file = span.offset() >> FILE_SHIFT;
+        let base = file << FILE_SHIFT;
+        let (name, text) = self.files.get(file).ok_or(MietteError::OutOfBounds)?;
+        let local = SourceSpan::new((span.offset() - base).into(), span.len());
+        let contents = text.read_span(&local, context_lines_before, context_lines_after)?;
+        let read = contents.span();
+        Ok(Box::new(MietteSpanContents::new_named(
+            name.clone(),
+            contents.data(),
+            SourceSpan::new((read.offset() + base).into(), read.len()),
+            contents.line(),
+            contents.column(),
+            contents.line_count(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use miette::{GraphicalReportHandler, GraphicalTheme, Report};
+
+    use crate::frontend::{parser::parse, symboltable::SymbolMap};
+
+    use super::SourceMap;
+
+    #[test]
+    fn labels_render_in_their_own_file() {
+        let mut files = SourceMap::new();
+        let a = files.add("a.c", "int f(int x);\n");
+        let b = files.add("b.c", "\n\nlong f(int x) { return x; }\n");
+        let mut items = parse(files.text(a), a).unwrap().0;
+        items.extend(parse(files.text(b), b).unwrap().0);
+
+        let mut errors = SymbolMap::new().fill_from_source(&items).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        let report = Report::new(errors.remove(0)).with_source_code(files);
+        let mut out = String::new();
+        GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor())
+            .render_report(&mut out, report.as_ref())
+            .unwrap();
+        assert!(out.contains("[b.c:3:6]"));
+        assert!(out.contains("[a.c:1:5]"));
+        assert!(out.contains("1 │ int f(int x);"));
+        assert!(out.contains("3 │ long f(int x)"));
+    }
+}
@@ -1,9 +1,15 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use miette::Diagnostic;
 
 use crate::types::designators::TypeInstance;
 
-use super::ast::{
-    ASTKind, BinExpr, Expr, Func, FuncDef, Param, Return, UnaryExpr, Value, Variable, AST,
+use super::{
+    ast::{ASTKind, Expr, FuncDef, Id, Param, UnaryExpr, Variable, AST},
+    span::Span,
 };
 
 pub enum ScopeKind {
@@ -24,44 +30,170 @@ impl Display for ScopeKind {
     }
 }
 
+#[derive(Debug, Diagnostic)]
 pub enum SymbolError {
-    AlreadyExists(Symbol),
+    #[diagnostic(
+        code(xlang::sym::redefined),
+        help("a name can only be declared once per scope, rename one of them")
+    )]
+    AlreadyExists {
+        name: String,
+        #[label("`{name}` first declared here")]
+        original: Span,
+        #[label("redeclared here")]
+        duplicate: Span,
+    },
+    #[diagnostic(code(xlang::sym::undeclared), help("declare `{name}` before using it"))]
+    Undeclared {
+        name: String,
+        #[label("not found in this scope")]
+        span: Span,
+    },
+    #[diagnostic(
+        code(xlang::sym::use_before_declaration),
+        help("move the declaration of `{name}` above its first use")
+    )]
+    UsedBeforeDeclaration {
+        name: String,
+        #[label("used here")]
+        used: Span,
+        #[label("but only declared here")]
+        declared: Span,
+    },
+    #[diagnostic(
+        code(xlang::sym::conflicting_signature),
+        help("every declaration of a function must have the same return and parameter types")
+    )]
+    ConflictingSignature {
+        name: String,
+        #[label("`{name}` first declared here")]
+        original: Span,
+        #[label("declared with a different type here")]
+        duplicate: Span,
+    },
+    #[diagnostic(
+        code(xlang::sym::duplicate_param),
+        help("rename one of the parameters")
+    )]
+    DuplicateParam {
+        name: String,
+        #[label("first parameter named `{name}`")]
+        original: Span,
+        #[label("reused here")]
+        duplicate: Span,
+    },
+    #[diagnostic(
+        code(xlang::sym::void_variable),
+        help("`void` has no values, give `{name}` a type such as `int`")
+    )]
+    VoidVariable {
+        name: String,
+        #[label("declared here")]
+        span: Span,
+    },
+}
+
+impl SymbolError {
+    /// Where the error is reported, the location of its primary label.
+    pub fn span(&self) -> Span {
+        match self {
+            SymbolError::AlreadyExists { duplicate, .. }
+            | SymbolError::ConflictingSignature { duplicate, .. }
+            | SymbolError::DuplicateParam { duplicate, .. } => *duplicate,
+            SymbolError::Undeclared { span, .. } | SymbolError::VoidVariable { span, .. } => *span,
+            SymbolError::UsedBeforeDeclaration { used, .. } => *used,
+        }
+    }
+}
+
+impl Display for SymbolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SymbolError::AlreadyExists { name, .. } => write!(f, "redefinition of `{name}`"),
+            SymbolError::Undeclared { name, .. } => {
+                write!(f, "use of undeclared identifier `{name}`")
+            }
+            SymbolError::UsedBeforeDeclaration { name, .. } => {
+                write!(f, "`{name}` is used before its declaration")
+            }
+            SymbolError::ConflictingSignature { name, .. } => {
+                write!(f, "conflicting types for `{name}`")
+            }
+            SymbolError::DuplicateParam { name, .. } => {
+                write!(f, "duplicate parameter `{name}`")
+            }
+            SymbolError::VoidVariable { name, .. } => {
+                write!(f, "`{name}` is declared with type `void`")
+            }
+        }
+    }
 }
 
+impl std::error::Error for SymbolError {}
+
 pub struct Symbol {
+    name: String,
     _type: TypeInstance,
     kind: ScopeKind,
-    val: Option<Value>,
+    span: Span,
 }
 
 impl Display for Symbol {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.val.is_none() {
-            write!(f, "{} | {} | None", self._type, self.kind)
-        } else {
-            write!(f, "{} | {} | {}", self._type, self.kind, self.val.unwrap())
-        }
+        write!(f, "{} | {}", self._type, self.kind)
     }
 }
 
 impl Symbol {
-    fn new(_type: TypeInstance, kind: ScopeKind, val: Option<Value>) -> Self {
-        Self { _type, kind, val }
+    fn new(name: impl Into<String>, _type: TypeInstance, kind: ScopeKind, span: Span) -> Self {
+        Self {
+            name: name.into(),
+            _type,
+            kind,
+            span,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_type(&self) -> TypeInstance {
+        self._type.clone()
+    }
+
+    pub fn kind(&self) -> &ScopeKind {
+        &self.kind
+    }
+
+    /// Where the symbol was declared.
+    pub fn span(&self) -> Span {
+        self.span
     }
 }
 
 pub type TableId = usize;
 
+/// A symbol's table and its position there, stable once the map is filled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SymbolId {
+    pub table: TableId,
+    pub index: usize,
+}
+
 pub struct SymbolTable {
-    table: HashMap<String, Symbol>,
+    symbols: Vec<Symbol>,
+    names: HashMap<String, usize>,
     parent: Option<TableId>,
+    /// Scopes nested directly in this one, in source order.
+    children: Vec<TableId>,
 }
 
 impl Display for SymbolTable {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Name | Type | Scope | Value")?;
-        for (key, value) in self.table.iter() {
-            writeln!(f, "{} | {} ", key, value)?;
+        writeln!(f, "Name | Type | Scope")?;
+        for symbol in self.symbols.iter() {
+            writeln!(f, "{} | {} ", symbol.name, symbol)?;
         }
         write!(f, "")
     }
@@ -70,134 +202,85 @@ impl Display for SymbolTable {
 impl SymbolTable {
     fn new(parent: Option<TableId>) -> Self {
         SymbolTable {
-            table: HashMap::new(),
+            symbols: Vec::new(),
+            names: HashMap::new(),
             parent,
+            children: Vec::new(),
         }
     }
 
     pub fn get(&self, name: &str) -> Option<&Symbol> {
-        self.table.get(name)
-    }
-
-    fn insert_abstract(&mut self, kind: &ASTKind) -> Result<(), SymbolError> {
-        match kind {
-            ASTKind::Expr(expr) => self.insert_expr(expr),
-            ASTKind::Param(param) => self.insert_param(param),
-            ASTKind::Return(ret) => self.insert_return(ret),
-            ASTKind::Val(val) => self.insert_val(val),
-            ASTKind::VarDec(var) => self.insert_var(var),
-            ASTKind::FuncDef(fdef) => self.insert_fdef(fdef),
-            //Adds function def in curr and creates a new child with parent current
-            ASTKind::Func(func) => self.insert_func(func),
-        }
+        self.names.get(name).map(|index| &self.symbols[*index])
     }
 
-    fn insert_val(&mut self, val: &Value) -> Result<(), SymbolError> {
-        self.table.insert(
-            self.gen_tmpname(),
-            Symbol::new(val.get_type(), ScopeKind::Var, Some(*val)),
-        );
-        Ok(())
+    /// Index of the symbol `name` in this table.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
     }
 
-    fn insert_param(&mut self, param: &Param) -> Result<(), SymbolError> {
-        match self.table.insert(
-            param.1.to_string(),
-            Symbol::new(param.0.clone(), ScopeKind::Param, None),
-        ) {
-            None => Ok(()),
-            Some(e) => Err(SymbolError::AlreadyExists(e)),
-        }
+    /// Symbols in declaration order.
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
     }
 
-    fn insert_return(&mut self, ret: &Return) -> Result<(), SymbolError> {
-        self.insert_abstract(&ret.0.kind)
+    pub fn parent(&self) -> Option<TableId> {
+        self.parent
     }
 
-    fn insert_fdef(&mut self, fdef: &FuncDef) -> Result<(), SymbolError> {
-        for param in fdef.2.iter() {
-            match self.insert_param(&param) {
-                Ok(()) => (),
-                Err(e) => return Err(e),
-            };
-        }
-
-        match self.table.insert(
-            fdef.1.to_string(),
-            Symbol::new(fdef.0.clone(), ScopeKind::FSign, None),
-        ) {
-            None => Ok(()),
-            Some(e) => Err(SymbolError::AlreadyExists(e)),
-        }
+    pub fn children(&self) -> &[TableId] {
+        &self.children
     }
 
-    fn insert_func(&mut self, f: &Func) -> Result<(), SymbolError> {
-        for ast in f.1.iter() {
-            match self.insert_abstract(&ast.kind) {
-                Ok(()) => (),
-                Err(e) => return Err(e),
+    /// Inserts `symbol` unless its name is already taken in this table, and
+    /// returns its index.
+    fn declare(&mut self, symbol: Symbol) -> Result<usize, SymbolError> {
+        match self.get(&symbol.name) {
+            Some(original) => Err(SymbolError::AlreadyExists {
+                name: symbol.name,
+                original: original.span,
+                duplicate: symbol.span,
+            }),
+            None => {
+                self.names.insert(symbol.name.clone(), self.symbols.len());
+                self.symbols.push(symbol);
+                Ok(self.symbols.len() - 1)
             }
         }
-        Ok(())
     }
 
-    fn insert_var(&mut self, var: &Variable) -> Result<(), SymbolError> {
-        let _type = var.0.clone();
-        let name = var.1;
-        let expr = &var.2;
-        match self.insert_expr(expr) {
-            Ok(()) => {
-                match self
-                    .table
-                    .insert(name.to_string(), Symbol::new(_type, ScopeKind::Var, None))
-                {
-                    None => Ok(()),
-                    Some(e) => Err(SymbolError::AlreadyExists(e)),
-                }
-            }
-            Err(e) => Err(e),
-        }
+    fn insert_param(&mut self, param: &Param) -> Result<usize, SymbolError> {
+        self.declare(Symbol::new(
+            param.1,
+            param.0.clone(),
+            ScopeKind::Param,
+            param.2,
+        ))
     }
 
-    fn insert_expr(&mut self, expr: &Expr) -> Result<(), SymbolError> {
-        match expr {
-            Expr::Binary(bin) => self.insert_bin_expr(bin),
-            Expr::Noop(val) => self.insert_val(val),
-            Expr::Unary(unary) => self.insert_unary(unary),
-        }
+    fn insert_fdef(&mut self, fdef: &FuncDef) -> Result<usize, SymbolError> {
+        self.declare(Symbol::new(
+            fdef.1,
+            fdef.get_type(),
+            ScopeKind::FSign,
+            fdef.3,
+        ))
     }
 
-    fn insert_bin_expr(&mut self, bin: &BinExpr) -> Result<(), SymbolError> {
-        let lhs = self.insert_abstract(&bin.lhs.kind);
-        let rhs = self.insert_abstract(&bin.rhs.kind);
-        match (lhs, rhs) {
-            (Ok(()), Ok(())) => Ok(()),
-            (Err(e), Ok(())) => Err(e),
-            (Ok(()), Err(e)) => Err(e),
-            (Err(e), Err(_)) => Err(e),
-        }
-    }
-
-    fn insert_unary(&mut self, un: &UnaryExpr) -> Result<(), SymbolError> {
-        match un {
-            UnaryExpr::Id(_) => Ok(()),
-        }
-    }
-
-    fn gen_tmpname(&self) -> String {
-        let mut id = 0;
-        let mut tmp_name = format!("t{}", id);
-        while self.table.contains_key(&tmp_name) {
-            id += 1;
-            tmp_name = format!("t{}", id);
-        }
-        tmp_name
+    fn insert_var(&mut self, var: &Variable) -> Result<usize, SymbolError> {
+        self.declare(Symbol::new(var.1, var.0.clone(), ScopeKind::Var, var.3))
     }
 }
 
 pub struct SymbolMap {
     inner: Vec<SymbolTable>,
-    pos: TableId,
+    /// The symbol every name declares or refers to, keyed by the span of
+    /// the name.
+    resolved: HashMap<Span, SymbolId>,
+    /// Functions that have a body.
+    defined: HashSet<SymbolId>,
+    /// Uses no declaration was visible for yet, with their scope.
+    pending: Vec<(TableId, String, Span)>,
+    errors: Vec<SymbolError>,
 }
 impl Display for SymbolMap {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -208,117 +291,462 @@ impl Display for SymbolMap {
         Ok(())
     }
 }
+impl Default for SymbolMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SymbolMap {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             inner: Vec::new(),
-            pos: 0,
+            resolved: HashMap::new(),
+            defined: HashSet::new(),
+            pending: Vec::new(),
+            errors: Vec::new(),
         }
     }
 
     fn add(&mut self, parent: Option<TableId>) -> TableId {
+        let id = self.inner.len();
         self.inner.push(SymbolTable::new(parent));
-        self.inner.len() - 1
-    }
-
-    fn insert(
-        &mut self,
-        curr: &mut SymbolTable,
-        prev: Option<&mut SymbolTable>,
-        kind: &ASTKind,
-    ) -> Result<(), SymbolError> {
-        match kind {
-            ASTKind::Expr(expr) => curr.insert_expr(expr),
-            ASTKind::Param(param) => curr.insert_param(param),
-            ASTKind::Return(ret) => curr.insert_return(ret),
-            ASTKind::Val(val) => curr.insert_val(val),
-            ASTKind::VarDec(var) => curr.insert_var(var),
-            ASTKind::FuncDef(fdef) => curr.insert_fdef(fdef),
-            //Adds function def in curr and creates a new child with parent current
+        if let Some(parent) = parent {
+            self.inner[parent].children.push(id);
+        }
+        id
+    }
+
+    /// Every table, the file scope first.
+    pub fn tables(&self) -> &[SymbolTable] {
+        &self.inner
+    }
+
+    pub fn table(&self, id: TableId) -> &SymbolTable {
+        &self.inner[id]
+    }
+
+    pub fn symbol(&self, id: SymbolId) -> &Symbol {
+        &self.inner[id.table].symbols[id.index]
+    }
+
+    /// Records that the name at `span` is the symbol `index` of `table`.
+    fn bind(&mut self, span: Span, table: TableId, index: usize) {
+        self.resolved.insert(span, SymbolId { table, index });
+    }
+
+    /// Binds the name at `span` to a newly declared symbol, or reports why
+    /// it could not be declared.
+    fn bind_declared(&mut self, span: Span, table: TableId, declared: Result<usize, SymbolError>) {
+        match declared {
+            Ok(index) => self.bind(span, table, index),
+            Err(e) => self.errors.push(e),
+        }
+    }
+
+    fn insert(&mut self, scope: TableId, ast: &AST) {
+        match &ast.kind {
+            ASTKind::Expr(expr) => self.insert_expr(scope, expr),
+            ASTKind::Param(param) => {
+                let declared = self.inner[scope].insert_param(param);
+                self.bind_declared(param.2, scope, declared);
+            }
+            ASTKind::Return(ret) => {
+                if let Some(value) = &ret.0 {
+                    self.insert(scope, value);
+                }
+            }
+            ASTKind::Val(_) => (),
+            // The scope of a variable starts before its initializer.
+            ASTKind::VarDec(var) => {
+                if var.0 == TypeInstance::Void {
+                    self.errors.push(SymbolError::VoidVariable {
+                        name: var.1.to_string(),
+                        span: var.3,
+                    });
+                }
+                let declared = self.inner[scope].insert_var(var);
+                self.bind_declared(var.3, scope, declared);
+                self.insert_expr(scope, &var.2);
+            }
+            ASTKind::FuncDef(fdef) => self.insert_function(scope, fdef, false),
+            // The signature goes in the enclosing scope, parameters and
+            // locals share a new one.
             ASTKind::Func(f) => {
-                let sign = curr.insert_fdef(&f.0);
-                let new_scope_ptr = self.add(Some(self.pos));
-                let new_scope = self.inner.get_mut(new_scope_ptr).unwrap();
-                new_scope.insert_func(f)
+                self.insert_function(scope, &f.0, true);
+                let body = self.add(Some(scope));
+                for param in f.0 .2.iter() {
+                    // Clashes were reported with the signature.
+                    if let Ok(index) = self.inner[body].insert_param(param) {
+                        self.bind(param.2, body, index);
+                    }
+                }
+                self.insert_all(body, &f.1);
+            }
+            ASTKind::Block(block) => {
+                let inner = self.add(Some(scope));
+                self.insert_all(inner, &block.0);
             }
         }
     }
 
-    fn fill_from_source(&mut self, source: &[AST]) -> Result<(), SymbolError> {
-        let mut root = SymbolTable::new(None);
-        for ast in source.iter() {
-            match self.insert(&mut root, None, &ast.kind) {
-                Ok(()) => (),
-                Err(e) => return Err(e),
-            };
+    /// Declares a function, or checks a redeclaration against the first
+    /// declaration. Only one declaration may come with a body.
+    fn insert_function(&mut self, scope: TableId, fdef: &FuncDef, defining: bool) {
+        for (i, param) in fdef.2.iter().enumerate() {
+            if param.0 == TypeInstance::Void {
+                self.errors.push(SymbolError::VoidVariable {
+                    name: param.1.to_string(),
+                    span: param.2,
+                });
+            }
+            if let Some(original) = fdef.2[..i].iter().find(|p| p.1 == param.1) {
+                self.errors.push(SymbolError::DuplicateParam {
+                    name: param.1.to_string(),
+                    original: original.2,
+                    duplicate: param.2,
+                });
+            }
+        }
+
+        let table = &self.inner[scope];
+        let index = match table.index_of(fdef.1) {
+            Some(index) if matches!(table.symbols[index].kind, ScopeKind::FSign) => {
+                let original = &table.symbols[index];
+                if original._type != fdef.get_type() {
+                    self.errors.push(SymbolError::ConflictingSignature {
+                        name: fdef.1.to_string(),
+                        original: original.span,
+                        duplicate: fdef.3,
+                    });
+                } else if defining
+                    && self.defined.contains(&SymbolId {
+                        table: scope,
+                        index,
+                    })
+                {
+                    self.errors.push(SymbolError::AlreadyExists {
+                        name: fdef.1.to_string(),
+                        original: original.span,
+                        duplicate: fdef.3,
+                    });
+                }
+                index
+            }
+            _ => match self.inner[scope].insert_fdef(fdef) {
+                Ok(index) => index,
+                Err(e) => {
+                    self.errors.push(e);
+                    return;
+                }
+            },
+        };
+        self.bind(fdef.3, scope, index);
+        if defining {
+            self.defined.insert(SymbolId {
+                table: scope,
+                index,
+            });
         }
-        self.inner.insert(0, root);
-        Ok(())
     }
 
-    pub fn get(&self, id: TableId, name: &str) -> Option<&Symbol> {
-        self.inner.get(id).unwrap().get(name)
+    fn insert_all(&mut self, scope: TableId, asts: &[AST]) {
+        for ast in asts.iter() {
+            self.insert(scope, ast);
+        }
     }
 
-    pub fn get_from_val(&self, id: TableId, val: Value) -> Option<&String> {
-        for (key, symbol) in self.inner.get(id).unwrap().table.iter() {
-            if let Some(value) = symbol.val {
-                if val == value {
-                    return Some(key);
-                }
+    fn insert_expr(&mut self, scope: TableId, expr: &Expr) {
+        match expr {
+            Expr::Binary(bin) => {
+                self.insert(scope, &bin.lhs);
+                self.insert(scope, &bin.rhs);
+            }
+            Expr::Noop(_) | Expr::SizeOf(_) => (),
+            Expr::Unary(UnaryExpr::Id(id)) => self.insert_use(scope, id),
+            Expr::Call(call) => {
+                self.insert_use(scope, &call.func);
+                self.insert_all(scope, &call.args);
+            }
+            Expr::Assign(assign) => {
+                self.insert_use(scope, &assign.target);
+                self.insert(scope, &assign.value);
             }
         }
+    }
+
+    fn insert_use(&mut self, scope: TableId, id: &Id) {
+        match self.resolve(scope, id.0) {
+            Some(symbol) => {
+                self.resolved.insert(id.1, symbol);
+            }
+            None => self.pending.push((scope, id.0.to_string(), id.1)),
+        }
+    }
+
+    /// Fills a fresh map from `source`. The file scope is table 0 and every
+    /// function body or block gets a child of its enclosing scope. Returns
+    /// every error found, in source order.
+    pub fn fill_from_source(&mut self, source: &[AST]) -> Result<(), Vec<SymbolError>> {
+        let root = self.add(None);
+        self.insert_all(root, source);
+
+        // A name declared further down than its use is still visible there
+        // once the whole scope is known.
+        for (scope, name, span) in std::mem::take(&mut self.pending) {
+            let error = match self.resolve(scope, &name) {
+                Some(symbol) => SymbolError::UsedBeforeDeclaration {
+                    name,
+                    used: span,
+                    declared: self.symbol(symbol).span,
+                },
+                None => SymbolError::Undeclared { name, span },
+            };
+            self.errors.push(error);
+        }
+
+        let mut errors = std::mem::take(&mut self.errors);
+        if errors.is_empty() {
+            return Ok(());
+        }
+        errors.sort_by_key(|e| e.span().start);
+        Err(errors)
+    }
+
+    /// The symbol `name` stands for in `scope`: its declaration there or in
+    /// the nearest enclosing scope, which it shadows.
+    pub fn resolve(&self, scope: TableId, name: &str) -> Option<SymbolId> {
+        let mut table = Some(scope);
+        while let Some(id) = table {
+            if let Some(index) = self.inner[id].index_of(name) {
+                return Some(SymbolId { table: id, index });
+            }
+            table = self.inner[id].parent;
+        }
         None
     }
+
+    /// The symbol a use of a name refers to.
+    pub fn resolved(&self, id: &Id) -> Option<SymbolId> {
+        self.resolved.get(&id.1).copied()
+    }
+
+    /// The symbol the name at `span` declares or refers to.
+    pub fn symbol_at(&self, span: Span) -> Option<SymbolId> {
+        self.resolved.get(&span).copied()
+    }
+
+    pub fn get(&self, id: TableId, name: &str) -> Option<&Symbol> {
+        self.inner.get(id).unwrap().get(name)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::frontend::symboltable::SymbolMap;
-    use crate::frontend::{ast::Source, symboltable::SymbolTable};
+    use miette::{Diagnostic, GraphicalReportHandler, GraphicalTheme, Report};
 
-    use crate::{
-        frontend::ast::{
-            BinExpr, BinOp, Expr, Func, FuncDef, Return, SignKind, Unsigned, Value, Variable, AST,
-        },
-        types::designators::TypeInstance,
-    };
+    use crate::frontend::parser::parse;
+    use crate::frontend::span::Span;
+    use crate::frontend::symboltable::{ScopeKind, SymbolError, SymbolMap};
+    use crate::types::designators::TypeInstance;
 
-    use super::ASTKind;
     #[test]
     fn simple_table() {
-        let expr = Expr::Binary(BinExpr {
-            lhs: Box::new(AST::new(ASTKind::Val(Value::Integer(SignKind::Unsigned(
-                Unsigned::Int(5),
-            ))))),
-            op: BinOp::Add,
-            rhs: Box::new(AST::new(ASTKind::Val(Value::Integer(SignKind::Unsigned(
-                Unsigned::Int(10),
-            ))))),
-        });
-
-        let var = AST::new(ASTKind::VarDec(Variable(TypeInstance::Int, "a", expr)));
-        let ret = Return(Box::new(AST {
-            kind: ASTKind::Val(Value::Integer(SignKind::Unsigned(Unsigned::Int(0)))),
-        }));
-
-        let ret_ast = AST {
-            kind: ASTKind::Return(ret),
-        };
+        let source = parse("void main() { int a = 5u + 10u; return 0u; }", 0).unwrap();
+        let mut s_map = SymbolMap::new();
+        s_map.fill_from_source(&source.0).unwrap();
+
+        assert_eq!(s_map.tables().len(), 2);
+        let main = s_map.table(0).get("main").unwrap();
+        assert!(matches!(main.kind(), ScopeKind::FSign));
+        assert!(
+            matches!(main.get_type(), TypeInstance::Func(ret, name, _) if *ret == TypeInstance::Void && name == "main")
+        );
 
-        let main_ast = ASTKind::Func(Func(
-            FuncDef(TypeInstance::Void, "main", Vec::new()),
-            vec![var, ret_ast],
-        ));
+        let a = s_map.table(1).get("a").unwrap();
+        assert!(matches!(a.kind(), ScopeKind::Var));
+        assert_eq!(a.get_type(), TypeInstance::Int);
+        assert_eq!(a.span(), Span::new(0, 18, 19));
+        assert!(s_map.table(0).get("a").is_none());
+    }
 
-        let source = Source(vec![AST::new(main_ast)]);
+    #[test]
+    fn redefinition_points_at_both_declarations() {
+        let src = "int main() {\n  int a = 1;\n  int a = 2;\n  return a;\n}\n";
+        let source = parse(src, 0).unwrap();
         let mut s_map = SymbolMap::new();
-        let _ = s_map.fill_from_source(&source.0);
+        let mut errors = s_map.fill_from_source(&source.0).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        let err = errors.remove(0);
+        let SymbolError::AlreadyExists {
+            ref name,
+            original,
+            duplicate,
+        } = err
+        else {
+            panic!("expected a redefinition")
+        };
+        assert_eq!(name, "a");
+        assert_eq!(original, Span::new(0, 19, 20));
+        assert_eq!(duplicate, Span::new(0, 32, 33));
+
+        let report = Report::new(err).with_source_code(src);
+        let mut out = String::new();
+        GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor())
+            .render_report(&mut out, report.as_ref())
+            .unwrap();
+        assert!(out.contains("redefinition of `a`"));
+        assert!(out.contains("`a` first declared here"));
+        assert!(out.contains("redeclared here"));
+    }
 
-        for ast in source.0 {
-            println!("{}", ast.kind);
+    #[test]
+    fn nested_scopes_and_shadowing() {
+        let src = "int a = 1; int f(int b) { int a = b; { char a = 2; { a; b; } } return a; }";
+        let source = parse(src, 0).unwrap();
+        let mut s_map = SymbolMap::new();
+        s_map.fill_from_source(&source.0).unwrap();
+
+        // file -> f -> block -> block
+        assert!(s_map.table(0).parent().is_none());
+        assert_eq!(s_map.table(0).children(), [1]);
+        assert_eq!(s_map.table(1).parent(), Some(0));
+        assert_eq!(s_map.table(1).children(), [2]);
+        assert_eq!(s_map.table(2).children(), [3]);
+        assert!(s_map.table(3).children().is_empty());
+
+        assert!(s_map.get(0, "f").is_some());
+        assert!(s_map.get(0, "b").is_none());
+        let declared_at = |scope, name| {
+            s_map
+                .resolve(scope, name)
+                .map(|id| s_map.symbol(id).span().start)
+        };
+        assert_eq!(declared_at(0, "a"), Some(4));
+        assert_eq!(declared_at(1, "a"), Some(30));
+        assert_eq!(declared_at(3, "a"), Some(44));
+        assert_eq!(declared_at(3, "b"), Some(21));
+        assert!(declared_at(0, "b").is_none());
+    }
+
+    #[test]
+    fn undeclared_identifiers() {
+        for (src, name, start) in [
+            ("int f() { { int x = 1; } return x; }", "x", 32),
+            ("int g = h;", "h", 8),
+        ] {
+            let source = parse(src, 0).unwrap();
+            let errors = SymbolMap::new().fill_from_source(&source.0).unwrap_err();
+            let [ref err] = errors[..] else {
+                panic!("expected a single error in `{src}`")
+            };
+            let SymbolError::Undeclared {
+                name: ref found,
+                span,
+            } = *err
+            else {
+                panic!("expected an undeclared identifier in `{src}`")
+            };
+            assert_eq!(found, name);
+            assert_eq!(span, Span::new(0, start, start + 1));
+            assert_eq!(
+                err.to_string(),
+                format!("use of undeclared identifier `{name}`")
+            );
         }
+    }
 
-        println!("{}", s_map);
+    #[test]
+    fn uses_link_to_declarations() {
+        let src = "int g(char c); long f(int a) { { int a = g; } return a; }";
+        let source = parse(src, 0).unwrap();
+        let mut s_map = SymbolMap::new();
+        s_map.fill_from_source(&source.0).unwrap();
+
+        let at = |start: usize| s_map.symbol_at(Span::new(0, start, start + 1)).unwrap();
+        let (g, param_a, local_a, g_use, a_use) = (at(4), at(26), at(37), at(41), at(53));
+        assert_eq!(g_use, g);
+        assert_eq!(a_use, param_a);
+        assert_ne!(local_a, param_a);
+        assert_eq!(param_a.table, 1);
+        assert_eq!(local_a.table, 2);
+
+        let symbol = s_map.symbol(g_use);
+        assert_eq!(symbol.name(), "g");
+        assert!(matches!(symbol.kind(), ScopeKind::FSign));
+        assert!(symbol.get_type().to_string().starts_with("int g"));
+        let symbol = s_map.symbol(a_use);
+        assert!(matches!(symbol.kind(), ScopeKind::Param));
+        assert_eq!(symbol.get_type(), TypeInstance::Int);
+    }
+
+    fn errors_of(src: &str) -> Vec<SymbolError> {
+        let source = parse(src, 0).unwrap();
+        SymbolMap::new().fill_from_source(&source.0).unwrap_err()
+    }
+
+    #[test]
+    fn use_before_declaration() {
+        let errors = errors_of("int f() { int y = z; int z = 1; return y; }");
+        let [SymbolError::UsedBeforeDeclaration {
+            ref name,
+            used,
+            declared,
+        }] = errors[..]
+        else {
+            panic!("expected a use before declaration")
+        };
+        assert_eq!(name, "z");
+        assert_eq!(used, Span::new(0, 18, 19));
+        assert_eq!(declared, Span::new(0, 25, 26));
+    }
+
+    #[test]
+    fn function_declarations_and_definitions() {
+        let source = parse("int f(int a); int f(int b); int f(int c) { return c; }", 0).unwrap();
+        let mut s_map = SymbolMap::new();
+        s_map.fill_from_source(&source.0).unwrap();
+        let f = s_map.symbol_at(Span::new(0, 4, 5)).unwrap();
+        assert_eq!(s_map.symbol_at(Span::new(0, 32, 33)), Some(f));
+
+        let errors = errors_of("int f(int a); long f(int a) { return a; }");
+        let [SymbolError::ConflictingSignature {
+            original,
+            duplicate,
+            ..
+        }] = errors[..]
+        else {
+            panic!("expected conflicting signatures")
+        };
+        assert_eq!(original, Span::new(0, 4, 5));
+        assert_eq!(duplicate, Span::new(0, 19, 20));
+
+        let errors = errors_of("int f() { return 0; } int f() { return 1; }");
+        assert!(matches!(errors[..], [SymbolError::AlreadyExists { .. }]));
+
+        let errors = errors_of("int f = 0; int f() { return 0; }");
+        assert!(matches!(errors[..], [SymbolError::AlreadyExists { .. }]));
+    }
+
+    #[test]
+    fn collects_every_error() {
+        let src = "int f(int a, char a); int f(int b) { void v = 0; return x; } void g(void p) { }";
+        let errors = errors_of(src);
+        let codes: Vec<String> = errors
+            .iter()
+            .map(|e| e.code().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                "xlang::sym::duplicate_param",
+                "xlang::sym::conflicting_signature",
+                "xlang::sym::void_variable",
+                "xlang::sym::undeclared",
+                "xlang::sym::void_variable",
+            ]
+        );
+        assert!(errors.iter().all(|e| e.help().is_some()));
+        assert_eq!(errors[2].to_string(), "`v` is declared with type `void`");
     }
 }
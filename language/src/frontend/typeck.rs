@@ -0,0 +1,641 @@
+//! Type checking of the AST: every expression gets a `TypeInstance`
+//! following C's integer promotions and usual arithmetic conversions.
+//! Names are typed through the `SymbolMap` that resolved them; names it
+//! could not resolve are left untyped, reporting them is up to resolution.
+
+use std::{collections::HashMap, fmt::Display};
+
+use miette::Diagnostic;
+
+use crate::types::{designators::TypeInstance, layout::TargetLayout};
+
+use super::{
+    ast::{ASTKind, CallExpr, Expr, Func, FuncDef, Source, UnaryExpr, AST},
+    span::Span,
+    symboltable::{ScopeKind, SymbolMap},
+};
+
+#[derive(Debug, Diagnostic)]
+pub enum TypeError {
+    #[diagnostic(code(xlang::typeck::void_value))]
+    VoidValue {
+        #[label("this has type `void`")]
+        span: Span,
+    },
+    #[diagnostic(code(xlang::typeck::invalid_operands))]
+    InvalidOperands {
+        op: String,
+        lhs: String,
+        rhs: String,
+        #[label("both operands must be integers")]
+        span: Span,
+    },
+    #[diagnostic(code(xlang::typeck::invalid_conversion))]
+    InvalidConversion {
+        from: String,
+        to: String,
+        #[label("this has type `{from}`")]
+        span: Span,
+    },
+    #[diagnostic(
+        code(xlang::typeck::return_in_void),
+        help("remove the value or change the return type")
+    )]
+    ReturnInVoid {
+        func: String,
+        #[label("returned here")]
+        value: Span,
+        #[label("`{func}` is declared to return `void`")]
+        decl: Span,
+    },
+    #[diagnostic(
+        code(xlang::typeck::return_without_value),
+        help("return a value of type `{ret}`")
+    )]
+    ReturnWithoutValue {
+        func: String,
+        ret: String,
+        #[label("returns nothing")]
+        span: Span,
+        #[label("`{func}` is declared to return `{ret}`")]
+        decl: Span,
+    },
+    #[diagnostic(code(xlang::typeck::not_callable))]
+    NotCallable {
+        name: String,
+        _type: String,
+        #[label("this has type `{_type}`")]
+        span: Span,
+    },
+    #[diagnostic(code(xlang::typeck::argument_count))]
+    ArgumentCount {
+        func: String,
+        expected: usize,
+        found: usize,
+        #[label("called with {found}")]
+        span: Span,
+        #[label("declared here")]
+        decl: Span,
+    },
+    #[diagnostic(
+        code(xlang::typeck::global_variable),
+        help("file-scope variables are not supported yet, declare `{name}` inside a function")
+    )]
+    GlobalVariable {
+        name: String,
+        #[label("declared at file scope")]
+        span: Span,
+    },
+    #[diagnostic(code(xlang::typeck::not_assignable))]
+    NotAssignable {
+        name: String,
+        #[label("`{name}` is a function")]
+        span: Span,
+    },
+    #[diagnostic(code(xlang::typeck::invalid_sizeof))]
+    InvalidSizeOf {
+        _type: String,
+        #[label("`{_type}` has no size")]
+        span: Span,
+    },
+}
+
+impl Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeError::VoidValue { .. } => write!(f, "void value used in an expression"),
+            TypeError::InvalidOperands { op, lhs, rhs, .. } => {
+                write!(f, "invalid operands to `{op}` (`{lhs}` and `{rhs}`)")
+            }
+            TypeError::InvalidConversion { from, to, .. } => {
+                write!(f, "cannot convert `{from}` to `{to}`")
+            }
+            TypeError::ReturnInVoid { func, .. } => {
+                write!(f, "`return` with a value in `{func}`, which returns `void`")
+            }
+            TypeError::ReturnWithoutValue { func, .. } => {
+                write!(f, "`return` without a value in `{func}`")
+            }
+            TypeError::NotCallable { name, .. } => write!(f, "`{name}` is not a function"),
+            TypeError::ArgumentCount {
+                func,
+                expected,
+                found,
+                ..
+            } => write!(
+                f,
+                "`{func}` takes {expected} argument{} but {found} {} given",
+                if *expected == 1 { "" } else { "s" },
+                if *found == 1 { "was" } else { "were" }
+            ),
+            TypeError::GlobalVariable { name, .. } => {
+                write!(f, "`{name}` is a file-scope variable")
+            }
+            TypeError::NotAssignable { name, .. } => {
+                write!(f, "cannot assign to function `{name}`")
+            }
+            TypeError::InvalidSizeOf { _type, .. } => {
+                write!(f, "invalid application of `sizeof` to `{_type}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+/// The type of every expression, keyed by the span of its node.
+#[derive(Debug, Default)]
+pub struct ExprTypes(HashMap<Span, TypeInstance>);
+
+impl ExprTypes {
+    pub fn get(&self, span: Span) -> Option<&TypeInstance> {
+        self.0.get(&span)
+    }
+}
+
+/// Checks every function in `source`, whose names `map` resolved, and
+/// rejects the file-scope variables lowering has no place for.
+pub fn check<'a>(
+    source: &'a Source<'a>,
+    map: &'a SymbolMap,
+    layout: &TargetLayout,
+) -> Result<ExprTypes, Vec<TypeError>> {
+    let mut checker = Checker {
+        map,
+        layout: *layout,
+        func: None,
+        types: ExprTypes::default(),
+        errors: Vec::new(),
+    };
+    for ast in source.0.iter() {
+        checker.item(ast);
+    }
+    if checker.errors.is_empty() {
+        Ok(checker.types)
+    } else {
+        Err(checker.errors)
+    }
+}
+
+/// Integer promotion: types of lower rank than `int` become `int` if it
+/// can represent all of their values, `unsigned int` otherwise.
+pub fn promote(_type: &TypeInstance, layout: &TargetLayout) -> TypeInstance {
+    match rank(_type) {
+        Some(rank) if rank < 3 => {
+            let (size, int) = (layout.size_of(_type), layout.size_of(&TypeInstance::Int));
+            if size < int || !_type.is_unsigned() {
+                TypeInstance::Int
+            } else {
+                TypeInstance::UInt
+            }
+        }
+        _ => _type.clone(),
+    }
+}
+
+fn rank(_type: &TypeInstance) -> Option<u8> {
+    match _type {
+        TypeInstance::Char | TypeInstance::SChar | TypeInstance::UChar => Some(1),
+        TypeInstance::Short | TypeInstance::UShort => Some(2),
+        TypeInstance::Int | TypeInstance::UInt => Some(3),
+        TypeInstance::Long | TypeInstance::ULong => Some(4),
+        TypeInstance::LongLong | TypeInstance::ULongLong => Some(5),
+        TypeInstance::Func(..) | TypeInstance::Ptr(_) | TypeInstance::Void => None,
+    }
+}
+
+pub fn is_integer(_type: &TypeInstance) -> bool {
+    rank(_type).is_some()
+}
+
+/// The usual arithmetic conversions: the type both integer operands of a
+/// binary operator are converted to, and the type of its result.
+pub fn common_type(lhs: &TypeInstance, rhs: &TypeInstance, layout: &TargetLayout) -> TypeInstance {
+    let (lhs, rhs) = (promote(lhs, layout), promote(rhs, layout));
+    if lhs.is_unsigned() == rhs.is_unsigned() {
+        return if rank(&lhs) >= rank(&rhs) { lhs } else { rhs };
+    }
+    let (unsigned, signed) = if lhs.is_unsigned() {
+        (lhs, rhs)
+    } else {
+        (rhs, lhs)
+    };
+    if rank(&unsigned) >= rank(&signed) {
+        unsigned
+    } else if layout.size_of(&signed) > layout.size_of(&unsigned) {
+        // The signed type can represent every value of the unsigned one.
+        signed
+    } else {
+        signed.to_unsigned()
+    }
+}
+
+struct Checker<'a> {
+    map: &'a SymbolMap,
+    layout: TargetLayout,
+    /// The function whose body is being checked.
+    func: Option<&'a FuncDef<'a>>,
+    types: ExprTypes,
+    errors: Vec<TypeError>,
+}
+
+impl<'a> Checker<'a> {
+    fn item(&mut self, ast: &'a AST<'a>) {
+        match &ast.kind {
+            ASTKind::Func(func) => self.func(func),
+            ASTKind::FuncDef(_) => (),
+            ASTKind::VarDec(var) => {
+                self.errors.push(TypeError::GlobalVariable {
+                    name: var.1.to_string(),
+                    span: var.3,
+                });
+                self.stmt(ast);
+            }
+            _ => self.stmt(ast),
+        }
+    }
+
+    fn func(&mut self, func: &'a Func<'a>) {
+        self.func = Some(&func.0);
+        for stmt in func.1.iter() {
+            self.stmt(stmt);
+        }
+        self.func = None;
+    }
+
+    fn stmt(&mut self, ast: &'a AST<'a>) {
+        match &ast.kind {
+            ASTKind::VarDec(var) => {
+                let span = expr_span(&var.2).unwrap_or(ast.span);
+                let found = self.expr(&var.2, span);
+                // A `void` variable is an error of the declaration itself,
+                // not of its initializer.
+                if let Some(found) = found.filter(|_| var.0 != TypeInstance::Void) {
+                    self.convert(&found, &var.0, span);
+                }
+            }
+            ASTKind::Expr(expr) => {
+                self.expr(expr, ast.span);
+            }
+            ASTKind::Return(ret) => {
+                let found = ret.0.as_ref().and_then(|value| self.ast(value));
+                let Some(fdef) = self.func else {
+                    return;
+                };
+                match &ret.0 {
+                    Some(value) if fdef.0 == TypeInstance::Void => {
+                        self.errors.push(TypeError::ReturnInVoid {
+                            func: fdef.1.to_string(),
+                            value: value.span,
+                            decl: fdef.3,
+                        })
+                    }
+                    Some(value) => {
+                        if let Some(found) = found {
+                            self.convert(&found, &fdef.0, value.span);
+                        }
+                    }
+                    None if fdef.0 != TypeInstance::Void => {
+                        self.errors.push(TypeError::ReturnWithoutValue {
+                            func: fdef.1.to_string(),
+                            ret: describe(&fdef.0),
+                            span: ast.span,
+                            decl: fdef.3,
+                        })
+                    }
+                    None => (),
+                }
+            }
+            ASTKind::Block(block) => {
+                for stmt in block.0.iter() {
+                    self.stmt(stmt);
+                }
+            }
+            ASTKind::Val(_) => {
+                self.ast(ast);
+            }
+            ASTKind::Param(_) | ASTKind::FuncDef(_) | ASTKind::Func(_) => (),
+        }
+    }
+
+    /// Checks that a value of type `from` can be implicitly converted to `to`.
+    fn convert(&mut self, from: &TypeInstance, to: &TypeInstance, span: Span) {
+        if *from == TypeInstance::Void {
+            self.errors.push(TypeError::VoidValue { span });
+        } else if !(is_integer(from) && is_integer(to)) && from != to {
+            self.errors.push(TypeError::InvalidConversion {
+                from: describe(from),
+                to: describe(to),
+                span,
+            });
+        }
+    }
+
+    fn ast(&mut self, ast: &AST<'a>) -> Option<TypeInstance> {
+        match &ast.kind {
+            ASTKind::Val(val) => {
+                let _type = val.get_type();
+                self.types.0.insert(ast.span, _type.clone());
+                Some(_type)
+            }
+            ASTKind::Expr(expr) => self.expr(expr, ast.span),
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr<'a>, span: Span) -> Option<TypeInstance> {
+        let _type = match expr {
+            Expr::Noop(val) => val.get_type(),
+            Expr::Unary(UnaryExpr::Id(id)) => self.map.symbol(self.map.resolved(id)?).get_type(),
+            Expr::Binary(bin) => {
+                let lhs = self.ast(&bin.lhs);
+                let rhs = self.ast(&bin.rhs);
+                let (lhs, rhs) = (
+                    self.operand(lhs, bin.lhs.span)?,
+                    self.operand(rhs, bin.rhs.span)?,
+                );
+                if !(is_integer(&lhs) && is_integer(&rhs)) {
+                    self.errors.push(TypeError::InvalidOperands {
+                        op: bin.op.to_string(),
+                        lhs: describe(&lhs),
+                        rhs: describe(&rhs),
+                        span,
+                    });
+                    return None;
+                }
+                common_type(&lhs, &rhs, &self.layout)
+            }
+            Expr::Call(call) => self.call(call)?,
+            Expr::Assign(assign) => {
+                let value = self.ast(&assign.value);
+                let target = self.map.symbol(self.map.resolved(&assign.target)?);
+                if matches!(target.kind(), ScopeKind::FSign) {
+                    self.errors.push(TypeError::NotAssignable {
+                        name: assign.target.0.to_string(),
+                        span: assign.target.1,
+                    });
+                    return None;
+                }
+                if let Some(value) = value {
+                    self.convert(&value, &target.get_type(), assign.value.span);
+                }
+                target.get_type()
+            }
+            Expr::SizeOf(size) => {
+                if self.layout.size_of(&size._type).is_none() {
+                    self.errors.push(TypeError::InvalidSizeOf {
+                        _type: describe(&size._type),
+                        span: size.span,
+                    });
+                    return None;
+                }
+                self.layout.size_type()
+            }
+        };
+        self.types.0.insert(span, _type.clone());
+        Some(_type)
+    }
+
+    /// Checks the arguments of `call` against the parameters of the function
+    /// and returns what it returns.
+    fn call(&mut self, call: &CallExpr<'a>) -> Option<TypeInstance> {
+        let args: Vec<_> = call.args.iter().map(|arg| self.ast(arg)).collect();
+        let func = self.map.symbol(self.map.resolved(&call.func)?);
+        let TypeInstance::Func(ret, name, params) = func.get_type() else {
+            self.errors.push(TypeError::NotCallable {
+                name: call.func.0.to_string(),
+                _type: describe(&func.get_type()),
+                span: call.func.1,
+            });
+            return None;
+        };
+        // A declaration without a prototype takes any arguments.
+        let Some(params) = params else {
+            return Some(*ret);
+        };
+        if params.len() != args.len() {
+            self.errors.push(TypeError::ArgumentCount {
+                func: name,
+                expected: params.len(),
+                found: args.len(),
+                span: call.span,
+                decl: func.span(),
+            });
+            return Some(*ret);
+        }
+        for ((arg, found), param) in call.args.iter().zip(args).zip(params.iter()) {
+            if let Some(found) = found {
+                self.convert(&found, param, arg.span);
+            }
+        }
+        Some(*ret)
+    }
+
+    /// An operand of a binary operator, which may not be `void`.
+    fn operand(&mut self, _type: Option<TypeInstance>, span: Span) -> Option<TypeInstance> {
+        match _type? {
+            TypeInstance::Void => {
+                self.errors.push(TypeError::VoidValue { span });
+                None
+            }
+            _type => Some(_type),
+        }
+    }
+}
+
+/// How a type is spelled in diagnostics.
+fn describe(_type: &TypeInstance) -> String {
+    match _type {
+        TypeInstance::Func(ret, name, params) => {
+            let params: Vec<String> = params.iter().flatten().map(describe).collect();
+            format!("{} {}({})", describe(ret), name, params.join(", "))
+        }
+        _type => _type.to_string(),
+    }
+}
+
+/// The span of an expression that is not wrapped in an `AST` node, if it
+/// can be recovered from its children.
+fn expr_span(expr: &Expr) -> Option<Span> {
+    match expr {
+        Expr::Binary(bin) => Some(bin.lhs.span.to(bin.rhs.span)),
+        Expr::Call(call) => Some(call.span),
+        Expr::SizeOf(size) => Some(size.span),
+        Expr::Assign(assign) => Some(assign.target.1.to(assign.value.span)),
+        Expr::Unary(_) | Expr::Noop(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        frontend::{parser::parse, span::Span, symboltable::SymbolMap},
+        types::{designators::TypeInstance, layout::TargetLayout},
+    };
+
+    use super::{check, common_type, ExprTypes, TypeError};
+
+    fn check_src(src: &str) -> Result<ExprTypes, Vec<TypeError>> {
+        let source = parse(src, 0).unwrap();
+        let mut map = SymbolMap::new();
+        map.fill_from_source(&source.0).unwrap();
+        check(&source, &map, &TargetLayout::LP64)
+    }
+
+    #[test]
+    fn arithmetic_conversions() {
+        use TypeInstance::*;
+        let lp64 = TargetLayout::LP64;
+        assert_eq!(common_type(&Char, &Short, &lp64), Int);
+        assert_eq!(common_type(&Char, &Char, &lp64), Int);
+        assert_eq!(common_type(&Int, &Long, &lp64), Long);
+        assert_eq!(common_type(&LongLong, &Long, &lp64), LongLong);
+        assert_eq!(common_type(&UChar, &UShort, &lp64), Int);
+        assert_eq!(common_type(&UInt, &Int, &lp64), UInt);
+        assert_eq!(common_type(&UInt, &Long, &lp64), Long);
+        assert_eq!(common_type(&ULong, &LongLong, &lp64), ULongLong);
+        assert_eq!(common_type(&SChar, &ULong, &lp64), ULong);
+        // `long` cannot hold every `unsigned int` when both are 32 bits.
+        assert_eq!(common_type(&UInt, &Long, &TargetLayout::ILP32), ULong);
+
+        let src = "long f(char c, short s, long l) { long long x = c + s * l; return c + s; }";
+        let types = check_src(src).unwrap();
+        let at = |text: &str| {
+            let start = src.rfind(text).unwrap();
+            types.get(Span::new(0, start, start + text.len())).cloned()
+        };
+        assert_eq!(at("c"), Some(Char));
+        assert_eq!(at("s * l"), Some(Long));
+        assert_eq!(at("c + s * l"), Some(Long));
+        assert_eq!(at("c + s"), Some(Int));
+    }
+
+    #[test]
+    fn void_and_function_values() {
+        let src = "int g(char c); void v = 0; int f() { int a = v + 1; int b = g; return g * 2; }";
+        let source = parse(src, 0).unwrap();
+        let mut map = SymbolMap::new();
+        // `v` itself is rejected by the symbol pass, its uses are still typed.
+        assert_eq!(map.fill_from_source(&source.0).unwrap_err().len(), 1);
+        let errors = check(&source, &map, &TargetLayout::LP64).unwrap_err();
+        assert_eq!(errors.len(), 4);
+        assert_eq!(errors[0].to_string(), "`v` is a file-scope variable");
+        let TypeError::VoidValue { span } = &errors[1] else {
+            panic!("expected a void value error")
+        };
+        assert_eq!(*span, Span::new(0, 45, 46));
+        assert_eq!(
+            errors[2].to_string(),
+            "cannot convert `int g(char)` to `int`"
+        );
+        assert_eq!(
+            errors[3].to_string(),
+            "invalid operands to `*` (`int g(char)` and `int`)"
+        );
+    }
+
+    #[test]
+    fn file_scope_variables_are_rejected() {
+        // Used, which lowering used to read as an undefined value, and unused.
+        for src in [
+            "int g = 5; int main() { return g; }",
+            "int main() { return 0; } long g = 5;",
+        ] {
+            let errors = check_src(src).unwrap_err();
+            assert_eq!(errors.len(), 1);
+            let start = src.find(" g ").unwrap() + 1;
+            assert!(
+                matches!(&errors[0], TypeError::GlobalVariable { name, span }
+                if name == "g" && *span == Span::new(0, start, start + 1))
+            );
+        }
+        let errors = check_src("int main(); int g = 1 + main;").unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[1].to_string(),
+            "invalid operands to `+` (`int` and `int main()`)"
+        );
+    }
+
+    #[test]
+    fn returns_match_the_declaration() {
+        let src = "void f() { return 1; }";
+        let errors = check_src(src).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(
+            matches!(errors[0], TypeError::ReturnInVoid { value, decl, .. }
+                if value == Span::new(0, 18, 19) && decl == Span::new(0, 5, 6))
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "`return` with a value in `f`, which returns `void`"
+        );
+
+        check_src("long f() { char c = 1; return c; }").unwrap();
+
+        // Returns nested in blocks are checked too, against the innermost `x`.
+        let src = "int g(); int f() { int x = 1; { int x = g; } { long x = 2; return x + g; } }";
+        let errors = check_src(src).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].to_string(), "cannot convert `int g()` to `int`");
+        assert_eq!(
+            errors[1].to_string(),
+            "invalid operands to `+` (`long` and `int g()`)"
+        );
+    }
+
+    #[test]
+    fn sizeof_has_the_size_type() {
+        let src = "int f() { return sizeof(long) + 1; }";
+        let source = parse(src, 0).unwrap();
+        let mut map = SymbolMap::new();
+        map.fill_from_source(&source.0).unwrap();
+        let span = Span::new(0, 17, 29);
+        let types = check(&source, &map, &TargetLayout::LP64).unwrap();
+        assert_eq!(types.get(span), Some(&TypeInstance::ULong));
+        let types = check(&source, &map, &TargetLayout::ILP32).unwrap();
+        assert_eq!(types.get(span), Some(&TypeInstance::UInt));
+
+        let errors = check_src("int f() { return sizeof(void); }").unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "invalid application of `sizeof` to `void`"
+        );
+        assert!(matches!(
+            errors[..],
+            [TypeError::InvalidSizeOf { span, .. }] if span == Span::new(0, 17, 29)
+        ));
+    }
+
+    #[test]
+    fn calls_and_assignments() {
+        let src = "long g(char c); int f() { int a = 0; return a = g(300); }";
+        let types = check_src(src).unwrap();
+        let at = |text: &str| {
+            let start = src.rfind(text).unwrap();
+            types.get(Span::new(0, start, start + text.len())).cloned()
+        };
+        assert_eq!(at("g(300)"), Some(TypeInstance::Long));
+        assert_eq!(at("a = g(300)"), Some(TypeInstance::Int));
+
+        let src = "int g(char c); void v(); int f() { int h = 1; g(1, 2); int a = v(); \
+                   a = g; g = 1; h(); return; }";
+        let errors = check_src(src).unwrap_err();
+        let messages: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "`g` takes 1 argument but 2 were given",
+                "void value used in an expression",
+                "cannot convert `int g(char)` to `int`",
+                "cannot assign to function `g`",
+                "`h` is not a function",
+                "`return` without a value in `f`",
+            ]
+        );
+        assert!(
+            matches!(errors[0], TypeError::ArgumentCount { span, decl, .. }
+                if span == Span::new(0, 46, 53) && decl == Span::new(0, 4, 5))
+        );
+    }
+}
@@ -0,0 +1,502 @@
+//! A reference interpreter walking `Cfg`s directly, the oracle the native
+//! backend and the optimiser are tested against.
+//!
+//! Arithmetic happens in the type of the destination, made unsigned if
+//! either operand is. Results wrap around to the width that type has on the
+//! target's `TargetLayout`.
+
+use std::{collections::HashMap, fmt::Display};
+
+use miette::Diagnostic;
+
+use crate::{
+    frontend::{
+        ast::{BinOp, SignKind, Value},
+        cfg::Cfg,
+    },
+    types::layout::TargetLayout,
+};
+
+use super::{text::print_value, BlockId, Instruction, Operand, Terminator, ValueId};
+
+#[derive(Debug, Diagnostic)]
+pub enum InterpError {
+    #[diagnostic(code(xlang::interp::no_main), help("define `int main()`"))]
+    NoMain,
+    #[diagnostic(code(xlang::interp::unknown_function))]
+    UnknownFunction { name: String },
+    #[diagnostic(code(xlang::interp::arguments))]
+    Arguments {
+        func: String,
+        expected: usize,
+        found: usize,
+    },
+    #[diagnostic(code(xlang::interp::undefined))]
+    Undefined { func: String, value: String },
+    #[diagnostic(code(xlang::interp::division_by_zero))]
+    DivisionByZero { func: String, block: BlockId },
+    #[diagnostic(code(xlang::interp::unreachable))]
+    Unreachable { func: String, block: BlockId },
+    #[diagnostic(code(xlang::interp::stack_overflow))]
+    StackOverflow { depth: usize },
+    #[diagnostic(code(xlang::interp::out_of_steps))]
+    OutOfSteps { steps: usize },
+}
+
+impl Display for InterpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterpError::NoMain => write!(f, "no `main` function to run"),
+            InterpError::UnknownFunction { name } => write!(f, "call to unknown function `{name}`"),
+            InterpError::Arguments {
+                func,
+                expected,
+                found,
+            } => write!(f, "`{func}` takes {expected} arguments but got {found}"),
+            InterpError::Undefined { func, value } => {
+                write!(f, "in `{func}`, `{value}` is read before it is assigned")
+            }
+            InterpError::DivisionByZero { func, block } => {
+                write!(f, "in `{func}`, division by zero in {block}")
+            }
+            InterpError::Unreachable { func, block } => {
+                write!(
+                    f,
+                    "in `{func}`, control reached the unreachable end of {block}"
+                )
+            }
+            InterpError::StackOverflow { depth } => {
+                write!(f, "stack overflow, more than {depth} nested calls")
+            }
+            InterpError::OutOfSteps { steps } => {
+                write!(
+                    f,
+                    "gave up after {steps} steps, the program may not terminate"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for InterpError {}
+
+/// Runs `main` on `layout` and returns its exit value, `0` if it returns
+/// nothing.
+pub fn run(cfgs: &[Cfg], layout: &TargetLayout) -> Result<i64, InterpError> {
+    let ret = Interpreter::new(cfgs, *layout).call("main", &[])?;
+    Ok(ret.map_or(0, |val| val.to_i128() as i64))
+}
+
+pub struct Interpreter<'a> {
+    cfgs: &'a [Cfg],
+    funcs: HashMap<&'a str, usize>,
+    max_depth: usize,
+    max_steps: usize,
+    layout: TargetLayout,
+}
+
+/// An activation of a function.
+struct Frame {
+    func: usize,
+    block: BlockId,
+    pos: usize,
+    env: Vec<Option<Value>>,
+    /// Where the caller wants the result.
+    dst: Option<ValueId>,
+}
+
+impl<'a> Interpreter<'a> {
+    /// An interpreter of `cfgs` whose integers have the widths of `layout`.
+    pub fn new(cfgs: &'a [Cfg], layout: TargetLayout) -> Self {
+        Self {
+            cfgs,
+            funcs: cfgs
+                .iter()
+                .enumerate()
+                .map(|(i, cfg)| (cfg.name(), i))
+                .collect(),
+            max_depth: 10_000,
+            max_steps: 100_000_000,
+            layout,
+        }
+    }
+
+    /// Bounds the number of instructions run, so that a program that loops
+    /// forever fails instead of hanging.
+    pub fn set_max_steps(&mut self, steps: usize) {
+        self.max_steps = steps;
+    }
+
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    /// Calls the function `name` with `args` and returns its result.
+    pub fn call(&self, name: &str, args: &[Value]) -> Result<Option<Value>, InterpError> {
+        if name == "main" && !self.funcs.contains_key(name) {
+            return Err(InterpError::NoMain);
+        }
+        let mut stack = vec![self.enter(name, args, None)?];
+        let mut steps = 0;
+        loop {
+            steps += 1;
+            if steps > self.max_steps {
+                return Err(InterpError::OutOfSteps {
+                    steps: self.max_steps,
+                });
+            }
+            let frame = stack.last_mut().unwrap();
+            let cfg = &self.cfgs[frame.func];
+            let block = cfg.block(frame.block);
+            if let Some(instr) = block.instrs().get(frame.pos) {
+                frame.pos += 1;
+                if let Instruction::Call(call) = instr {
+                    let args = call
+                        .args
+                        .iter()
+                        .map(|arg| self.operand(cfg, &frame.env, arg))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if stack.len() >= self.max_depth {
+                        return Err(InterpError::StackOverflow {
+                            depth: self.max_depth,
+                        });
+                    }
+                    stack.push(self.enter(&call.func, &args, call.dst)?);
+                } else {
+                    self.step(cfg, frame, instr)?;
+                }
+                continue;
+            }
+
+            let ret = match block.term() {
+                Terminator::Ret(op) => op
+                    .map(|op| self.operand(cfg, &frame.env, &op))
+                    .transpose()?
+                    .and_then(|val| val.convert(cfg.ret_type(), &self.layout)),
+                Terminator::Jump(target) => {
+                    self.jump(cfg, frame, *target)?;
+                    continue;
+                }
+                Terminator::Branch(cond, then, _else) => {
+                    let cond = self.operand(cfg, &frame.env, cond)?;
+                    let target = if cond.to_i128() != 0 { *then } else { *_else };
+                    self.jump(cfg, frame, target)?;
+                    continue;
+                }
+                Terminator::Unreachable => {
+                    return Err(InterpError::Unreachable {
+                        func: cfg.name().to_string(),
+                        block: frame.block,
+                    })
+                }
+            };
+            let done = stack.pop().unwrap();
+            let Some(caller) = stack.last_mut() else {
+                return Ok(ret);
+            };
+            if let (Some(dst), Some(val)) = (done.dst, ret) {
+                let cfg = &self.cfgs[caller.func];
+                let _type = &cfg.value(dst)._type;
+                caller.env[dst.0] = val.convert(_type, &self.layout);
+            }
+        }
+    }
+
+    fn enter(
+        &self,
+        name: &str,
+        args: &[Value],
+        dst: Option<ValueId>,
+    ) -> Result<Frame, InterpError> {
+        let Some(func) = self.funcs.get(name).copied() else {
+            return Err(InterpError::UnknownFunction {
+                name: name.to_string(),
+            });
+        };
+        let cfg = &self.cfgs[func];
+        if cfg.params().len() != args.len() {
+            return Err(InterpError::Arguments {
+                func: name.to_string(),
+                expected: cfg.params().len(),
+                found: args.len(),
+            });
+        }
+        let mut env = vec![None; cfg.values().len()];
+        for (param, arg) in cfg.params().iter().zip(args) {
+            env[param.0] = arg.convert(&cfg.value(*param)._type, &self.layout);
+        }
+        let mut frame = Frame {
+            func,
+            block: cfg.entry(),
+            pos: 0,
+            env,
+            dst,
+        };
+        if cfg.blocks().is_empty() {
+            return Err(InterpError::Unreachable {
+                func: name.to_string(),
+                block: frame.block,
+            });
+        }
+        self.skip_phis(cfg, &mut frame);
+        Ok(frame)
+    }
+
+    /// Moves to `target`, assigning all of its phis at once.
+    fn jump(&self, cfg: &Cfg, frame: &mut Frame, target: BlockId) -> Result<(), InterpError> {
+        let mut assigned = Vec::new();
+        for instr in cfg.block(target).instrs() {
+            let Instruction::Phi(phi) = instr else {
+                break;
+            };
+            let op = phi.incoming_from(frame.block).unwrap_or_else(|| {
+                panic!("phi in {target} of `{}` misses {}", cfg.name(), frame.block)
+            });
+            let val = self.operand(cfg, &frame.env, op)?;
+            assigned.push((phi.dst, val));
+        }
+        for (dst, val) in assigned {
+            frame.env[dst.0] = val.convert(&cfg.value(dst)._type, &self.layout);
+        }
+        frame.block = target;
+        frame.pos = 0;
+        self.skip_phis(cfg, frame);
+        Ok(())
+    }
+
+    fn skip_phis(&self, cfg: &Cfg, frame: &mut Frame) {
+        frame.pos = cfg
+            .block(frame.block)
+            .instrs()
+            .iter()
+            .take_while(|instr| matches!(instr, Instruction::Phi(_)))
+            .count();
+    }
+
+    fn step(&self, cfg: &Cfg, frame: &mut Frame, instr: &Instruction) -> Result<(), InterpError> {
+        let (dst, val) = match instr {
+            Instruction::BAssign(bin) => {
+                let lhs = self.operand(cfg, &frame.env, &bin.lop)?;
+                let rhs = self.operand(cfg, &frame.env, &bin.rop)?;
+                let _type = &cfg.value(bin.dst)._type;
+                // Well-typed operands already share the destination's type;
+                // unsigned constants in hand-written IR make it unsigned.
+                let op_type = if is_unsigned(lhs) || is_unsigned(rhs) {
+                    _type.to_unsigned()
+                } else {
+                    _type.clone()
+                };
+                let widen = |val: Value| {
+                    val.convert(&op_type, &self.layout)
+                        .map_or(0, |v| v.to_i128())
+                };
+                let (a, b) = (widen(lhs), widen(rhs));
+                let result = match bin.op {
+                    BinOp::Add => a.wrapping_add(b),
+                    BinOp::Sub => a.wrapping_sub(b),
+                    BinOp::Mul => a.wrapping_mul(b),
+                    BinOp::Div if b == 0 => {
+                        return Err(InterpError::DivisionByZero {
+                            func: cfg.name().to_string(),
+                            block: frame.block,
+                        })
+                    }
+                    BinOp::Div => a / b,
+                };
+                (bin.dst, Value::wrap(result, _type, &self.layout))
+            }
+            Instruction::SAssign(single) => {
+                let val = self.operand(cfg, &frame.env, &single.src)?;
+                let _type = &cfg.value(single.dst)._type;
+                (single.dst, val.convert(_type, &self.layout))
+            }
+            Instruction::Mov(mov) => {
+                let val = self.operand(cfg, &frame.env, &mov.src)?;
+                let _type = &cfg.value(mov.dst)._type;
+                (mov.dst, val.convert(_type, &self.layout))
+            }
+            Instruction::Call(_) | Instruction::Phi(_) => {
+                unreachable!("calls and phis are handled by the caller")
+            }
+        };
+        frame.env[dst.0] = val;
+        Ok(())
+    }
+
+    fn operand(
+        &self,
+        cfg: &Cfg,
+        env: &[Option<Value>],
+        op: &Operand,
+    ) -> Result<Value, InterpError> {
+        match op {
+            Operand::Const(val) => Ok(*val),
+            Operand::Value(id) => env[id.0].ok_or_else(|| InterpError::Undefined {
+                func: cfg.name().to_string(),
+                value: print_value(cfg, *id),
+            }),
+        }
+    }
+}
+
+fn is_unsigned(val: Value) -> bool {
+    matches!(val, Value::Integer(SignKind::Unsigned(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        frontend::{
+            ast::{SignKind, Signed, Value},
+            cfg::Cfg,
+            parser,
+            symboltable::SymbolMap,
+        },
+        ir::text::parse,
+        types::layout::TargetLayout,
+    };
+
+    use super::{run, InterpError, Interpreter};
+
+    fn run_text(text: &str) -> Result<i64, InterpError> {
+        run(&parse(text).unwrap(), &TargetLayout::LP64)
+    }
+
+    #[test]
+    fn runs_lowered_source() {
+        let source = parser::parse("int main() { int a = 5 + 10 * 2; return a - 3; }", 0).unwrap();
+        let mut map = SymbolMap::new();
+        map.fill_from_source(&source.0).unwrap();
+        let cfgs = Cfg::fill_from_source(&source, &map, &TargetLayout::LP64);
+        assert_eq!(run(&cfgs, &TargetLayout::LP64).unwrap(), 22);
+
+        // Inner declarations shadow outer ones until their block ends.
+        for (src, expected) in [
+            (
+                "int main() { int a = 1; { int a = 10; { int b = a * 2; } } return a; }",
+                1,
+            ),
+            (
+                "int main() { int a = 1; { long a = 2; { int b = 3; return a * b; } } }",
+                6,
+            ),
+        ] {
+            let source = parser::parse(src, 0).unwrap();
+            let mut map = SymbolMap::new();
+            map.fill_from_source(&source.0).unwrap();
+            let cfgs = Cfg::fill_from_source(&source, &map, &TargetLayout::LP64);
+            assert_eq!(run(&cfgs, &TargetLayout::LP64).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn arithmetic_wraps_at_each_width() {
+        let result = |ty: &str, expr: &str| {
+            run_text(&format!(
+                "fn main() -> long {{\n {ty} %r\nbb0:\n %r = {expr}\n ret %r\n}}"
+            ))
+            .unwrap()
+        };
+        assert_eq!(result("char", "char 127 + char 1"), -128);
+        assert_eq!(result("char", "uchar 255 + uchar 1"), 0);
+        assert_eq!(result("short", "short -32768 - short 1"), 32767);
+        assert_eq!(result("int", "int 2147483647 * int 2"), -2);
+        assert_eq!(result("int", "uint 4294967295 / int 2"), 2147483647);
+        assert_eq!(result("int", "int -7 / int 2"), -3);
+        assert_eq!(
+            result("long", "long 9223372036854775807 + long 1"),
+            i64::MIN
+        );
+        assert_eq!(result("longlong", "ulonglong 0 - ulonglong 1"), -1);
+        assert_eq!(result("uint", "int -1 / int 2"), 2147483647);
+        assert_eq!(result("uchar", "char -1 + char 0"), 255);
+        assert_eq!(result("ulong", "long -8 / long 2"), i64::MAX - 3);
+        // Moves truncate to the destination type.
+        assert_eq!(result("char", "int 300"), 44);
+
+        // `long` is only 32 bits wide on ILP32.
+        let cfgs = parse(
+            "fn main() -> longlong {\n long %r\nbb0:\n %r = long 2147483647 + long 1\n ret %r\n}",
+        )
+        .unwrap();
+        let interp = Interpreter::new(&cfgs, TargetLayout::ILP32);
+        let min = Value::Integer(SignKind::Signed(Signed::LongLong(i32::MIN.into())));
+        assert_eq!(interp.call("main", &[]).unwrap(), Some(min));
+    }
+
+    #[test]
+    fn calls_recursion_and_phis() {
+        let text = "\
+fn fact(int %n) -> int {
+    int %t
+    int %r
+    int %m
+bb0:
+    branch %n, bb1, bb2
+bb1:
+    %t = %n - int 1
+    %r = call fact(%t)
+    %m = %r * %n
+    ret %m
+bb2:
+    ret int 1
+}
+
+fn main() -> int {
+    int %i
+    int %i1
+    int %acc
+    int %acc1
+    int %f
+bb0:
+    jump bb1
+bb1:
+    %i = phi [bb0: int 5, bb2: %i1]
+    %acc = phi [bb0: int 0, bb2: %acc1]
+    branch %i, bb2, bb3
+bb2:
+    %f = call fact(%i)
+    %acc1 = %acc + %f
+    %i1 = %i - int 1
+    jump bb1
+bb3:
+    ret %acc
+}
+";
+        // 5! + 4! + 3! + 2! + 1!
+        assert_eq!(run_text(text).unwrap(), 153);
+    }
+
+    #[test]
+    fn runtime_errors() {
+        let err = run_text("fn main() -> int {\n int %x\nbb0:\n %x = int 1 / int 0\n ret %x\n}")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "in `main`, division by zero in bb0");
+
+        let err = run_text("fn main() -> int {\n int %x\nbb0:\n ret %x\n}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "in `main`, `%x` is read before it is assigned"
+        );
+
+        let err = run_text("fn f() -> int {\nbb0:\n ret int 0\n}").unwrap_err();
+        assert!(matches!(err, InterpError::NoMain));
+
+        let err =
+            run_text("fn main() -> int {\n int %x\nbb0:\n %x = call g()\n ret %x\n}").unwrap_err();
+        assert_eq!(err.to_string(), "call to unknown function `g`");
+
+        let cfgs = parse("fn main() -> int {\nbb0:\n jump bb0\n}").unwrap();
+        let mut interp = Interpreter::new(&cfgs, TargetLayout::LP64);
+        interp.set_max_steps(1000);
+        let err = interp.call("main", &[]).unwrap_err();
+        assert!(matches!(err, InterpError::OutOfSteps { steps: 1000 }));
+
+        let cfgs =
+            parse("fn main() -> int {\n int %x\nbb0:\n %x = call main()\n ret %x\n}").unwrap();
+        let mut interp = Interpreter::new(&cfgs, TargetLayout::LP64);
+        interp.set_max_depth(50);
+        let err = interp.call("main", &[]).unwrap_err();
+        assert!(matches!(err, InterpError::StackOverflow { depth: 50 }));
+    }
+}
@@ -0,0 +1,343 @@
+pub mod interp;
+pub mod ssa;
+pub mod text;
+pub mod verify;
+
+pub use verify::verify;
+
+use std::fmt::Display;
+
+use crate::{
+    frontend::ast::{BinOp, Value},
+    types::designators::TypeInstance,
+};
+
+/// Index of a value in its function's value table.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ValueId(pub usize);
+
+/// Index of a block in its function's block list.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockId(pub usize);
+
+impl Display for ValueId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl Display for BlockId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+/// A named, typed value: a user variable, a parameter or a temporary.
+#[derive(Clone, Debug)]
+pub struct ValueData {
+    pub name: String,
+    pub _type: TypeInstance,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Operand {
+    Const(Value),
+    Value(ValueId),
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand::Const(val) => write!(f, "{val}"),
+            Operand::Value(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Instruction {
+    BAssign(BinAssign),
+    SAssign(SingleAssign),
+    Mov(Move),
+    Call(Call),
+    Phi(Phi),
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::BAssign(bin) => write!(f, "{bin}"),
+            Instruction::SAssign(single) => write!(f, "{single}"),
+            Instruction::Mov(mov) => write!(f, "{mov}"),
+            Instruction::Call(call) => write!(f, "{call}"),
+            Instruction::Phi(phi) => write!(f, "{phi}"),
+        }
+    }
+}
+
+impl Instruction {
+    /// The value defined by this instruction, if any.
+    pub fn result(&self) -> Option<ValueId> {
+        match self {
+            Instruction::BAssign(bin) => Some(bin.dst),
+            Instruction::SAssign(single) => Some(single.dst),
+            Instruction::Mov(mov) => Some(mov.dst),
+            Instruction::Call(call) => call.dst,
+            Instruction::Phi(phi) => Some(phi.dst),
+        }
+    }
+
+    pub fn result_mut(&mut self) -> Option<&mut ValueId> {
+        match self {
+            Instruction::BAssign(bin) => Some(&mut bin.dst),
+            Instruction::SAssign(single) => Some(&mut single.dst),
+            Instruction::Mov(mov) => Some(&mut mov.dst),
+            Instruction::Call(call) => call.dst.as_mut(),
+            Instruction::Phi(phi) => Some(&mut phi.dst),
+        }
+    }
+
+    /// Operands read by this instruction, in evaluation order.
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Instruction::BAssign(bin) => vec![&bin.lop, &bin.rop],
+            Instruction::SAssign(single) => vec![&single.src],
+            Instruction::Mov(mov) => vec![&mov.src],
+            Instruction::Call(call) => call.args.iter().collect(),
+            Instruction::Phi(phi) => phi.incoming.iter().map(|(_, op)| op).collect(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Instruction::BAssign(bin) => vec![&mut bin.lop, &mut bin.rop],
+            Instruction::SAssign(single) => vec![&mut single.src],
+            Instruction::Mov(mov) => vec![&mut mov.src],
+            Instruction::Call(call) => call.args.iter_mut().collect(),
+            Instruction::Phi(phi) => phi.incoming.iter_mut().map(|(_, op)| op).collect(),
+        }
+    }
+}
+
+/// `dst = lop op rop`
+#[derive(Debug)]
+pub struct BinAssign {
+    pub dst: ValueId,
+    pub lop: Operand,
+    pub op: BinOp,
+    pub rop: Operand,
+}
+
+impl Display for BinAssign {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} = {} {} {}", self.dst, self.lop, self.op, self.rop)
+    }
+}
+
+/// Defines a fresh temporary, `dst = src`. The only instruction whose
+/// operand may have another integer type than `dst`, it converts as C does.
+#[derive(Debug)]
+pub struct SingleAssign {
+    pub dst: ValueId,
+    pub src: Operand,
+}
+
+impl Display for SingleAssign {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} = {}", self.dst, self.src)
+    }
+}
+
+/// Copies into a variable that may be assigned more than once.
+#[derive(Debug)]
+pub struct Move {
+    pub dst: ValueId,
+    pub src: Operand,
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} <- {}", self.dst, self.src)
+    }
+}
+
+/// `dst = func(args)`, `dst` is `None` for calls whose result is unused.
+#[derive(Debug)]
+pub struct Call {
+    pub dst: Option<ValueId>,
+    pub func: String,
+    pub args: Vec<Operand>,
+}
+
+impl Display for Call {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(dst) = self.dst {
+            write!(f, "{dst} = ")?;
+        }
+        write!(f, "call {}(", self.func)?;
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{arg}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// `dst = phi [pred: op, ...]`, picks the operand of the edge control came
+/// in on. Phis only appear at the top of a block.
+#[derive(Debug)]
+pub struct Phi {
+    pub dst: ValueId,
+    pub incoming: Vec<(BlockId, Operand)>,
+}
+
+impl Display for Phi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} = phi [", self.dst)?;
+        for (i, (pred, op)) in self.incoming.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{pred}: {op}")?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl Phi {
+    pub fn incoming_from(&self, pred: BlockId) -> Option<&Operand> {
+        self.incoming
+            .iter()
+            .find(|(block, _)| *block == pred)
+            .map(|(_, op)| op)
+    }
+}
+
+/// The single control transfer closing every block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Terminator {
+    Ret(Option<Operand>),
+    Jump(BlockId),
+    /// Goes to the first block if the condition is non-zero, else to the second.
+    Branch(Operand, BlockId, BlockId),
+    Unreachable,
+}
+
+impl Display for Terminator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Terminator::Ret(Some(op)) => write!(f, "ret {op}"),
+            Terminator::Ret(None) => write!(f, "ret"),
+            Terminator::Jump(target) => write!(f, "jump {target}"),
+            Terminator::Branch(cond, then, _else) => {
+                write!(f, "branch {cond}, {then}, {_else}")
+            }
+            Terminator::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+impl Terminator {
+    /// Distinct successor blocks, in the order they are named.
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Ret(_) | Terminator::Unreachable => Vec::new(),
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch(_, then, _else) if then == _else => vec![*then],
+            Terminator::Branch(_, then, _else) => vec![*then, *_else],
+        }
+    }
+
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Terminator::Ret(Some(op)) | Terminator::Branch(op, _, _) => vec![op],
+            Terminator::Ret(None) | Terminator::Jump(_) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Terminator::Ret(Some(op)) | Terminator::Branch(op, _, _) => vec![op],
+            Terminator::Ret(None) | Terminator::Jump(_) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::ast::{BinOp, SignKind, Signed, Value};
+
+    use super::{
+        BinAssign, BlockId, Call, Instruction, Move, Operand, Phi, SingleAssign, Terminator,
+        ValueId,
+    };
+
+    #[test]
+    fn display_instructions() {
+        let (a, b) = (Operand::Value(ValueId(0)), Operand::Value(ValueId(1)));
+        let five = Operand::Const(Value::Integer(SignKind::Signed(Signed::Int(5))));
+        let instrs = [
+            Instruction::BAssign(BinAssign {
+                dst: ValueId(2),
+                lop: a,
+                op: BinOp::Mul,
+                rop: five,
+            }),
+            Instruction::SAssign(SingleAssign {
+                dst: ValueId(3),
+                src: five,
+            }),
+            Instruction::Mov(Move {
+                dst: ValueId(0),
+                src: b,
+            }),
+            Instruction::Call(Call {
+                dst: Some(ValueId(4)),
+                func: "f".to_string(),
+                args: vec![a, five],
+            }),
+            Instruction::Call(Call {
+                dst: None,
+                func: "g".to_string(),
+                args: Vec::new(),
+            }),
+            Instruction::Phi(Phi {
+                dst: ValueId(5),
+                incoming: vec![(BlockId(1), a), (BlockId(2), b)],
+            }),
+        ];
+        let lines: Vec<String> = instrs.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "%2 = %0 * 5",
+                "%3 = 5",
+                "%0 <- %1",
+                "%4 = call f(%0, 5)",
+                "call g()",
+                "%5 = phi [bb1: %0, bb2: %1]",
+            ]
+        );
+
+        let terms = [
+            Terminator::Ret(Some(a)),
+            Terminator::Ret(None),
+            Terminator::Jump(BlockId(3)),
+            Terminator::Branch(b, BlockId(1), BlockId(2)),
+            Terminator::Unreachable,
+        ];
+        let lines: Vec<String> = terms.iter().map(|t| t.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "ret %0",
+                "ret",
+                "jump bb3",
+                "branch %1, bb1, bb2",
+                "unreachable"
+            ]
+        );
+    }
+}
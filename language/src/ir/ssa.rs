@@ -0,0 +1,653 @@
+//! Construction of static single assignment form, following Cytron et al.:
+//! phis go on the iterated dominance frontier of every variable assigned in
+//! more than one block, then a walk of the dominator tree renames each
+//! definition to a fresh value (`x`, `x1`, `x2`, ...).
+//!
+//! `destruct` goes the other way, turning phis back into moves on the
+//! incoming edges for the backend.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::{
+    analysis::dominators::DomTree,
+    frontend::{ast::Value, cfg::Cfg},
+};
+
+use super::{verify::debug_verify, BlockId, Instruction, Move, Operand, Phi, Terminator, ValueId};
+
+/// Where phis are placed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SsaKind {
+    /// On the whole iterated dominance frontier of a variable's definitions.
+    Minimal,
+    /// Like `Minimal`, but only where the variable is live on entry.
+    Pruned,
+}
+
+/// Rewrites `cfg` into SSA form. Blocks unreachable from the entry are left
+/// as they are.
+pub fn construct(cfg: &mut Cfg, kind: SsaKind) {
+    if cfg.blocks().is_empty() {
+        return;
+    }
+    let doms = DomTree::dominators(cfg);
+    let frontiers = doms.frontiers(cfg);
+
+    let mut defsites: BTreeMap<ValueId, BTreeSet<BlockId>> = BTreeMap::new();
+    let mut defcount: HashMap<ValueId, usize> = HashMap::new();
+    for param in cfg.params() {
+        defsites.entry(*param).or_default().insert(cfg.entry());
+        *defcount.entry(*param).or_default() += 1;
+    }
+    for block in doms.rpo() {
+        for instr in cfg.block(*block).instrs() {
+            if let Some(dst) = instr.result() {
+                defsites.entry(dst).or_default().insert(*block);
+                *defcount.entry(dst).or_default() += 1;
+            }
+        }
+    }
+
+    let live_in = match kind {
+        SsaKind::Minimal => None,
+        SsaKind::Pruned => Some(live_in(cfg, doms.rpo())),
+    };
+
+    // The original variable of every phi, per block, in instruction order.
+    let mut phis: Vec<Vec<ValueId>> = vec![Vec::new(); cfg.blocks().len()];
+    for (var, sites) in defsites.iter().filter(|(_, sites)| sites.len() > 1) {
+        let mut placed = BTreeSet::new();
+        let mut work: Vec<BlockId> = sites.iter().copied().collect();
+        while let Some(block) = work.pop() {
+            for front in frontiers[block.0].iter() {
+                let live = live_in
+                    .as_ref()
+                    .is_none_or(|live| live[front.0].contains(var));
+                if !live || !placed.insert(*front) {
+                    continue;
+                }
+                phis[front.0].push(*var);
+                if !sites.contains(front) {
+                    work.push(*front);
+                }
+            }
+        }
+    }
+    for (block, vars) in phis.iter().enumerate() {
+        let block = BlockId(block);
+        let preds = cfg.preds(block);
+        let mut instrs: Vec<Instruction> = vars
+            .iter()
+            .map(|var| {
+                let undef = undef(cfg, *var);
+                Instruction::Phi(Phi {
+                    dst: *var,
+                    incoming: preds.iter().map(|pred| (*pred, undef)).collect(),
+                })
+            })
+            .collect();
+        instrs.append(cfg.block_mut(block).instrs_mut());
+        *cfg.block_mut(block).instrs_mut() = instrs;
+    }
+
+    let mut renamer = Renamer {
+        names: cfg.values().iter().map(|v| v.name.clone()).collect(),
+        stacks: defcount
+            .iter()
+            .filter(|(_, count)| **count > 1)
+            .map(|(var, _)| (*var, Vec::new()))
+            .collect(),
+        kept: HashSet::new(),
+        counters: HashMap::new(),
+        phis,
+    };
+    for param in cfg.params().to_vec() {
+        if let Some(stack) = renamer.stacks.get_mut(&param) {
+            stack.push(param);
+            renamer.kept.insert(param);
+        }
+    }
+    renamer.rename(cfg, &doms, cfg.entry());
+    debug_verify(cfg, "SSA construction");
+}
+
+/// A value standing for a variable read before any assignment to it.
+fn undef(cfg: &Cfg, var: ValueId) -> Operand {
+    Value::zero(&cfg.value(var)._type).map_or(Operand::Value(var), Operand::Const)
+}
+
+struct Renamer {
+    names: HashSet<String>,
+    /// Current name of every variable with more than one definition.
+    stacks: HashMap<ValueId, Vec<ValueId>>,
+    /// Variables whose first definition already took the original id.
+    kept: HashSet<ValueId>,
+    counters: HashMap<ValueId, usize>,
+    phis: Vec<Vec<ValueId>>,
+}
+
+impl Renamer {
+    fn current(&self, cfg: &Cfg, var: ValueId) -> Option<Operand> {
+        let stack = self.stacks.get(&var)?;
+        Some(
+            stack
+                .last()
+                .map_or_else(|| undef(cfg, var), |v| Operand::Value(*v)),
+        )
+    }
+
+    fn rewrite(&self, cfg: &Cfg, op: &mut Operand) {
+        if let Operand::Value(var) = op {
+            if let Some(new) = self.current(cfg, *var) {
+                *op = new;
+            }
+        }
+    }
+
+    /// The first definition keeps the variable, later ones get `x1`, `x2`...
+    fn fresh(&mut self, cfg: &mut Cfg, var: ValueId) -> ValueId {
+        if self.kept.insert(var) {
+            return var;
+        }
+        let base = cfg.value(var).name.clone();
+        let counter = self.counters.entry(var).or_default();
+        let name = loop {
+            *counter += 1;
+            let name = format!("{}{}", base, counter);
+            if self.names.insert(name.clone()) {
+                break name;
+            }
+        };
+        let _type = cfg.value(var)._type.clone();
+        cfg.new_value(name, _type)
+    }
+
+    fn rename(&mut self, cfg: &mut Cfg, doms: &DomTree, block: BlockId) {
+        let mut pushed = Vec::new();
+        let mut instrs = std::mem::take(cfg.block_mut(block).instrs_mut());
+        for instr in instrs.iter_mut() {
+            if !matches!(instr, Instruction::Phi(_)) {
+                for op in instr.operands_mut() {
+                    self.rewrite(cfg, op);
+                }
+            }
+            if let Some(dst) = instr.result_mut() {
+                if self.stacks.contains_key(dst) {
+                    let var = *dst;
+                    *dst = self.fresh(cfg, var);
+                    self.stacks.get_mut(&var).unwrap().push(*dst);
+                    pushed.push(var);
+                }
+            }
+        }
+        *cfg.block_mut(block).instrs_mut() = instrs;
+        let mut term: Vec<Operand> = cfg
+            .block(block)
+            .term()
+            .operands()
+            .into_iter()
+            .copied()
+            .collect();
+        for op in term.iter_mut() {
+            self.rewrite(cfg, op);
+        }
+        for (op, new) in cfg
+            .block_mut(block)
+            .term_operands_mut()
+            .into_iter()
+            .zip(term)
+        {
+            *op = new;
+        }
+
+        for succ in cfg.succs(block) {
+            for (i, var) in self.phis[succ.0].clone().into_iter().enumerate() {
+                let op = self.current(cfg, var).unwrap_or(Operand::Value(var));
+                if let Instruction::Phi(phi) = &mut cfg.block_mut(succ).instrs_mut()[i] {
+                    for (pred, incoming) in phi.incoming.iter_mut() {
+                        if *pred == block {
+                            *incoming = op;
+                        }
+                    }
+                }
+            }
+        }
+
+        for child in doms.children(block) {
+            self.rename(cfg, doms, *child);
+        }
+        for var in pushed {
+            self.stacks.get_mut(&var).unwrap().pop();
+        }
+    }
+}
+
+/// Replaces every phi by moves at the end of its predecessors. Critical
+/// edges are split first so that the moves only run on their own edge,
+/// which also keeps a phi's old value alive where it is still read (the
+/// lost-copy problem).
+pub fn destruct(cfg: &mut Cfg) {
+    for block in 0..cfg.blocks().len() {
+        let block = BlockId(block);
+        let has_phis = cfg
+            .block(block)
+            .instrs()
+            .iter()
+            .any(|instr| matches!(instr, Instruction::Phi(_)));
+        if !has_phis {
+            continue;
+        }
+
+        let mut copies: BTreeMap<BlockId, Vec<(ValueId, Operand)>> = BTreeMap::new();
+        let preds = cfg.preds(block);
+        let instrs = std::mem::take(cfg.block_mut(block).instrs_mut());
+        let (phis, rest): (Vec<_>, Vec<_>) = instrs
+            .into_iter()
+            .partition(|instr| matches!(instr, Instruction::Phi(_)));
+        *cfg.block_mut(block).instrs_mut() = rest;
+        for phi in phis {
+            let Instruction::Phi(phi) = phi else {
+                unreachable!()
+            };
+            for (pred, op) in phi.incoming {
+                copies.entry(pred).or_default().push((phi.dst, op));
+            }
+        }
+
+        for (pred, copies) in copies {
+            let at = if preds.len() > 1 && cfg.succs(pred).len() > 1 {
+                split_edge(cfg, pred, block)
+            } else {
+                pred
+            };
+            for instr in sequentialise(cfg, copies) {
+                cfg.block_mut(at).add(instr);
+            }
+        }
+    }
+    debug_verify(cfg, "SSA destruction");
+}
+
+/// Puts a new block on the edge `from -> to` and returns it.
+fn split_edge(cfg: &mut Cfg, from: BlockId, to: BlockId) -> BlockId {
+    let mid = cfg.new_block();
+    cfg.set_term(mid, Terminator::Jump(to));
+    let retarget = |target: BlockId| if target == to { mid } else { target };
+    let term = match cfg.block(from).term().clone() {
+        Terminator::Jump(target) => Terminator::Jump(retarget(target)),
+        Terminator::Branch(cond, then, _else) => {
+            Terminator::Branch(cond, retarget(then), retarget(_else))
+        }
+        term @ (Terminator::Ret(_) | Terminator::Unreachable) => term,
+    };
+    cfg.set_term(from, term);
+    mid
+}
+
+/// Orders the parallel copies `dst <- src` as moves that give the same
+/// result when run one after another. A copy is emitted once nothing
+/// still reads its destination; a cycle such as a swap is broken by
+/// saving one destination to a temporary.
+fn sequentialise(cfg: &mut Cfg, copies: Vec<(ValueId, Operand)>) -> Vec<Instruction> {
+    let mut pending: Vec<(ValueId, Operand)> = copies
+        .into_iter()
+        .filter(|(dst, src)| *src != Operand::Value(*dst))
+        .collect();
+    let mut moves = Vec::new();
+    while !pending.is_empty() {
+        let free = pending
+            .iter()
+            .position(|(dst, _)| !pending.iter().any(|(_, src)| *src == Operand::Value(*dst)));
+        if let Some(i) = free {
+            let (dst, src) = pending.remove(i);
+            moves.push(Instruction::Mov(Move { dst, src }));
+            continue;
+        }
+        let saved = pending[0].0;
+        let tmp = temp(cfg, saved);
+        moves.push(Instruction::Mov(Move {
+            dst: tmp,
+            src: Operand::Value(saved),
+        }));
+        for (_, src) in pending.iter_mut() {
+            if *src == Operand::Value(saved) {
+                *src = Operand::Value(tmp);
+            }
+        }
+    }
+    moves
+}
+
+/// A new temporary of the same type as `like`.
+fn temp(cfg: &mut Cfg, like: ValueId) -> ValueId {
+    let _type = cfg.value(like)._type.clone();
+    cfg.new_temp(_type)
+}
+
+/// Values live on entry to each block, by the usual backward dataflow.
+fn live_in(cfg: &Cfg, rpo: &[BlockId]) -> Vec<HashSet<ValueId>> {
+    let len = cfg.blocks().len();
+    let mut uses = vec![HashSet::new(); len];
+    let mut defs = vec![HashSet::new(); len];
+    for block in rpo.iter() {
+        let (uses, defs) = (&mut uses[block.0], &mut defs[block.0]);
+        let data = cfg.block(*block);
+        let reads = data
+            .instrs()
+            .iter()
+            .map(|instr| (instr.operands(), instr.result()))
+            .chain(std::iter::once((data.term().operands(), None)));
+        for (operands, result) in reads {
+            for op in operands {
+                if let Operand::Value(v) = op {
+                    if !defs.contains(v) {
+                        uses.insert(*v);
+                    }
+                }
+            }
+            if let Some(dst) = result {
+                defs.insert(dst);
+            }
+        }
+    }
+
+    let mut live: Vec<HashSet<ValueId>> = uses.clone();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in rpo.iter().rev() {
+            let mut out = HashSet::new();
+            for succ in cfg.succs(*block) {
+                out.extend(live[succ.0].iter().copied());
+            }
+            for v in out {
+                if !defs[block.0].contains(&v) && live[block.0].insert(v) {
+                    changed = true;
+                }
+            }
+        }
+    }
+    live
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        frontend::{
+            ast::{BinOp, SignKind, Signed, Value},
+            cfg::Cfg,
+        },
+        ir::{BinAssign, BlockId, Instruction, Move, Operand, Phi, Terminator, ValueId},
+        types::designators::TypeInstance,
+    };
+
+    use super::{construct, destruct, sequentialise, SsaKind};
+    use crate::ir::text::run_checks;
+
+    fn int(n: i32) -> Operand {
+        Operand::Const(Value::Integer(SignKind::Signed(Signed::Int(n))))
+    }
+
+    fn mov(cfg: &mut Cfg, block: BlockId, dst: ValueId, src: Operand) {
+        cfg.block_mut(block)
+            .add(Instruction::Mov(Move { dst, src }));
+    }
+
+    fn phis(cfg: &Cfg, block: BlockId) -> usize {
+        cfg.block(block)
+            .instrs()
+            .iter()
+            .filter(|i| matches!(i, Instruction::Phi(_)))
+            .count()
+    }
+
+    /// `if (c) y = 1; else y = 2;` with `ret` reading `y` or not.
+    fn diamond(use_y: bool) -> (Cfg, [BlockId; 4]) {
+        let mut cfg = Cfg::new("f", TypeInstance::Int);
+        let c = cfg.add_param("c", TypeInstance::Int);
+        let y = cfg.new_value("y", TypeInstance::Int);
+        let blocks = [
+            cfg.new_block(),
+            cfg.new_block(),
+            cfg.new_block(),
+            cfg.new_block(),
+        ];
+        let [entry, then, _else, join] = blocks;
+        cfg.set_term(entry, Terminator::Branch(Operand::Value(c), then, _else));
+        mov(&mut cfg, then, y, int(1));
+        cfg.set_term(then, Terminator::Jump(join));
+        mov(&mut cfg, _else, y, int(2));
+        cfg.set_term(_else, Terminator::Jump(join));
+        let ret = if use_y { Operand::Value(y) } else { int(0) };
+        cfg.set_term(join, Terminator::Ret(Some(ret)));
+        (cfg, blocks)
+    }
+
+    #[test]
+    fn straight_line_definitions_are_renamed() {
+        let mut cfg = Cfg::new("f", TypeInstance::Int);
+        let x = cfg.new_value("x", TypeInstance::Int);
+        let y = cfg.new_value("y", TypeInstance::Int);
+        let z = cfg.new_value("z", TypeInstance::Int);
+        let entry = cfg.new_block();
+        mov(&mut cfg, entry, x, int(1));
+        let add = |dst| {
+            Instruction::BAssign(BinAssign {
+                dst,
+                lop: Operand::Value(x),
+                op: BinOp::Add,
+                rop: int(1),
+            })
+        };
+        cfg.block_mut(entry).add(add(y));
+        mov(&mut cfg, entry, x, int(2));
+        cfg.block_mut(entry).add(add(z));
+        cfg.set_term(entry, Terminator::Ret(Some(Operand::Value(z))));
+
+        construct(&mut cfg, SsaKind::Minimal);
+        let instrs = cfg.block(entry).instrs();
+        let names: Vec<&str> = instrs
+            .iter()
+            .map(|i| cfg.value(i.result().unwrap()).name.as_str())
+            .collect();
+        assert_eq!(names, ["x", "y", "x1", "z"]);
+        let x1 = instrs[2].result().unwrap();
+        assert_eq!(instrs[1].operands()[0], &Operand::Value(x));
+        assert_eq!(instrs[3].operands()[0], &Operand::Value(x1));
+    }
+
+    #[test]
+    fn join_gets_a_phi() {
+        let (mut cfg, [_, then, _else, join]) = diamond(true);
+        construct(&mut cfg, SsaKind::Minimal);
+
+        let then_y = cfg.block(then).instrs()[0].result().unwrap();
+        let else_y = cfg.block(_else).instrs()[0].result().unwrap();
+        assert_eq!(cfg.value(then_y).name, "y");
+        assert_eq!(cfg.value(else_y).name, "y1");
+        let Instruction::Phi(phi) = &cfg.block(join).instrs()[0] else {
+            panic!("expected a phi at the join")
+        };
+        assert_eq!(cfg.value(phi.dst).name, "y2");
+        assert_eq!(phi.incoming_from(then), Some(&Operand::Value(then_y)));
+        assert_eq!(phi.incoming_from(_else), Some(&Operand::Value(else_y)));
+        assert_eq!(
+            *cfg.block(join).term(),
+            Terminator::Ret(Some(Operand::Value(phi.dst)))
+        );
+    }
+
+    #[test]
+    fn pruned_form_skips_dead_phis() {
+        let (mut minimal, [.., join]) = diamond(false);
+        construct(&mut minimal, SsaKind::Minimal);
+        assert_eq!(phis(&minimal, join), 1);
+
+        let (mut pruned, [.., join]) = diamond(false);
+        construct(&mut pruned, SsaKind::Pruned);
+        assert_eq!(phis(&pruned, join), 0);
+    }
+
+    #[test]
+    fn loop_header_merges_entry_and_back_edge() {
+        // i = 10; while (i) i = i - 1; return i;
+        let mut cfg = Cfg::new("f", TypeInstance::Int);
+        let i = cfg.new_value("i", TypeInstance::Int);
+        let t = cfg.new_value("t", TypeInstance::Int);
+        let [entry, head, body, exit] = [
+            cfg.new_block(),
+            cfg.new_block(),
+            cfg.new_block(),
+            cfg.new_block(),
+        ];
+        mov(&mut cfg, entry, i, int(10));
+        cfg.set_term(entry, Terminator::Jump(head));
+        cfg.set_term(head, Terminator::Branch(Operand::Value(i), body, exit));
+        cfg.block_mut(body).add(Instruction::BAssign(BinAssign {
+            dst: t,
+            lop: Operand::Value(i),
+            op: BinOp::Sub,
+            rop: int(1),
+        }));
+        mov(&mut cfg, body, i, Operand::Value(t));
+        cfg.set_term(body, Terminator::Jump(head));
+        cfg.set_term(exit, Terminator::Ret(Some(Operand::Value(i))));
+
+        construct(&mut cfg, SsaKind::Pruned);
+        assert_eq!(phis(&cfg, entry), 0);
+        assert_eq!(phis(&cfg, body), 0);
+        assert_eq!(phis(&cfg, exit), 0);
+        let Instruction::Phi(phi) = &cfg.block(head).instrs()[0] else {
+            panic!("expected a phi in the loop header")
+        };
+        let head_i = Operand::Value(phi.dst);
+        let body_i = cfg.block(body).instrs()[1].result().unwrap();
+        assert_eq!(phi.incoming_from(entry), Some(&Operand::Value(i)));
+        assert_eq!(phi.incoming_from(body), Some(&Operand::Value(body_i)));
+        assert_eq!(cfg.block(body).instrs()[0].operands()[0], &head_i);
+        assert_eq!(
+            *cfg.block(head).term(),
+            Terminator::Branch(head_i, body, exit)
+        );
+        assert_eq!(*cfg.block(exit).term(), Terminator::Ret(Some(head_i)));
+    }
+
+    fn moves(cfg: &Cfg, block: BlockId) -> Vec<(ValueId, Operand)> {
+        cfg.block(block)
+            .instrs()
+            .iter()
+            .filter_map(|i| match i {
+                Instruction::Mov(mov) => Some((mov.dst, mov.src)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Runs `instrs`, all moves, over `env` and returns it.
+    fn run_moves(
+        instrs: &[Instruction],
+        mut env: HashMap<ValueId, Operand>,
+    ) -> HashMap<ValueId, Operand> {
+        for instr in instrs {
+            let Instruction::Mov(mov) = instr else {
+                panic!("expected only moves")
+            };
+            let val = match mov.src {
+                Operand::Value(v) => env[&v],
+                konst => konst,
+            };
+            env.insert(mov.dst, val);
+        }
+        env
+    }
+
+    #[test]
+    fn phis_become_moves_in_predecessors() {
+        let (mut cfg, [_, then, _else, join]) = diamond(true);
+        construct(&mut cfg, SsaKind::Minimal);
+        let Instruction::Phi(phi) = &cfg.block(join).instrs()[0] else {
+            panic!("expected a phi at the join")
+        };
+        let (y2, then_y, else_y) = (
+            phi.dst,
+            *phi.incoming_from(then).unwrap(),
+            *phi.incoming_from(_else).unwrap(),
+        );
+        destruct(&mut cfg);
+
+        assert_eq!(cfg.blocks().len(), 4);
+        assert_eq!(phis(&cfg, join), 0);
+        assert_eq!(moves(&cfg, then).last(), Some(&(y2, then_y)));
+        assert_eq!(moves(&cfg, _else).last(), Some(&(y2, else_y)));
+    }
+
+    #[test]
+    fn swaps_go_through_a_temporary() {
+        let mut cfg = Cfg::new("f", TypeInstance::Int);
+        let [a, b, c, d] = ["a", "b", "c", "d"].map(|name| cfg.new_value(name, TypeInstance::Int));
+        // a and b swap, c reads a before it is overwritten, d is a no-op.
+        let copies = vec![
+            (a, Operand::Value(b)),
+            (b, Operand::Value(a)),
+            (c, Operand::Value(a)),
+            (d, Operand::Value(d)),
+        ];
+        let moves = sequentialise(&mut cfg, copies);
+        assert_eq!(moves.len(), 4);
+        assert_eq!(cfg.value(ValueId(4)).name, "t0");
+        let env = HashMap::from([(a, int(1)), (b, int(2)), (c, int(3)), (d, int(4))]);
+        let env = run_moves(&moves, env);
+        assert_eq!(env[&a], int(2));
+        assert_eq!(env[&b], int(1));
+        assert_eq!(env[&c], int(1));
+        assert_eq!(env[&d], int(4));
+    }
+
+    #[test]
+    fn critical_back_edge_is_split() {
+        // x1 = phi [entry: 1, body: t]; t = x1 + 1; exit reads x1. A move
+        // at the end of `body` would clobber x1 on the way out of the loop,
+        // which is the lost-copy problem.
+        let mut cfg = Cfg::new("f", TypeInstance::Int);
+        let x1 = cfg.new_value("x1", TypeInstance::Int);
+        let t = cfg.new_value("t", TypeInstance::Int);
+        let [entry, body, exit] = [cfg.new_block(), cfg.new_block(), cfg.new_block()];
+        cfg.set_term(entry, Terminator::Jump(body));
+        cfg.block_mut(body).add(Instruction::Phi(Phi {
+            dst: x1,
+            incoming: vec![(entry, int(1)), (body, Operand::Value(t))],
+        }));
+        cfg.block_mut(body).add(Instruction::BAssign(BinAssign {
+            dst: t,
+            lop: Operand::Value(x1),
+            op: BinOp::Add,
+            rop: int(1),
+        }));
+        cfg.set_term(body, Terminator::Branch(Operand::Value(t), body, exit));
+        cfg.set_term(exit, Terminator::Ret(Some(Operand::Value(x1))));
+
+        destruct(&mut cfg);
+
+        assert_eq!(cfg.blocks().len(), 4);
+        let split = BlockId(3);
+        assert_eq!(
+            *cfg.block(body).term(),
+            Terminator::Branch(Operand::Value(t), split, exit)
+        );
+        assert_eq!(*cfg.block(split).term(), Terminator::Jump(body));
+        assert_eq!(moves(&cfg, entry), [(x1, int(1))]);
+        assert!(moves(&cfg, body).is_empty());
+        assert_eq!(phis(&cfg, body), 0);
+        assert_eq!(moves(&cfg, split), [(x1, Operand::Value(t))]);
+    }
+
+    #[test]
+    fn ir_files() {
+        run_checks(include_str!("../../tests/ir/ssa_construct.ir"), |cfg| {
+            construct(cfg, SsaKind::Pruned)
+        });
+        run_checks(include_str!("../../tests/ir/ssa_destruct.ir"), destruct);
+    }
+}
@@ -0,0 +1,1019 @@
+//! A stable textual form of `Cfg`s, so that middle-end tests can be written
+//! directly in IR:
+//!
+//! ```text
+//! fn max(int %a, int %b) -> int {
+//!     int %t0
+//! bb0:
+//!     %t0 = %a - %b
+//!     branch %t0, bb1, bb2
+//! bb1:
+//!     ret %a
+//! bb2:
+//!     ret %b
+//! }
+//! ```
+//!
+//! Every value other than a parameter is declared with its type before the
+//! first block. Constants carry their type, `int 5`, `ulong 7`. A `;` starts
+//! a comment running to the end of the line, which is where `check` looks
+//! for its directives.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use miette::{Diagnostic, SourceSpan};
+
+use crate::{
+    frontend::{
+        ast::{BinOp, SignKind, Signed, Unsigned, Value},
+        cfg::Cfg,
+        span::Span,
+    },
+    types::designators::TypeInstance,
+};
+
+use super::{
+    BinAssign, BlockId, Call, Instruction, Move, Operand, Phi, SingleAssign, Terminator, ValueId,
+};
+
+#[derive(Debug, Diagnostic)]
+pub enum IrError {
+    #[diagnostic(code(xlang::ir::unexpected_token))]
+    Unexpected {
+        expected: &'static str,
+        found: String,
+        #[label("expected {expected}")]
+        span: Span,
+    },
+    #[diagnostic(code(xlang::ir::undefined))]
+    Undefined {
+        what: &'static str,
+        name: String,
+        #[label("not defined in this function")]
+        span: Span,
+    },
+    #[diagnostic(code(xlang::ir::redefined))]
+    Redefined {
+        what: &'static str,
+        name: String,
+        #[label("defined again here")]
+        span: Span,
+    },
+    #[diagnostic(code(xlang::ir::out_of_range))]
+    OutOfRange {
+        literal: i128,
+        _type: &'static str,
+        #[label("does not fit")]
+        span: Span,
+    },
+}
+
+impl Display for IrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IrError::Unexpected {
+                expected, found, ..
+            } => write!(f, "expected {expected}, found {found}"),
+            IrError::Undefined { what, name, .. } => write!(f, "undefined {what} `{name}`"),
+            IrError::Redefined { what, name, .. } => write!(f, "{what} `{name}` defined twice"),
+            IrError::OutOfRange { literal, _type, .. } => {
+                write!(f, "`{literal}` does not fit in `{_type}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for IrError {}
+
+/// Prints every function, separated by blank lines.
+pub fn print(cfgs: &[Cfg]) -> String {
+    cfgs.iter()
+        .map(print_function)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn print_function(cfg: &Cfg) -> String {
+    listing(cfg).text
+}
+
+/// The text of `print_function` with the span of every value declaration
+/// and of every line in each block, so diagnostics can point into it.
+pub struct Listing {
+    pub text: String,
+    values: Vec<SourceSpan>,
+    /// A block's label followed by its instructions and terminator.
+    blocks: Vec<Vec<SourceSpan>>,
+}
+
+impl Listing {
+    /// Where `value` is declared, in the signature for parameters.
+    pub fn value(&self, value: ValueId) -> SourceSpan {
+        self.values[value.0]
+    }
+
+    /// The label opening `block`.
+    pub fn block(&self, block: BlockId) -> SourceSpan {
+        self.blocks[block.0][0]
+    }
+
+    /// Instruction `index` of `block`, its terminator one past the last.
+    pub fn line(&self, block: BlockId, index: usize) -> SourceSpan {
+        self.blocks[block.0][index + 1]
+    }
+}
+
+pub fn listing(cfg: &Cfg) -> Listing {
+    let printer = Printer {
+        names: unique_names(cfg),
+    };
+    let mut text = String::new();
+    let mut push = |line: String, indent: &str| {
+        text.push_str(indent);
+        let span = SourceSpan::new(text.len().into(), line.len());
+        text.push_str(&line);
+        span
+    };
+
+    let mut values = vec![SourceSpan::from(0); cfg.values().len()];
+    push(format!("fn {}(", cfg.name()), "");
+    for (i, param) in cfg.params().iter().enumerate() {
+        let decl = format!(
+            "{} {}",
+            type_name(&cfg.value(*param)._type),
+            printer.value(*param)
+        );
+        values[param.0] = push(decl, if i == 0 { "" } else { ", " });
+    }
+    push(format!(") -> {} {{", type_name(cfg.ret_type())), "");
+    for (i, value) in cfg.values().iter().enumerate() {
+        if !cfg.params().contains(&ValueId(i)) {
+            let decl = format!("{} {}", type_name(&value._type), printer.value(ValueId(i)));
+            values[i] = push(decl, "\n    ");
+        }
+    }
+    let mut blocks = Vec::new();
+    for block in cfg.blocks() {
+        let mut lines = vec![push(format!("{}:", block.id()), "\n")];
+        for instr in block.instrs() {
+            lines.push(push(printer.instr(instr), "\n    "));
+        }
+        lines.push(push(printer.term(block.term()), "\n    "));
+        blocks.push(lines);
+    }
+    push("}\n".to_string(), "\n");
+    Listing {
+        text,
+        values,
+        blocks,
+    }
+}
+
+/// A single instruction as `print` writes it, for diagnostics.
+pub fn print_instruction(cfg: &Cfg, instr: &Instruction) -> String {
+    Printer {
+        names: unique_names(cfg),
+    }
+    .instr(instr)
+}
+
+pub fn print_terminator(cfg: &Cfg, term: &Terminator) -> String {
+    Printer {
+        names: unique_names(cfg),
+    }
+    .term(term)
+}
+
+pub fn print_value(cfg: &Cfg, id: ValueId) -> String {
+    Printer {
+        names: unique_names(cfg),
+    }
+    .value(id)
+}
+
+/// Value names, made unique by suffixing `.1`, `.2`... to later duplicates.
+fn unique_names(cfg: &Cfg) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut names: Vec<Option<String>> = cfg
+        .values()
+        .iter()
+        .map(|v| seen.insert(v.name.clone()).then(|| v.name.clone()))
+        .collect();
+    for (value, name) in cfg.values().iter().zip(names.iter_mut()) {
+        if name.is_none() {
+            let unique = (1..)
+                .map(|n| format!("{}.{}", value.name, n))
+                .find(|candidate| !seen.contains(candidate))
+                .unwrap();
+            seen.insert(unique.clone());
+            *name = Some(unique);
+        }
+    }
+    names.into_iter().map(Option::unwrap).collect()
+}
+
+fn type_name(_type: &TypeInstance) -> String {
+    match _type {
+        TypeInstance::Char => "char".to_string(),
+        TypeInstance::Short => "short".to_string(),
+        TypeInstance::Int => "int".to_string(),
+        TypeInstance::Long => "long".to_string(),
+        TypeInstance::LongLong => "longlong".to_string(),
+        TypeInstance::SChar => "schar".to_string(),
+        TypeInstance::UChar => "uchar".to_string(),
+        TypeInstance::UShort => "ushort".to_string(),
+        TypeInstance::UInt => "uint".to_string(),
+        TypeInstance::ULong => "ulong".to_string(),
+        TypeInstance::ULongLong => "ulonglong".to_string(),
+        TypeInstance::Void => "void".to_string(),
+        TypeInstance::Ptr(to) => format!("{}*", type_name(to)),
+        TypeInstance::Func(ret, ..) => format!("{}()", type_name(ret)),
+    }
+}
+
+fn const_type_name(val: &Value) -> &'static str {
+    match val {
+        Value::Integer(SignKind::Signed(Signed::Char(_))) => "char",
+        Value::Integer(SignKind::Signed(Signed::Short(_))) => "short",
+        Value::Integer(SignKind::Signed(Signed::Int(_))) => "int",
+        Value::Integer(SignKind::Signed(Signed::Long(_))) => "long",
+        Value::Integer(SignKind::Signed(Signed::LongLong(_))) => "longlong",
+        Value::Integer(SignKind::Unsigned(Unsigned::Char(_))) => "uchar",
+        Value::Integer(SignKind::Unsigned(Unsigned::Short(_))) => "ushort",
+        Value::Integer(SignKind::Unsigned(Unsigned::Int(_))) => "uint",
+        Value::Integer(SignKind::Unsigned(Unsigned::Long(_))) => "ulong",
+        Value::Integer(SignKind::Unsigned(Unsigned::LongLong(_))) => "ulonglong",
+    }
+}
+
+/// The constant `n` of the integer type named `_type`, `None` if it does
+/// not fit.
+fn constant(_type: &str, n: i128) -> Option<Value> {
+    let int = match _type {
+        "char" | "schar" => SignKind::Signed(Signed::Char(n.try_into().ok()?)),
+        "short" => SignKind::Signed(Signed::Short(n.try_into().ok()?)),
+        "int" => SignKind::Signed(Signed::Int(n.try_into().ok()?)),
+        "long" => SignKind::Signed(Signed::Long(n.try_into().ok()?)),
+        "longlong" => SignKind::Signed(Signed::LongLong(n.try_into().ok()?)),
+        "uchar" => SignKind::Unsigned(Unsigned::Char(n.try_into().ok()?)),
+        "ushort" => SignKind::Unsigned(Unsigned::Short(n.try_into().ok()?)),
+        "uint" => SignKind::Unsigned(Unsigned::Int(n.try_into().ok()?)),
+        "ulong" => SignKind::Unsigned(Unsigned::Long(n.try_into().ok()?)),
+        "ulonglong" => SignKind::Unsigned(Unsigned::LongLong(n.try_into().ok()?)),
+        _ => return None,
+    };
+    Some(Value::Integer(int))
+}
+
+const CONST_TYPES: [&str; 11] = [
+    "char",
+    "schar",
+    "short",
+    "int",
+    "long",
+    "longlong",
+    "uchar",
+    "ushort",
+    "uint",
+    "ulong",
+    "ulonglong",
+];
+
+struct Printer {
+    names: Vec<String>,
+}
+
+impl Printer {
+    fn value(&self, id: ValueId) -> String {
+        format!("%{}", self.names[id.0])
+    }
+
+    fn operand(&self, op: &Operand) -> String {
+        match op {
+            Operand::Const(val) => format!("{} {}", const_type_name(val), val),
+            Operand::Value(id) => self.value(*id),
+        }
+    }
+
+    fn instr(&self, instr: &Instruction) -> String {
+        match instr {
+            Instruction::BAssign(bin) => format!(
+                "{} = {} {} {}",
+                self.value(bin.dst),
+                self.operand(&bin.lop),
+                bin.op,
+                self.operand(&bin.rop)
+            ),
+            Instruction::SAssign(single) => {
+                format!("{} = {}", self.value(single.dst), self.operand(&single.src))
+            }
+            Instruction::Mov(mov) => {
+                format!("{} <- {}", self.value(mov.dst), self.operand(&mov.src))
+            }
+            Instruction::Call(call) => {
+                let args: Vec<String> = call.args.iter().map(|a| self.operand(a)).collect();
+                let call_text = format!("call {}({})", call.func, args.join(", "));
+                match call.dst {
+                    Some(dst) => format!("{} = {}", self.value(dst), call_text),
+                    None => call_text,
+                }
+            }
+            Instruction::Phi(phi) => {
+                let incoming: Vec<String> = phi
+                    .incoming
+                    .iter()
+                    .map(|(pred, op)| format!("{}: {}", pred, self.operand(op)))
+                    .collect();
+                format!("{} = phi [{}]", self.value(phi.dst), incoming.join(", "))
+            }
+        }
+    }
+
+    fn term(&self, term: &Terminator) -> String {
+        match term {
+            Terminator::Ret(Some(op)) => format!("ret {}", self.operand(op)),
+            Terminator::Ret(None) => "ret".to_string(),
+            Terminator::Jump(target) => format!("jump {target}"),
+            Terminator::Branch(cond, then, _else) => {
+                format!("branch {}, {}, {}", self.operand(cond), then, _else)
+            }
+            Terminator::Unreachable => "unreachable".to_string(),
+        }
+    }
+}
+
+/// Parses functions in the form written by `print`. Block labels may be
+/// any identifier; blocks are numbered in the order they are defined.
+pub fn parse(text: &str) -> Result<Vec<Cfg>, IrError> {
+    let mut parser = IrParser {
+        tokens: lex(text)?,
+        pos: 0,
+    };
+    let mut cfgs = Vec::new();
+    while parser.peek().0 != Tok::Eof {
+        cfgs.push(parser.function()?);
+    }
+    Ok(cfgs)
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Tok<'a> {
+    Ident(&'a str),
+    Value(&'a str),
+    Int(i128),
+    Punct(&'static str),
+    Eof,
+}
+
+impl Display for Tok<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tok::Ident(name) => write!(f, "`{name}`"),
+            Tok::Value(name) => write!(f, "`%{name}`"),
+            Tok::Int(n) => write!(f, "`{n}`"),
+            Tok::Punct(p) => write!(f, "`{p}`"),
+            Tok::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+const PUNCTS: [&str; 15] = [
+    "<-", "->", "(", ")", "{", "}", "[", "]", ",", ":", "=", "+", "-", "*", "/",
+];
+
+fn is_name_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'.'
+}
+
+fn lex(text: &str) -> Result<Vec<(Tok<'_>, Span)>, IrError> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let start = pos;
+        let c = bytes[pos];
+        let tok = if c.is_ascii_whitespace() {
+            pos += 1;
+            continue;
+        } else if c == b';' {
+            while pos < bytes.len() && bytes[pos] != b'\n' {
+                pos += 1;
+            }
+            continue;
+        } else if c == b'%' {
+            pos += 1;
+            while pos < bytes.len() && is_name_char(bytes[pos]) {
+                pos += 1;
+            }
+            Tok::Value(&text[start + 1..pos])
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            while pos < bytes.len() && is_name_char(bytes[pos]) {
+                pos += 1;
+            }
+            Tok::Ident(&text[start..pos])
+        } else if c.is_ascii_digit()
+            || (c == b'-' && bytes.get(pos + 1).is_some_and(u8::is_ascii_digit))
+        {
+            pos += 1;
+            while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                pos += 1;
+            }
+            let span = Span::new(0, start, pos);
+            let n = text[start..pos].parse().map_err(|_| IrError::OutOfRange {
+                literal: if c == b'-' { i128::MIN } else { i128::MAX },
+                _type: "ulonglong",
+                span,
+            })?;
+            Tok::Int(n)
+        } else if let Some(p) = PUNCTS.iter().find(|p| text[pos..].starts_with(**p)) {
+            pos += p.len();
+            Tok::Punct(p)
+        } else {
+            let found = text[pos..].chars().next().unwrap();
+            return Err(IrError::Unexpected {
+                expected: "an IR token",
+                found: format!("`{found}`"),
+                span: Span::new(0, start, start + found.len_utf8()),
+            });
+        };
+        tokens.push((tok, Span::new(0, start, pos)));
+    }
+    tokens.push((Tok::Eof, Span::new(0, text.len(), text.len())));
+    Ok(tokens)
+}
+
+struct IrParser<'a> {
+    tokens: Vec<(Tok<'a>, Span)>,
+    pos: usize,
+}
+
+/// Per-function state: names in scope and blocks seen so far. Blocks are
+/// numbered by first mention until the function is done, then renumbered
+/// in order of definition.
+struct Function<'a> {
+    cfg: Cfg,
+    values: HashMap<&'a str, ValueId>,
+    labels: HashMap<&'a str, (BlockId, Span)>,
+    defined: Vec<(BlockId, Vec<Instruction>, Terminator)>,
+}
+
+impl<'a> Function<'a> {
+    fn declare(&mut self, name: &'a str, _type: TypeInstance, span: Span) -> Result<(), IrError> {
+        if self.values.contains_key(name) {
+            return Err(IrError::Redefined {
+                what: "value",
+                name: name.to_string(),
+                span,
+            });
+        }
+        let id = self.cfg.new_value(name, _type);
+        self.values.insert(name, id);
+        Ok(())
+    }
+
+    fn label(&mut self, name: &'a str, span: Span) -> BlockId {
+        let next = BlockId(self.labels.len());
+        self.labels.entry(name).or_insert((next, span)).0
+    }
+}
+
+impl<'a> IrParser<'a> {
+    fn peek(&self) -> (Tok<'a>, Span) {
+        self.tokens[self.pos]
+    }
+
+    fn peek_at(&self, ahead: usize) -> Tok<'a> {
+        self.tokens[(self.pos + ahead).min(self.tokens.len() - 1)].0
+    }
+
+    fn unexpected<T>(&self, expected: &'static str) -> Result<T, IrError> {
+        let (tok, span) = self.peek();
+        Err(IrError::Unexpected {
+            expected,
+            found: tok.to_string(),
+            span,
+        })
+    }
+
+    fn eat(&mut self, punct: &'static str) -> bool {
+        if self.peek().0 == Tok::Punct(punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &'static str, expected: &'static str) -> Result<Span, IrError> {
+        let span = self.peek().1;
+        if self.eat(punct) {
+            Ok(span)
+        } else {
+            self.unexpected(expected)
+        }
+    }
+
+    fn ident(&mut self, expected: &'static str) -> Result<(&'a str, Span), IrError> {
+        match self.peek() {
+            (Tok::Ident(name), span) => {
+                self.pos += 1;
+                Ok((name, span))
+            }
+            _ => self.unexpected(expected),
+        }
+    }
+
+    fn keyword(&mut self, word: &'static str, expected: &'static str) -> Result<(), IrError> {
+        match self.peek().0 {
+            Tok::Ident(name) if name == word => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => self.unexpected(expected),
+        }
+    }
+
+    fn function(&mut self) -> Result<Cfg, IrError> {
+        self.keyword("fn", "`fn`")?;
+        let (name, _) = self.ident("a function name")?;
+        self.expect("(", "`(`")?;
+        let mut params = Vec::new();
+        if !self.eat(")") {
+            loop {
+                let _type = self.type_name()?;
+                let (param, span) = self.value_name()?;
+                params.push((_type, param, span));
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",", "`,` or `)`")?;
+            }
+        }
+        self.expect("->", "`->`")?;
+        let ret = self.type_name()?;
+        self.expect("{", "`{`")?;
+
+        let mut func = Function {
+            cfg: Cfg::new(name, ret),
+            values: HashMap::new(),
+            labels: HashMap::new(),
+            defined: Vec::new(),
+        };
+        for (_type, param, span) in params {
+            if func.values.contains_key(param) {
+                return Err(IrError::Redefined {
+                    what: "value",
+                    name: param.to_string(),
+                    span,
+                });
+            }
+            let id = func.cfg.add_param(param, _type);
+            func.values.insert(param, id);
+        }
+        while matches!(self.peek().0, Tok::Ident(_))
+            && matches!(
+                self.peek_at(1),
+                Tok::Value(_) | Tok::Punct("*") | Tok::Punct("(")
+            )
+        {
+            let _type = self.type_name()?;
+            let (value, span) = self.value_name()?;
+            func.declare(value, _type, span)?;
+        }
+        while !self.eat("}") {
+            self.block(&mut func)?;
+        }
+        func.finish()
+    }
+
+    fn block(&mut self, func: &mut Function<'a>) -> Result<(), IrError> {
+        let (label, span) = self.ident("a block label or `}`")?;
+        self.expect(":", "`:`")?;
+        let id = func.label(label, span);
+        if func.defined.iter().any(|(defined, ..)| *defined == id) {
+            return Err(IrError::Redefined {
+                what: "block",
+                name: label.to_string(),
+                span,
+            });
+        }
+        let mut instrs = Vec::new();
+        loop {
+            match self.peek().0 {
+                Tok::Ident("ret")
+                | Tok::Ident("jump")
+                | Tok::Ident("branch")
+                | Tok::Ident("unreachable") => {
+                    let term = self.term(func)?;
+                    func.defined.push((id, instrs, term));
+                    return Ok(());
+                }
+                Tok::Ident("call") => {
+                    self.pos += 1;
+                    instrs.push(Instruction::Call(self.call(func, None)?));
+                }
+                Tok::Value(_) => instrs.push(self.assignment(func)?),
+                _ => return self.unexpected("an instruction or terminator"),
+            }
+        }
+    }
+
+    fn assignment(&mut self, func: &mut Function<'a>) -> Result<Instruction, IrError> {
+        let dst = self.value(func)?;
+        if self.eat("<-") {
+            let src = self.operand(func)?;
+            return Ok(Instruction::Mov(Move { dst, src }));
+        }
+        self.expect("=", "`=` or `<-`")?;
+        match self.peek().0 {
+            Tok::Ident("call") => {
+                self.pos += 1;
+                return Ok(Instruction::Call(self.call(func, Some(dst))?));
+            }
+            Tok::Ident("phi") => {
+                self.pos += 1;
+                return Ok(Instruction::Phi(self.phi(func, dst)?));
+            }
+            _ => (),
+        }
+        let lop = self.operand(func)?;
+        let op = match self.peek().0 {
+            Tok::Punct("+") => BinOp::Add,
+            Tok::Punct("-") => BinOp::Sub,
+            Tok::Punct("*") => BinOp::Mul,
+            Tok::Punct("/") => BinOp::Div,
+            _ => return Ok(Instruction::SAssign(SingleAssign { dst, src: lop })),
+        };
+        self.pos += 1;
+        let rop = self.operand(func)?;
+        Ok(Instruction::BAssign(BinAssign { dst, lop, op, rop }))
+    }
+
+    fn call(&mut self, func: &mut Function<'a>, dst: Option<ValueId>) -> Result<Call, IrError> {
+        let (name, _) = self.ident("a function name")?;
+        self.expect("(", "`(`")?;
+        let mut args = Vec::new();
+        if !self.eat(")") {
+            loop {
+                args.push(self.operand(func)?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",", "`,` or `)`")?;
+            }
+        }
+        Ok(Call {
+            dst,
+            func: name.to_string(),
+            args,
+        })
+    }
+
+    fn phi(&mut self, func: &mut Function<'a>, dst: ValueId) -> Result<Phi, IrError> {
+        self.expect("[", "`[`")?;
+        let mut incoming = Vec::new();
+        if !self.eat("]") {
+            loop {
+                let (label, span) = self.ident("a block label")?;
+                let pred = func.label(label, span);
+                self.expect(":", "`:`")?;
+                incoming.push((pred, self.operand(func)?));
+                if self.eat("]") {
+                    break;
+                }
+                self.expect(",", "`,` or `]`")?;
+            }
+        }
+        Ok(Phi { dst, incoming })
+    }
+
+    fn term(&mut self, func: &mut Function<'a>) -> Result<Terminator, IrError> {
+        let (word, _) = self.ident("a terminator")?;
+        Ok(match word {
+            "ret" => {
+                let has_operand = matches!(
+                    (self.peek().0, self.peek_at(1)),
+                    (Tok::Value(_), _) | (Tok::Ident(_), Tok::Int(_))
+                );
+                if has_operand {
+                    Terminator::Ret(Some(self.operand(func)?))
+                } else {
+                    Terminator::Ret(None)
+                }
+            }
+            "jump" => Terminator::Jump(self.target(func)?),
+            "branch" => {
+                let cond = self.operand(func)?;
+                self.expect(",", "`,`")?;
+                let then = self.target(func)?;
+                self.expect(",", "`,`")?;
+                let _else = self.target(func)?;
+                Terminator::Branch(cond, then, _else)
+            }
+            _ => Terminator::Unreachable,
+        })
+    }
+
+    fn target(&mut self, func: &mut Function<'a>) -> Result<BlockId, IrError> {
+        let (label, span) = self.ident("a block label")?;
+        Ok(func.label(label, span))
+    }
+
+    fn operand(&mut self, func: &Function<'a>) -> Result<Operand, IrError> {
+        if let Tok::Value(_) = self.peek().0 {
+            return Ok(Operand::Value(self.value(func)?));
+        }
+        let _type = match self.peek().0 {
+            Tok::Ident(name) => CONST_TYPES.iter().find(|t| **t == name).copied(),
+            _ => None,
+        };
+        let Some(_type) = _type else {
+            return self.unexpected("a value or typed constant");
+        };
+        self.pos += 1;
+        match self.peek() {
+            (Tok::Int(n), span) => {
+                self.pos += 1;
+                constant(_type, n)
+                    .map(Operand::Const)
+                    .ok_or(IrError::OutOfRange {
+                        literal: n,
+                        _type,
+                        span,
+                    })
+            }
+            _ => self.unexpected("an integer"),
+        }
+    }
+
+    fn value_name(&mut self) -> Result<(&'a str, Span), IrError> {
+        match self.peek() {
+            (Tok::Value(name), span) => {
+                self.pos += 1;
+                Ok((name, span))
+            }
+            _ => self.unexpected("a value"),
+        }
+    }
+
+    fn value(&mut self, func: &Function<'a>) -> Result<ValueId, IrError> {
+        let (name, span) = self.value_name()?;
+        func.values
+            .get(name)
+            .copied()
+            .ok_or_else(|| IrError::Undefined {
+                what: "value",
+                name: name.to_string(),
+                span,
+            })
+    }
+
+    fn type_name(&mut self) -> Result<TypeInstance, IrError> {
+        let mut _type = match self.peek().0 {
+            Tok::Ident("char") => TypeInstance::Char,
+            Tok::Ident("short") => TypeInstance::Short,
+            Tok::Ident("int") => TypeInstance::Int,
+            Tok::Ident("long") => TypeInstance::Long,
+            Tok::Ident("longlong") => TypeInstance::LongLong,
+            Tok::Ident("schar") => TypeInstance::SChar,
+            Tok::Ident("uchar") => TypeInstance::UChar,
+            Tok::Ident("ushort") => TypeInstance::UShort,
+            Tok::Ident("uint") => TypeInstance::UInt,
+            Tok::Ident("ulong") => TypeInstance::ULong,
+            Tok::Ident("ulonglong") => TypeInstance::ULongLong,
+            Tok::Ident("void") => TypeInstance::Void,
+            _ => return self.unexpected("a type"),
+        };
+        self.pos += 1;
+        loop {
+            if self.eat("*") {
+                _type = TypeInstance::Ptr(Box::new(_type));
+            } else if self.eat("(") {
+                self.expect(")", "`)`")?;
+                _type = TypeInstance::Func(Box::new(_type), String::new(), None);
+            } else {
+                return Ok(_type);
+            }
+        }
+    }
+}
+
+impl Function<'_> {
+    /// Renumbers the blocks in definition order and builds the graph.
+    fn finish(self) -> Result<Cfg, IrError> {
+        let Function {
+            mut cfg,
+            labels,
+            defined,
+            ..
+        } = self;
+        let mut order = vec![None; labels.len()];
+        for (i, (id, ..)) in defined.iter().enumerate() {
+            order[id.0] = Some(BlockId(i));
+        }
+        let mut undefined: Vec<(&str, Span)> = labels
+            .iter()
+            .filter(|(_, (id, _))| order[id.0].is_none())
+            .map(|(name, (_, span))| (*name, *span))
+            .collect();
+        undefined.sort_by_key(|(_, span)| span.start);
+        if let Some((name, span)) = undefined.first() {
+            return Err(IrError::Undefined {
+                what: "block",
+                name: name.to_string(),
+                span: *span,
+            });
+        }
+        let renumber = |id: BlockId| order[id.0].unwrap();
+
+        for _ in defined.iter() {
+            cfg.new_block();
+        }
+        for (i, (_, instrs, term)) in defined.into_iter().enumerate() {
+            let block = BlockId(i);
+            for mut instr in instrs {
+                if let Instruction::Phi(phi) = &mut instr {
+                    for (pred, _) in phi.incoming.iter_mut() {
+                        *pred = renumber(*pred);
+                    }
+                }
+                cfg.block_mut(block).add(instr);
+            }
+            let term = match term {
+                Terminator::Jump(target) => Terminator::Jump(renumber(target)),
+                Terminator::Branch(cond, then, _else) => {
+                    Terminator::Branch(cond, renumber(then), renumber(_else))
+                }
+                term @ (Terminator::Ret(_) | Terminator::Unreachable) => term,
+            };
+            cfg.set_term(block, term);
+        }
+        Ok(cfg)
+    }
+}
+
+/// Matches `output` against the `; CHECK` directives in `checks`, in the
+/// manner of LLVM's FileCheck: `CHECK:` finds its text on a line after
+/// the previous match, `CHECK-NEXT:` on the line right after it, and
+/// `CHECK-NOT:` must not occur between the surrounding matches.
+pub fn check(output: &str, checks: &str) -> Result<(), String> {
+    let lines: Vec<&str> = output.lines().collect();
+    let mut cursor = 0;
+    let mut nots: Vec<&str> = Vec::new();
+    let forbid = |nots: &mut Vec<&str>, range: &[&str]| -> Result<(), String> {
+        for not in nots.drain(..) {
+            if let Some(line) = range.iter().find(|line| line.contains(not)) {
+                return Err(format!("CHECK-NOT: `{not}` found in `{line}`"));
+            }
+        }
+        Ok(())
+    };
+    for directive in checks.lines().filter_map(|l| l.trim().strip_prefix(';')) {
+        let directive = directive.trim();
+        if let Some(pattern) = directive.strip_prefix("CHECK-NOT:") {
+            nots.push(pattern.trim());
+        } else if let Some(pattern) = directive.strip_prefix("CHECK-NEXT:") {
+            let pattern = pattern.trim();
+            match lines.get(cursor) {
+                Some(line) if line.contains(pattern) => cursor += 1,
+                Some(line) => return Err(format!("CHECK-NEXT: `{pattern}` not found in `{line}`")),
+                None => return Err(format!("CHECK-NEXT: `{pattern}` past end of output")),
+            }
+            forbid(&mut nots, &[])?;
+        } else if let Some(pattern) = directive.strip_prefix("CHECK:") {
+            let pattern = pattern.trim();
+            let Some(found) = lines[cursor..].iter().position(|l| l.contains(pattern)) else {
+                return Err(format!(
+                    "CHECK: `{pattern}` not found after line {cursor} of\n{output}"
+                ));
+            };
+            forbid(&mut nots, &lines[cursor..cursor + found])?;
+            cursor += found + 1;
+        }
+    }
+    forbid(&mut nots, &lines[cursor..])
+}
+
+/// Parses `text`, runs `pass` over every function and checks the printed
+/// result against the directives in `text`.
+#[cfg(test)]
+pub(crate) fn run_checks(text: &str, mut pass: impl FnMut(&mut Cfg)) {
+    let mut cfgs = match parse(text) {
+        Ok(cfgs) => cfgs,
+        Err(e) => panic!("{e}"),
+    };
+    for cfg in cfgs.iter_mut() {
+        pass(cfg);
+    }
+    if let Err(e) = check(&print(&cfgs), text) {
+        panic!("{e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        frontend::{cfg::Cfg, parser, symboltable::SymbolMap},
+        types::layout::TargetLayout,
+    };
+
+    use super::{check, parse, print, IrError};
+
+    const EVERYTHING: &str = "\
+fn f(int %a, long* %p) -> int {
+    int %t0
+    int %x
+    int %x.1
+    char %c
+    int %r
+bb0:
+    %t0 = %a * int -3
+    %x <- %t0
+    %x.1 = %x / int 2
+    %c = uchar 255
+    %r = call g(%x, longlong 1)
+    call h()
+    branch %x.1, bb1, bb2
+bb1:
+    jump bb2
+bb2:
+    %r = phi [bb0: %t0, bb1: %x.1]
+    ret %x
+}
+
+fn h() -> void {
+bb0:
+    ret
+bb1:
+    unreachable
+}
+";
+
+    #[test]
+    fn print_parse_round_trip() {
+        let text = EVERYTHING;
+        let cfgs = parse(text).unwrap();
+        assert_eq!(cfgs.len(), 2);
+        assert_eq!(cfgs[0].params().len(), 2);
+        assert_eq!(cfgs[0].preds(super::BlockId(2)).len(), 2);
+        assert_eq!(print(&cfgs), text);
+    }
+
+    #[test]
+    fn lowered_source_round_trips() {
+        let source = parser::parse("int main() { int a = 5 + 10 * 2; return a; }", 0).unwrap();
+        let mut map = SymbolMap::new();
+        map.fill_from_source(&source.0).unwrap();
+        let cfgs = Cfg::fill_from_source(&source, &map, &TargetLayout::default());
+        let text = print(&cfgs);
+        assert!(text.contains("    %a <- %t"));
+        assert_eq!(print(&parse(&text).unwrap()), text);
+    }
+
+    #[test]
+    fn labels_are_numbered_by_definition() {
+        let text = "fn f() -> void {\nentry:\n jump exit\nmid:\n jump exit\nexit: ret\n}";
+        let cfgs = parse(text).unwrap();
+        check(
+            &print(&cfgs),
+            "; CHECK: bb0:\n; CHECK-NEXT: jump bb2\n; CHECK: bb1:\n; CHECK-NOT: ret\n; CHECK: bb2:",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn parse_errors() {
+        let undefined = parse("fn f() -> int {\nbb0:\n    ret %y\n}").unwrap_err();
+        assert!(
+            matches!(&undefined, IrError::Undefined { what: "value", span, .. } if span.start == 29)
+        );
+        assert_eq!(undefined.to_string(), "undefined value `y`");
+
+        let label = parse("fn f() -> int {\nbb0:\n    jump nowhere\n}").unwrap_err();
+        assert_eq!(label.to_string(), "undefined block `nowhere`");
+
+        let range = parse("fn f() -> char {\nbb0:\n    ret char 300\n}").unwrap_err();
+        assert_eq!(range.to_string(), "`300` does not fit in `char`");
+
+        let twice = parse("fn f() -> int {\nbb0:\n ret\nbb0:\n ret\n}").unwrap_err();
+        assert_eq!(twice.to_string(), "block `bb0` defined twice");
+
+        let unexpected = parse("fn f() -> int {\nbb0:\n    %y\n}").unwrap_err();
+        assert_eq!(unexpected.to_string(), "undefined value `y`");
+        let unexpected = parse("fn f(int %y) -> int {\nbb0:\n    %y ret\n}").unwrap_err();
+        assert_eq!(unexpected.to_string(), "expected `=` or `<-`, found `ret`");
+    }
+
+    #[test]
+    fn check_directives() {
+        let output = "a\nb\nc\n";
+        check(output, "; CHECK: a\n; CHECK-NEXT: b\n; CHECK-NOT: x").unwrap();
+        assert!(check(output, "; CHECK: b\n; CHECK: a").is_err());
+        assert!(check(output, "; CHECK: a\n; CHECK-NEXT: c").is_err());
+        assert!(check(output, "; CHECK: a\n; CHECK-NOT: b\n; CHECK: c").is_err());
+        assert!(check(output, "; CHECK-NOT: c").is_err());
+    }
+}
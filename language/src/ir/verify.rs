@@ -0,0 +1,664 @@
+//! Structural checks on a `Cfg`, meant to run between passes.
+//!
+//! A block holds exactly one terminator by construction, so what is
+//! checked here is everything the types do not enforce: edges agreeing
+//! with terminators, phis at the top of their block with one operand per
+//! predecessor, values defined before they are used, and operand types.
+//! Operands must have the type of what they flow into, except the source
+//! of a `SingleAssign`, which converts between integer types.
+
+use std::fmt::Display;
+
+use miette::{Diagnostic, NamedSource, SourceSpan};
+
+use crate::{
+    analysis::dominators::DomTree,
+    frontend::{cfg::Cfg, typeck::is_integer},
+    types::designators::TypeInstance,
+};
+
+use super::{
+    text::{listing, print_instruction, print_terminator, print_value, Listing},
+    BlockId, Instruction, Operand, Terminator, ValueId,
+};
+
+#[derive(Debug, Diagnostic)]
+pub enum VerifyError {
+    #[diagnostic(
+        code(xlang::ir::verify::edges),
+        help("edges are only meant to change through `Cfg::set_term`")
+    )]
+    Edges {
+        func: String,
+        block: BlockId,
+        what: &'static str,
+        expected: String,
+        found: String,
+        #[source_code]
+        ir: NamedSource<String>,
+        #[label("{what} recorded for this block")]
+        span: SourceSpan,
+    },
+    #[diagnostic(
+        code(xlang::ir::verify::phi),
+        help("phis must come before every other instruction of their block")
+    )]
+    PhiPlacement {
+        func: String,
+        block: BlockId,
+        instr: String,
+        #[source_code]
+        ir: NamedSource<String>,
+        #[label("follows a non-phi")]
+        span: SourceSpan,
+    },
+    #[diagnostic(
+        code(xlang::ir::verify::phi),
+        help("a phi takes exactly one operand from each predecessor")
+    )]
+    PhiIncoming {
+        func: String,
+        block: BlockId,
+        instr: String,
+        preds: String,
+        #[source_code]
+        ir: NamedSource<String>,
+        #[label("expected [{preds}]")]
+        span: SourceSpan,
+    },
+    #[diagnostic(
+        code(xlang::ir::verify::undefined),
+        help("assign the value before its first use, or make it a parameter")
+    )]
+    Undefined {
+        func: String,
+        block: BlockId,
+        value: String,
+        #[source_code]
+        ir: NamedSource<String>,
+        #[label("used here")]
+        span: SourceSpan,
+    },
+    #[diagnostic(
+        code(xlang::ir::verify::redefined),
+        help("only moves (`<-`) may assign a value more than once")
+    )]
+    Redefined {
+        func: String,
+        value: String,
+        #[source_code]
+        ir: NamedSource<String>,
+        #[label("declared here")]
+        span: SourceSpan,
+    },
+    #[diagnostic(
+        code(xlang::ir::verify::dominance),
+        help("every path from the entry must pass the definition before reaching the use")
+    )]
+    NotDominated {
+        func: String,
+        block: BlockId,
+        value: String,
+        user: String,
+        #[source_code]
+        ir: NamedSource<String>,
+        #[label("`{value}` used here")]
+        span: SourceSpan,
+    },
+    #[diagnostic(
+        code(xlang::ir::verify::types),
+        help("only `=` from a single operand converts between integer types")
+    )]
+    TypeMismatch {
+        func: String,
+        block: BlockId,
+        user: String,
+        expected: String,
+        found: String,
+        #[source_code]
+        ir: NamedSource<String>,
+        #[label("found `{found}`")]
+        span: SourceSpan,
+    },
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::Edges {
+                func,
+                block,
+                what,
+                expected,
+                found,
+                ..
+            } => write!(
+                f,
+                "in `{func}`, {block} has {what} [{found}] but its edges imply [{expected}]"
+            ),
+            VerifyError::PhiPlacement {
+                func, block, instr, ..
+            } => {
+                write!(f, "in `{func}`, `{instr}` in {block} follows a non-phi")
+            }
+            VerifyError::PhiIncoming {
+                func,
+                block,
+                instr,
+                preds,
+                ..
+            } => write!(
+                f,
+                "in `{func}`, `{instr}` must name each predecessor of {block} once: [{preds}]"
+            ),
+            VerifyError::Undefined {
+                func, block, value, ..
+            } => {
+                write!(
+                    f,
+                    "in `{func}`, {block} uses `{value}` which is never defined"
+                )
+            }
+            VerifyError::Redefined { func, value, .. } => {
+                write!(f, "in `{func}`, `{value}` is defined more than once")
+            }
+            VerifyError::NotDominated {
+                func,
+                block,
+                value,
+                user,
+                ..
+            } => write!(
+                f,
+                "in `{func}`, the definition of `{value}` does not dominate `{user}` in {block}"
+            ),
+            VerifyError::TypeMismatch {
+                func,
+                block,
+                user,
+                expected,
+                found,
+                ..
+            } => write!(
+                f,
+                "in `{func}`, `{user}` in {block} expects `{expected}` but found `{found}`"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Checks the invariants described in the module docs and returns every
+/// violation found.
+pub fn verify(cfg: &Cfg) -> Result<(), Vec<VerifyError>> {
+    let mut verifier = Verifier {
+        cfg,
+        listing: listing(cfg),
+        errors: Vec::new(),
+    };
+    verifier.edges();
+    // Dominance is meaningless on a graph whose edges are wrong.
+    if verifier.errors.is_empty() {
+        verifier.phis();
+        verifier.definitions();
+        verifier.types();
+    }
+    if verifier.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(verifier.errors)
+    }
+}
+
+/// Verifies `cfg` in debug builds and panics with the rendered diagnostics,
+/// which quote the offending function, if it is malformed. `after` names the pass
+/// that produced it.
+pub fn debug_verify(cfg: &Cfg, after: &str) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    if let Err(errors) = verify(cfg) {
+        let mut message = format!("invalid IR after {after}:\n");
+        for error in errors {
+            message.push_str(&format!("{:?}\n", miette::Report::new(error)));
+        }
+        panic!("{message}");
+    }
+}
+
+fn list(blocks: &[BlockId]) -> String {
+    blocks
+        .iter()
+        .map(BlockId::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Where a value gets its value from.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Def {
+    Param,
+    Instr(BlockId, usize),
+}
+
+struct Verifier<'a> {
+    cfg: &'a Cfg,
+    /// The printed function the errors point into.
+    listing: Listing,
+    errors: Vec<VerifyError>,
+}
+
+impl Verifier<'_> {
+    fn func(&self) -> String {
+        self.cfg.name().to_string()
+    }
+
+    fn ir(&self) -> NamedSource<String> {
+        NamedSource::new(format!("fn {}", self.cfg.name()), self.listing.text.clone())
+    }
+
+    fn edges(&mut self) {
+        let cfg = self.cfg;
+        let mut preds = vec![Vec::new(); cfg.blocks().len()];
+        for block in cfg.blocks() {
+            let succs = block.term().successors();
+            let next = block.next().map(|n| n.ids()).unwrap_or_default();
+            if next != succs {
+                self.errors.push(VerifyError::Edges {
+                    func: self.func(),
+                    block: block.id(),
+                    what: "successors",
+                    expected: list(&succs),
+                    found: list(&next),
+                    ir: self.ir(),
+                    span: self.listing.line(block.id(), block.instrs().len()),
+                });
+            }
+            for succ in succs {
+                preds[succ.0].push(block.id());
+            }
+        }
+        for (block, mut expected) in preds.into_iter().enumerate() {
+            let block = BlockId(block);
+            let mut found = cfg.preds(block);
+            expected.sort();
+            found.sort();
+            if found != expected {
+                self.errors.push(VerifyError::Edges {
+                    func: self.func(),
+                    block,
+                    what: "predecessors",
+                    expected: list(&expected),
+                    found: list(&found),
+                    ir: self.ir(),
+                    span: self.listing.block(block),
+                });
+            }
+        }
+    }
+
+    fn phis(&mut self) {
+        let cfg = self.cfg;
+        for block in cfg.blocks() {
+            let mut preds = cfg.preds(block.id());
+            preds.sort();
+            let mut leading = true;
+            for (i, instr) in block.instrs().iter().enumerate() {
+                let Instruction::Phi(phi) = instr else {
+                    leading = false;
+                    continue;
+                };
+                if !leading {
+                    self.errors.push(VerifyError::PhiPlacement {
+                        func: self.func(),
+                        block: block.id(),
+                        instr: print_instruction(cfg, instr),
+                        ir: self.ir(),
+                        span: self.listing.line(block.id(), i),
+                    });
+                }
+                let mut incoming: Vec<BlockId> = phi.incoming.iter().map(|(b, _)| *b).collect();
+                incoming.sort();
+                if incoming != preds {
+                    self.errors.push(VerifyError::PhiIncoming {
+                        func: self.func(),
+                        block: block.id(),
+                        instr: print_instruction(cfg, instr),
+                        preds: list(&preds),
+                        ir: self.ir(),
+                        span: self.listing.line(block.id(), i),
+                    });
+                }
+            }
+        }
+    }
+
+    fn definitions(&mut self) {
+        let cfg = self.cfg;
+        let mut defs: Vec<Vec<Def>> = vec![Vec::new(); cfg.values().len()];
+        for param in cfg.params() {
+            defs[param.0].push(Def::Param);
+        }
+        for block in cfg.blocks() {
+            for (i, instr) in block.instrs().iter().enumerate() {
+                if let Some(dst) = instr.result() {
+                    defs[dst.0].push(Def::Instr(block.id(), i));
+                }
+            }
+        }
+        for (value, sites) in defs.iter().enumerate() {
+            let only_moves = sites.iter().all(|site| match site {
+                Def::Param => true,
+                Def::Instr(block, i) => {
+                    matches!(cfg.block(*block).instrs()[*i], Instruction::Mov(_))
+                }
+            });
+            if sites.len() > 1 && !only_moves {
+                self.errors.push(VerifyError::Redefined {
+                    func: self.func(),
+                    value: print_value(cfg, ValueId(value)),
+                    ir: self.ir(),
+                    span: self.listing.value(ValueId(value)),
+                });
+            }
+        }
+
+        let doms = DomTree::dominators(cfg);
+        let mut reported = vec![false; cfg.values().len()];
+        for block in doms.rpo() {
+            let data = cfg.block(*block);
+            let uses = data
+                .instrs()
+                .iter()
+                .enumerate()
+                .flat_map(|(i, instr)| {
+                    let user = print_instruction(cfg, instr);
+                    let ops: Vec<(Option<BlockId>, Operand)> = match instr {
+                        Instruction::Phi(phi) => {
+                            phi.incoming.iter().map(|(p, op)| (Some(*p), *op)).collect()
+                        }
+                        _ => instr.operands().into_iter().map(|op| (None, *op)).collect(),
+                    };
+                    ops.into_iter()
+                        .map(move |(pred, op)| (i, pred, op, user.clone()))
+                })
+                .chain(data.term().operands().into_iter().map(|op| {
+                    (
+                        data.instrs().len(),
+                        None,
+                        *op,
+                        print_terminator(cfg, data.term()),
+                    )
+                }));
+            for (at, pred, op, user) in uses {
+                let Operand::Value(value) = op else {
+                    continue;
+                };
+                let sites = &defs[value.0];
+                if sites.is_empty() {
+                    if !reported[value.0] {
+                        reported[value.0] = true;
+                        self.errors.push(VerifyError::Undefined {
+                            func: self.func(),
+                            block: *block,
+                            value: print_value(cfg, value),
+                            ir: self.ir(),
+                            span: self.listing.line(*block, at),
+                        });
+                    }
+                    continue;
+                }
+                // Values assigned by several moves are not in SSA form yet.
+                let [site] = sites[..] else {
+                    continue;
+                };
+                let dominated = match (site, pred) {
+                    (Def::Param, _) => true,
+                    // A phi operand is read at the end of its predecessor.
+                    (Def::Instr(def, _), Some(pred)) => doms.dominates(def, pred),
+                    (Def::Instr(def, i), None) if def == *block => i < at,
+                    (Def::Instr(def, _), None) => doms.strictly_dominates(def, *block),
+                };
+                if !dominated {
+                    self.errors.push(VerifyError::NotDominated {
+                        func: self.func(),
+                        block: *block,
+                        value: print_value(cfg, value),
+                        user,
+                        ir: self.ir(),
+                        span: self.listing.line(*block, at),
+                    });
+                }
+            }
+        }
+    }
+
+    fn types(&mut self) {
+        let cfg = self.cfg;
+        for block in cfg.blocks() {
+            for (i, instr) in block.instrs().iter().enumerate() {
+                let Some(dst) = instr.result() else {
+                    continue;
+                };
+                let expected = &cfg.value(dst)._type;
+                match instr {
+                    Instruction::Call(_) => continue,
+                    Instruction::SAssign(single)
+                        if is_integer(expected) && is_integer(&cfg.operand_type(&single.src)) =>
+                    {
+                        continue
+                    }
+                    _ => (),
+                }
+                for op in instr.operands() {
+                    self.expect((block.id(), i), expected, op, || {
+                        print_instruction(cfg, instr)
+                    });
+                }
+            }
+            let ret = cfg.ret_type();
+            let term = (block.id(), block.instrs().len());
+            match block.term() {
+                Terminator::Ret(Some(op)) => {
+                    self.expect(term, ret, op, || print_terminator(cfg, block.term()))
+                }
+                Terminator::Ret(None) if *ret != TypeInstance::Void => {
+                    self.errors.push(VerifyError::TypeMismatch {
+                        func: self.func(),
+                        block: block.id(),
+                        user: print_terminator(cfg, block.term()),
+                        expected: ret.to_string(),
+                        found: TypeInstance::Void.to_string(),
+                        ir: self.ir(),
+                        span: self.listing.line(term.0, term.1),
+                    })
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Checks the operand of the instruction at `(block, index)`.
+    fn expect(
+        &mut self,
+        (block, index): (BlockId, usize),
+        expected: &TypeInstance,
+        op: &Operand,
+        user: impl Fn() -> String,
+    ) {
+        let found = self.cfg.operand_type(op);
+        if found != *expected {
+            self.errors.push(VerifyError::TypeMismatch {
+                func: self.func(),
+                block,
+                user: user(),
+                expected: expected.to_string(),
+                found: found.to_string(),
+                ir: self.ir(),
+                span: self.listing.line(block, index),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use miette::{Diagnostic, GraphicalReportHandler, GraphicalTheme, Report};
+
+    use crate::ir::text::parse;
+
+    use super::{verify, VerifyError};
+
+    fn verify_text(text: &str) -> Vec<VerifyError> {
+        let cfgs = parse(text).unwrap();
+        verify(&cfgs[0]).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn well_formed_ir_passes() {
+        let errors = verify_text(
+            "fn f(int %c) -> int {
+                int %y
+                int %y1
+                int %y2
+            bb0:
+                branch %c, bb1, bb2
+            bb1:
+                %y = int 1
+                jump bb3
+            bb2:
+                %y1 = %c + int 2
+                jump bb3
+            bb3:
+                %y2 = phi [bb1: %y, bb2: %y1]
+                ret %y2
+            }",
+        );
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn phis_must_lead_and_match_predecessors() {
+        let errors = verify_text(
+            "fn f(int %c) -> int {
+                int %x
+                int %y
+            bb0:
+                branch %c, bb1, bb2
+            bb1:
+                jump bb2
+            bb2:
+                %x = int 1
+                %y = phi [bb1: %x]
+                ret %y
+            }",
+        );
+        assert!(matches!(errors[0], VerifyError::PhiPlacement { .. }));
+        assert_eq!(
+            errors[1].to_string(),
+            "in `f`, `%y = phi [bb1: %x]` must name each predecessor of bb2 once: [bb0, bb1]"
+        );
+    }
+
+    #[test]
+    fn definitions_must_dominate_uses() {
+        let errors = verify_text(
+            "fn f(int %c) -> int {
+                int %y
+                int %u
+                int %t
+            bb0:
+                branch %c, bb1, bb2
+            bb1:
+                %y = int 1
+                %t = int 2
+                %t = int 3
+                jump bb2
+            bb2:
+                ret %y
+            bb3:
+                ret %u
+            }",
+        );
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0].to_string(),
+            "in `f`, `%t` is defined more than once"
+        );
+        assert_eq!(
+            errors[1].to_string(),
+            "in `f`, the definition of `%y` does not dominate `ret %y` in bb2"
+        );
+
+        // The use of `%u` in the unreachable bb3 above is not reported.
+        let errors = verify_text(
+            "fn f() -> int {
+                int %u
+            bb0:
+                ret %u
+            }",
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "in `f`, bb0 uses `%u` which is never defined"
+        );
+    }
+
+    #[test]
+    fn operand_types_must_agree() {
+        let errors = verify_text(
+            "fn f(long %l) -> void {
+                char %c
+            bb0:
+                %c <- %l
+                %c <- char 1
+                ret %c
+            }",
+        );
+        assert_eq!(errors.len(), 2);
+        assert!(
+            verify_text("fn f(long %l) -> char {\n char %c\nbb0:\n %c = %l\n ret %c\n}").is_empty()
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "in `f`, `%c <- %l` in bb0 expects `char` but found `long`"
+        );
+        assert_eq!(
+            errors[1].to_string(),
+            "in `f`, `ret %c` in bb0 expects `void` but found `char`"
+        );
+    }
+
+    #[test]
+    fn errors_point_into_the_printed_function() {
+        let mut errors = verify_text(
+            "fn f(long %l) -> char {
+                char %c
+                char %d
+            bb0:
+                %c <- %l
+                ret %d
+            }",
+        );
+        assert_eq!(errors.len(), 2);
+        let labels: Vec<_> = errors
+            .iter()
+            .map(|error| error.labels().unwrap().next().unwrap())
+            .collect();
+        let text = "fn f(long %l) -> char {\n    char %c\n    char %d\nbb0:\n    %c <- %l\n    ret %d\n}\n";
+        let quoted = |offset: usize, len: usize| &text[offset..offset + len];
+        assert_eq!(quoted(labels[0].offset(), labels[0].len()), "ret %d");
+        assert_eq!(quoted(labels[1].offset(), labels[1].len()), "%c <- %l");
+        assert_eq!(labels[1].label(), Some("found `long`"));
+
+        let report = Report::new(errors.remove(0));
+        let mut out = String::new();
+        GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor())
+            .render_report(&mut out, report.as_ref())
+            .unwrap();
+        assert!(out.contains("[fn f:6:5]"));
+        assert!(out.contains("used here"));
+        assert!(out.contains("assign the value before its first use"));
+    }
+}
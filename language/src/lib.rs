@@ -0,0 +1,5 @@
+pub mod analysis;
+pub mod backend;
+pub mod frontend;
+pub mod ir;
+pub mod types;
@@ -1,6 +0,0 @@
-mod frontend;
-mod types;
-
-fn main() {
-    println!("Hello!");
-}
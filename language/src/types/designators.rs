@@ -1,17 +1,50 @@
 use std::fmt::Display;
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum TypeInstance {
     Int,
     Char,
     Short,
     Long,
     LongLong,
+    /// `signed char`, distinct from plain `char` whose signedness is up to
+    /// the target.
+    SChar,
+    UChar,
+    UShort,
+    UInt,
+    ULong,
+    ULongLong,
     Func(Box<TypeInstance>, String, Option<Vec<TypeInstance>>),
     Ptr(Box<TypeInstance>),
     Void,
 }
 
+impl TypeInstance {
+    pub fn is_unsigned(&self) -> bool {
+        matches!(
+            self,
+            TypeInstance::UChar
+                | TypeInstance::UShort
+                | TypeInstance::UInt
+                | TypeInstance::ULong
+                | TypeInstance::ULongLong
+        )
+    }
+
+    /// The unsigned integer type of the same rank, anything else unchanged.
+    pub fn to_unsigned(&self) -> TypeInstance {
+        match self {
+            TypeInstance::Char | TypeInstance::SChar => TypeInstance::UChar,
+            TypeInstance::Short => TypeInstance::UShort,
+            TypeInstance::Int => TypeInstance::UInt,
+            TypeInstance::Long => TypeInstance::ULong,
+            TypeInstance::LongLong => TypeInstance::ULongLong,
+            _type => _type.clone(),
+        }
+    }
+}
+
 impl Display for TypeInstance {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -20,6 +53,12 @@ impl Display for TypeInstance {
             TypeInstance::Short => write!(f, "short"),
             TypeInstance::Long => write!(f, "long"),
             TypeInstance::LongLong => write!(f, "long long"),
+            TypeInstance::SChar => write!(f, "signed char"),
+            TypeInstance::UChar => write!(f, "unsigned char"),
+            TypeInstance::UShort => write!(f, "unsigned short"),
+            TypeInstance::UInt => write!(f, "unsigned int"),
+            TypeInstance::ULong => write!(f, "unsigned long"),
+            TypeInstance::ULongLong => write!(f, "unsigned long long"),
             TypeInstance::Void => write!(f, "void"),
             TypeInstance::Func(_type, name, params) => {
                 write!(f, "{} {}", _type, name)?;
@@ -30,7 +69,7 @@ impl Display for TypeInstance {
                 } else {
                     write!(f, "()")?;
                 }
-                writeln!(f, "")
+                writeln!(f)
             }
             TypeInstance::Ptr(_type) => {
                 write!(f, "*{}", _type)
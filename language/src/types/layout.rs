@@ -0,0 +1,115 @@
+//! Sizes, alignments and signedness of types on the target.
+
+use super::designators::TypeInstance;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TargetLayout {
+    pub short: u64,
+    pub int: u64,
+    pub long: u64,
+    pub long_long: u64,
+    pub pointer: u64,
+    /// Whether plain `char` is signed.
+    pub char_signed: bool,
+}
+
+impl TargetLayout {
+    /// 64-bit Unix targets such as x86-64 Linux: `long` and pointers are
+    /// 8 bytes.
+    pub const LP64: TargetLayout = TargetLayout {
+        short: 2,
+        int: 4,
+        long: 8,
+        long_long: 8,
+        pointer: 8,
+        char_signed: true,
+    };
+
+    /// 32-bit targets such as i386: `int`, `long` and pointers are 4 bytes.
+    pub const ILP32: TargetLayout = TargetLayout {
+        short: 2,
+        int: 4,
+        long: 4,
+        long_long: 8,
+        pointer: 4,
+        char_signed: true,
+    };
+
+    /// Size in bytes, `None` for `void` and functions, which have none.
+    pub fn size_of(&self, _type: &TypeInstance) -> Option<u64> {
+        match _type {
+            TypeInstance::Char | TypeInstance::SChar | TypeInstance::UChar => Some(1),
+            TypeInstance::Short | TypeInstance::UShort => Some(self.short),
+            TypeInstance::Int | TypeInstance::UInt => Some(self.int),
+            TypeInstance::Long | TypeInstance::ULong => Some(self.long),
+            TypeInstance::LongLong | TypeInstance::ULongLong => Some(self.long_long),
+            TypeInstance::Ptr(_) => Some(self.pointer),
+            TypeInstance::Func(..) | TypeInstance::Void => None,
+        }
+    }
+
+    /// Every scalar is aligned to its size.
+    pub fn align_of(&self, _type: &TypeInstance) -> Option<u64> {
+        self.size_of(_type)
+    }
+
+    /// The type of `sizeof`, the unsigned integer type as wide as a pointer.
+    pub fn size_type(&self) -> TypeInstance {
+        if self.pointer == self.int {
+            TypeInstance::UInt
+        } else {
+            TypeInstance::ULong
+        }
+    }
+
+    /// Whether an integer type is signed, `None` for anything else.
+    pub fn is_signed(&self, _type: &TypeInstance) -> Option<bool> {
+        match _type {
+            TypeInstance::Char => Some(self.char_signed),
+            TypeInstance::SChar
+            | TypeInstance::Short
+            | TypeInstance::Int
+            | TypeInstance::Long
+            | TypeInstance::LongLong => Some(true),
+            TypeInstance::UChar
+            | TypeInstance::UShort
+            | TypeInstance::UInt
+            | TypeInstance::ULong
+            | TypeInstance::ULongLong => Some(false),
+            TypeInstance::Func(..) | TypeInstance::Ptr(_) | TypeInstance::Void => None,
+        }
+    }
+}
+
+impl Default for TargetLayout {
+    fn default() -> Self {
+        Self::LP64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::designators::TypeInstance;
+
+    use super::TargetLayout;
+
+    #[test]
+    fn lp64_and_ilp32() {
+        let ptr = TypeInstance::Ptr(Box::new(TypeInstance::Char));
+        let lp64 = TargetLayout::default();
+        assert_eq!(lp64.size_of(&TypeInstance::Short), Some(2));
+        assert_eq!(lp64.size_of(&TypeInstance::ULong), Some(8));
+        assert_eq!(lp64.size_of(&ptr), Some(8));
+        assert_eq!(lp64.align_of(&ptr), Some(8));
+        assert!(lp64.size_of(&TypeInstance::Void).is_none());
+
+        let ilp32 = TargetLayout::ILP32;
+        assert_eq!(ilp32.size_of(&TypeInstance::Long), Some(4));
+        assert_eq!(ilp32.size_of(&TypeInstance::LongLong), Some(8));
+        assert_eq!(ilp32.align_of(&ptr), Some(4));
+
+        assert_eq!(lp64.is_signed(&TypeInstance::Char), Some(true));
+        assert_eq!(lp64.is_signed(&TypeInstance::UChar), Some(false));
+        assert!(lp64.is_signed(&ptr).is_none());
+    }
+}
@@ -1 +1,2 @@
 pub mod designators;
+pub mod layout;